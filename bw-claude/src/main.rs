@@ -1,7 +1,11 @@
 //! Bubblewrap sandboxing wrapper for Claude CLI
 
 use anyhow::{Context, Result};
-use bwrap_core::{CommonArgs, ConfigLoader, HomeAccessMode, SandboxBuilder, SandboxConfig, ToolConfig, setup_policy};
+use bwrap_core::{
+    AdminCommand, CommitMode, CommonArgs, ConfigLoader, HomeAccessMode, LockedHosts, PermissionSet,
+    SandboxBuilder, SandboxConfig, SandboxLock, ToolConfig, UserMode, setup_policy,
+};
+use bwrap_proxy::PolicyEngine;
 use clap::Parser;
 use std::collections::HashMap;
 use std::env;
@@ -14,6 +18,10 @@ use std::path::PathBuf;
     version
 )]
 struct Args {
+    /// Manage policy/group config instead of launching Claude
+    #[command(subcommand)]
+    admin: Option<AdminCommand>,
+
     /// Disable --dangerously-skip-permissions for Claude
     #[arg(long)]
     no_skip_permissions: bool,
@@ -24,7 +32,20 @@ struct Args {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    if let Some(admin) = args.admin {
+        return bwrap_core::policy_admin::run(admin, args.common.proxy_config.as_deref())
+            .context("Policy/group admin command failed");
+    }
+
+    // Apply the system/user/project config layer chain on top of the
+    // parsed CLI flags (CLI always wins; see `bwrap_core::config::layer`),
+    // then pull Claude's own `[claude]` overrides out of what it loaded.
+    let config_layer = bwrap_core::apply_layered_config(&mut args.common)
+        .context("Failed to apply layered config")?;
+    args.no_skip_permissions =
+        bwrap_core::apply_tool_bool(args.no_skip_permissions, &config_layer, "claude", "no_skip_permissions");
 
     // Initialize logging - only if BW_LOG env var or verbose flag
     let _ = if args.common.verbose || env::var("BW_LOG").is_ok() {
@@ -76,6 +97,27 @@ async fn main() -> Result<()> {
         .await
         .context("Failed to set up policy")?;
 
+    // Reproducible grant-set lockfile: if requested, verify the
+    // freshly-resolved grant set against any existing lock before mounting
+    // anything, then refresh the lock to match this run.
+    if let Some(lockfile_path) = &args.common.lockfile {
+        let (allow_hosts, deny_hosts) = PolicyEngine::expand_hostnames(
+            &policy_setup.policy.network.effective_allow_groups(),
+            &policy_setup.policy.network.deny_groups,
+            &app_config.network,
+        )
+        .context("Failed to expand policy host groups for lockfile")?;
+
+        SandboxLock::enforce(
+            lockfile_path,
+            &policy_setup.filesystem_spec,
+            &policy_setup.network_mode,
+            &LockedHosts { allow: allow_hosts, deny: deny_hosts, learned: vec![] },
+            args.common.allow_lock_drift,
+        )
+        .context("Sandbox grant set lockfile check failed")?;
+    }
+
     // Determine target directory
     let target_dir = if let Some(dir) = args.common.dir.as_ref() {
         dir.canonicalize()
@@ -98,6 +140,19 @@ async fn main() -> Result<()> {
             .to_string(),
     };
 
+    // Granular --allow-read/--allow-write/--deny-read/--deny-write/
+    // --allow-run rules (see `bwrap_core::permissions`)
+    let home_dir = env::var("HOME").context("HOME environment variable not set")?;
+    let permissions = PermissionSet::from_args(
+        &args.common.allow_read,
+        &args.common.allow_write,
+        &args.common.deny_read,
+        &args.common.deny_write,
+        &args.common.allow_run,
+        args.common.full_home_access,
+        &PathBuf::from(home_dir),
+    );
+
     // Build sandbox configuration
     let config = SandboxConfig {
         tool_name: "claude".to_string(),
@@ -110,6 +165,10 @@ async fn main() -> Result<()> {
         } else {
             HomeAccessMode::Safe
         },
+        user_mode: match (args.common.map_uid, args.common.map_gid) {
+            (Some(uid), Some(gid)) => UserMode::Mapped { uid, gid },
+            _ => UserMode::Host,
+        },
         additional_ro_paths: args.common.allow_ro_paths,
         additional_rw_paths: args.common.allow_rw_paths,
         env_vars: HashMap::new(),
@@ -117,10 +176,15 @@ async fn main() -> Result<()> {
         verbose: args.common.verbose,
         shell: args.common.shell,
         bw_relay_path: args.common.bw_relay_path,
+        permissions,
+        seccomp: policy_setup.seccomp_spec,
+        commit_mode: CommitMode::Direct,
+        pty: args.common.pty,
     };
 
     // Build and execute sandbox
-    let sandbox = SandboxBuilder::new(config, policy_setup.filesystem_spec)
+    let security_policy = app_config.security.tools.get("claude");
+    let sandbox = SandboxBuilder::new(config, policy_setup.filesystem_spec, security_policy)
         .context("Failed to create sandbox builder")?
         .build()
         .context("Failed to build sandbox")?;
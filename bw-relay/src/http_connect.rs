@@ -13,6 +13,22 @@ pub enum RequestType {
     Forward { host: String, port: u16 },
 }
 
+/// What to tell an HTTP client when bw-proxy deliberately refuses a
+/// destination (a `BLOCKED` UDS reply), as opposed to `send_error_response`'s
+/// generic 502 for a genuine connection/protocol failure talking to the
+/// proxy. A plain 502 looks like transient upstream trouble; these make a
+/// policy denial unambiguous, and `Reset` lets a tool's retry logic react
+/// differently than it would to an error page.
+#[derive(Debug, Clone)]
+pub enum BlockAction {
+    /// A clearly-labeled 403 naming the denied host (the default)
+    BlockPage,
+    /// Close the connection without sending any response
+    Reset,
+    /// Serve this file's contents verbatim as the body of a 403
+    Custom(Vec<u8>),
+}
+
 /// Parse HTTP request from a stream
 ///
 /// Handles two formats:
@@ -155,10 +171,39 @@ pub async fn send_error_response(
     status: u16,
     message: &str,
 ) -> anyhow::Result<()> {
-    let response = format!(
-        "HTTP/1.1 {status} {message}\r\nContent-Length: 0\r\n\r\n"
+    send_response(stream, status, message, &[]).await
+}
+
+/// Send the client a response for a request bw-proxy deliberately blocked,
+/// per the relay's configured `--block-action` — see [`BlockAction`].
+pub async fn send_blocked_response(
+    stream: &mut TcpStream,
+    host: &str,
+    action: &BlockAction,
+) -> anyhow::Result<()> {
+    match action {
+        BlockAction::Reset => Ok(()),
+        BlockAction::BlockPage => {
+            let body = format!("Blocked by policy: connections to {host} are not permitted.\n");
+            send_response(stream, 403, "Forbidden", body.as_bytes()).await
+        }
+        BlockAction::Custom(contents) => send_response(stream, 403, "Forbidden", contents).await,
+    }
+}
+
+/// Send a complete HTTP response with the given status, reason phrase, and body
+async fn send_response(
+    stream: &mut TcpStream,
+    status: u16,
+    message: &str,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {status} {message}\r\nContent-Length: {len}\r\nConnection: close\r\n\r\n",
+        len = body.len()
     );
-    stream.write_all(response.as_bytes()).await?;
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(body).await?;
     stream.flush().await?;
     Ok(())
 }
@@ -1,8 +1,20 @@
+use anyhow::Context;
 use clap::Parser;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::Arc;
 
 mod http_connect;
+mod proxy_child;
+mod proxy_protocol;
+mod socks5;
+
+/// Version of the text wire protocol spoken to bw-proxy over the UDS
+/// control socket (see `forward_to_proxy`). Must match
+/// `bwrap_proxy::RELAY_PROTOCOL_VERSION` — kept as a literal here rather
+/// than a dependency on bwrap-proxy, since bw-relay is built to run
+/// standalone inside the sandbox with no internal crate dependencies.
+const RELAY_PROTOCOL_VERSION: u32 = 1;
 
 #[derive(Parser, Debug)]
 #[command(name = "bw-relay")]
@@ -12,10 +24,57 @@ struct Args {
     #[arg(long, default_value = "3128")]
     http_port: u16,
 
+    /// SOCKS5 listening port, for tools that only honor ALL_PROXY/SOCKS and
+    /// not HTTP_PROXY. Disabled (no SOCKS5 front-end) unless set.
+    #[arg(long)]
+    socks_port: Option<u16>,
+
     /// Unix domain socket path to connect to (optional - if not provided, just executes target command)
     #[arg(long)]
     socket: Option<PathBuf>,
 
+    /// Path to a bw-proxy binary to spawn and supervise instead of
+    /// connecting to a pre-existing `--socket`. bw-relay picks a private
+    /// socket path, launches bw-proxy against it, and kills it when bw-relay
+    /// exits. Mutually exclusive with `--socket`.
+    #[arg(long, conflicts_with = "socket")]
+    spawn_proxy: Option<PathBuf>,
+
+    /// Proxy mode to pass to a `--spawn-proxy`'d bw-proxy (same syntax as
+    /// bw-proxy's own `--mode`: open | learning | restrictive:<policy>).
+    /// Ignored without `--spawn-proxy`.
+    #[arg(long, default_value = "restrictive:default")]
+    proxy_mode: String,
+
+    /// Config file path to pass to a `--spawn-proxy`'d bw-proxy's `--config`.
+    /// Ignored without `--spawn-proxy`.
+    #[arg(long)]
+    proxy_config: Option<PathBuf>,
+
+    /// What to send an HTTP client when bw-proxy deliberately denies a
+    /// request, as opposed to a genuine proxy-connection failure (which
+    /// always gets a 502): "block-page" (default) returns a clearly-labeled
+    /// 403 naming the denied host, "reset" closes the connection without a
+    /// response, "custom" serves --block-response-file verbatim as a 403 body
+    #[arg(long, default_value = "block-page")]
+    block_action: String,
+
+    /// File served verbatim as the 403 body when --block-action=custom.
+    /// Ignored otherwise.
+    #[arg(long)]
+    block_response_file: Option<PathBuf>,
+
+    /// Prepend a PROXY protocol v2 header (encoding the client's real
+    /// `peer_addr` as the source and the CONNECT destination as the
+    /// destination) ahead of the usual `CONNECT host port\n` line on each
+    /// UDS connection to bw-proxy, so its audit logging can tie every
+    /// allowed/blocked request back to a concrete client endpoint. Off by
+    /// default: the legacy text-only handshake is what every bw-proxy
+    /// build understands, so this stays opt-in for pairings where the
+    /// proxy side has been updated to expect it.
+    #[arg(long)]
+    proxy_protocol_v2: bool,
+
     /// Enable debug logging
     #[arg(long, short = 'v')]
     verbose: bool,
@@ -53,20 +112,78 @@ async fn main() -> anyhow::Result<()> {
             .try_init()
     };
 
-    // Handle proxy mode (when socket is provided)
-    if let Some(ref socket_path) = args.socket {
+    let block_action = Arc::new(match args.block_action.as_str() {
+        "block-page" => http_connect::BlockAction::BlockPage,
+        "reset" => http_connect::BlockAction::Reset,
+        "custom" => {
+            let path = args.block_response_file.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("--block-action=custom requires --block-response-file")
+            })?;
+            let contents = std::fs::read(path)
+                .with_context(|| format!("Failed to read --block-response-file {path:?}"))?;
+            http_connect::BlockAction::Custom(contents)
+        }
+        other => anyhow::bail!("Invalid block action: {other}. Use 'block-page', 'reset', or 'custom'"),
+    });
+
+    // Either attach to a pre-existing proxy socket (`--socket`, the usual
+    // case where something else launched bw-proxy) or spawn and supervise
+    // our own (`--spawn-proxy`) — mutually exclusive per the clap
+    // `conflicts_with` above. `proxy_child` must stay alive for as long as
+    // `socket_path` is in use; it kills the spawned bw-proxy on drop.
+    let mut proxy_child = None;
+    let socket_path = if let Some(proxy_binary) = &args.spawn_proxy {
+        let child = proxy_child::ProxyChild::spawn(proxy_binary, &args.proxy_mode, args.proxy_config.as_deref())
+            .await
+            .context("Failed to spawn and supervise bw-proxy")?;
+        let socket_path = child.socket_path().to_path_buf();
+        proxy_child = Some(child);
+        Some(socket_path)
+    } else {
+        args.socket.clone()
+    };
+
+    // Handle proxy mode (when a socket, spawned or pre-existing, is available)
+    if let Some(ref socket_path) = socket_path {
         tracing::info!(
             "Starting bw-relay: HTTP on :{}, UDS at {:?}",
             args.http_port,
             socket_path
         );
 
+        // Agree on a wire protocol version with bw-proxy before trusting any
+        // of the rest of the protocol, so a stale relay (or proxy) binary
+        // refuses clearly here instead of failing mysteriously on the first
+        // real CONNECT.
+        check_protocol_version(socket_path).await?;
+
         // Spawn HTTP server for proxy mode
         let uds_path_http = socket_path.clone();
+        let http_block_action = block_action.clone();
+        let use_proxy_protocol_v2 = args.proxy_protocol_v2;
         let http_handle = tokio::spawn(async move {
-            run_http_server("127.0.0.1", args.http_port, &uds_path_http).await
+            run_http_server(
+                "127.0.0.1",
+                args.http_port,
+                &uds_path_http,
+                http_block_action,
+                use_proxy_protocol_v2,
+            )
+            .await
         });
 
+        // Spawn the SOCKS5 front-end too, if requested, so tools that only
+        // honor ALL_PROXY/SOCKS still get routed through the same UDS policy
+        // tunnel as the HTTP CONNECT front-end above.
+        let socks_handle = if let Some(socks_port) = args.socks_port {
+            let uds_path_socks = socket_path.clone();
+            Some(tokio::spawn(async move {
+                run_socks_server("127.0.0.1", socks_port, &uds_path_socks, use_proxy_protocol_v2).await
+            }))
+        } else {
+            None
+        };
+
         // If a target command is provided, execute it after a brief delay to allow servers to start
         if !target_command.is_empty() {
             // Wait a bit for servers to bind and start listening
@@ -76,25 +193,37 @@ async fn main() -> anyhow::Result<()> {
 
             // Set up proxy environment variables
             let http_proxy = format!("http://127.0.0.1:{}", args.http_port);
+            let socks_proxy = args.socks_port.map(|port| format!("socks5://127.0.0.1:{port}"));
 
             // Execute the target command with proxy env vars
-            let status = execute_command(&target_command, &http_proxy)?;
+            let status = execute_command(&target_command, &http_proxy, socks_proxy.as_deref())?;
 
-            // Drop the handle to stop the server
+            // Drop the handles to stop the servers, and any spawned bw-proxy
+            // child, before exiting — `std::process::exit` skips destructors
+            // entirely, so this has to happen explicitly rather than by
+            // letting `proxy_child` fall out of scope.
             http_handle.abort();
+            if let Some(handle) = socks_handle {
+                handle.abort();
+            }
+            drop(proxy_child);
 
             std::process::exit(status.code().unwrap_or(1));
         }
 
-        // If no target command, wait for the server to run forever
-        http_handle.await??;
+        // If no target command, wait for the server(s) to run forever
+        if let Some(socks_handle) = socks_handle {
+            tokio::try_join!(flatten(http_handle), flatten(socks_handle))?;
+        } else {
+            http_handle.await??;
+        }
     } else {
         // Non-proxy mode: just execute the target command if provided
         if !target_command.is_empty() {
             tracing::info!("Executing target command (non-proxy mode): {:?}", target_command);
 
             // Execute the target command without proxy env vars
-            let status = execute_command(&target_command, "")?;
+            let status = execute_command(&target_command, "", None)?;
             std::process::exit(status.code().unwrap_or(1));
         } else {
             anyhow::bail!("No target command provided and no socket for proxy mode");
@@ -104,14 +233,26 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Join a spawned task and flatten its `JoinError` into the same
+/// `anyhow::Result` its body returns, so `tokio::try_join!` can wait on
+/// several front-end servers at once without a nested `Result<Result<_>>`.
+async fn flatten(handle: tokio::task::JoinHandle<anyhow::Result<()>>) -> anyhow::Result<()> {
+    handle.await?
+}
+
 /// Execute a target command and wait for it to complete
 ///
 /// The child process inherits the parent's signal handlers, so signals
 /// (SIGTERM, SIGINT, etc.) will be delivered to both parent and child.
 /// The child's exit status is propagated back to the caller.
 ///
-/// Sets HTTP proxy environment variables for the child process via Command builder.
-fn execute_command(cmd_parts: &[String], http_proxy: &str) -> anyhow::Result<std::process::ExitStatus> {
+/// Sets HTTP (and, if the SOCKS5 front-end is enabled, ALL_PROXY) proxy
+/// environment variables for the child process via Command builder.
+fn execute_command(
+    cmd_parts: &[String],
+    http_proxy: &str,
+    socks_proxy: Option<&str>,
+) -> anyhow::Result<std::process::ExitStatus> {
     if cmd_parts.is_empty() {
         anyhow::bail!("No command provided");
     }
@@ -127,6 +268,13 @@ fn execute_command(cmd_parts: &[String], http_proxy: &str) -> anyhow::Result<std
     cmd.env("HTTPS_PROXY", http_proxy);
     cmd.env("https_proxy", http_proxy);
 
+    // Tools that only honor ALL_PROXY/SOCKS (not HTTP_PROXY) pick up the
+    // SOCKS5 front-end the same way, if it's running.
+    if let Some(socks_proxy) = socks_proxy {
+        cmd.env("ALL_PROXY", socks_proxy);
+        cmd.env("all_proxy", socks_proxy);
+    }
+
     // Inherit stdio from parent so output goes to console
     cmd.stdin(std::process::Stdio::inherit());
     cmd.stdout(std::process::Stdio::inherit());
@@ -139,7 +287,56 @@ fn execute_command(cmd_parts: &[String], http_proxy: &str) -> anyhow::Result<std
     Ok(status)
 }
 
-async fn run_http_server(host: &str, port: u16, uds_path: &PathBuf) -> anyhow::Result<()> {
+/// Send a `VERSION <n>\n` probe over a short-lived connection to bw-proxy
+/// and require a matching `VERSION_OK <n>\n` back before proceeding.
+/// Refuses with a clear, specific error on a version mismatch or an
+/// unrecognized reply (an older bw-proxy that doesn't understand `VERSION`
+/// at all would reply `ERROR\n`, also handled here), rather than letting a
+/// skewed pair fail opaquely on the first real CONNECT.
+async fn check_protocol_version(uds_path: &PathBuf) -> anyhow::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+
+    let mut proxy = UnixStream::connect(uds_path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to connect to bw-proxy at {uds_path:?} for version check: {e}"))?;
+
+    let request = format!("VERSION {RELAY_PROTOCOL_VERSION}\n");
+    proxy.write_all(request.as_bytes()).await?;
+    proxy.flush().await?;
+
+    let mut response = [0u8; 64];
+    let n = proxy.read(&mut response).await?;
+    if n == 0 {
+        anyhow::bail!("bw-proxy closed the connection during version negotiation");
+    }
+    let response_str = String::from_utf8_lossy(&response[..n]);
+    let response_str = response_str.trim();
+
+    if response_str == format!("VERSION_OK {RELAY_PROTOCOL_VERSION}") {
+        tracing::debug!("Relay protocol version {RELAY_PROTOCOL_VERSION} confirmed by bw-proxy");
+        return Ok(());
+    }
+
+    if let Some(server_version) = response_str.strip_prefix("VERSION_MISMATCH ") {
+        anyhow::bail!(
+            "bw-relay speaks protocol version {RELAY_PROTOCOL_VERSION} but bw-proxy speaks version {server_version}; \
+             reinstall matching bw-relay/bw-proxy binaries"
+        );
+    }
+
+    anyhow::bail!(
+        "Unexpected response to version negotiation: {response_str:?} (is bw-proxy too old to understand VERSION?)"
+    );
+}
+
+async fn run_http_server(
+    host: &str,
+    port: u16,
+    uds_path: &PathBuf,
+    block_action: Arc<http_connect::BlockAction>,
+    use_proxy_protocol_v2: bool,
+) -> anyhow::Result<()> {
     let addr = format!("{}:{}", host, port)
         .parse::<std::net::SocketAddr>()?;
 
@@ -152,9 +349,12 @@ async fn run_http_server(host: &str, port: u16, uds_path: &PathBuf) -> anyhow::R
         tracing::debug!("HTTP CONNECT client connected: {peer_addr}");
 
         let uds_path = uds_path.clone();
+        let block_action = block_action.clone();
         // Spawn a task to handle this connection
         tokio::spawn(async move {
-            if let Err(e) = handle_http_client(socket, &uds_path).await {
+            if let Err(e) =
+                handle_http_client(socket, &uds_path, &block_action, peer_addr, use_proxy_protocol_v2).await
+            {
                 tracing::warn!("Error handling HTTP client {peer_addr}: {e}");
             }
         });
@@ -162,12 +362,29 @@ async fn run_http_server(host: &str, port: u16, uds_path: &PathBuf) -> anyhow::R
 }
 
 /// Handle an HTTP client connection
-async fn handle_http_client(client: tokio::net::TcpStream, uds_path: &PathBuf) -> anyhow::Result<()> {
+async fn handle_http_client(
+    client: tokio::net::TcpStream,
+    uds_path: &PathBuf,
+    block_action: &http_connect::BlockAction,
+    client_addr: std::net::SocketAddr,
+    use_proxy_protocol_v2: bool,
+) -> anyhow::Result<()> {
     // Parse the request (consumes client to extract buffered data)
     let (req_type, headers, buffered_extra, mut client) = http_connect::parse_connect_request(client).await?;
 
     // Forward to bw-proxy via UDS
-    match forward_to_proxy(&mut client, uds_path, req_type, headers, buffered_extra).await {
+    match forward_to_proxy(
+        &mut client,
+        uds_path,
+        req_type,
+        headers,
+        buffered_extra,
+        block_action,
+        client_addr,
+        use_proxy_protocol_v2,
+    )
+    .await
+    {
         Ok(_) => {
             tracing::debug!("Request handled");
             Ok(())
@@ -180,98 +397,194 @@ async fn handle_http_client(client: tokio::net::TcpStream, uds_path: &PathBuf) -
     }
 }
 
-/// Forward HTTP request to bw-proxy via UDS
-async fn forward_to_proxy(
-    client: &mut tokio::net::TcpStream,
+/// Outcome of dialing a destination through bw-proxy over the UDS control
+/// socket, distinguishing the proxy deliberately refusing the destination
+/// from a connection/protocol failure talking to the proxy itself — a
+/// client shouldn't see the same response for both (see `BlockAction`).
+enum ProxyOutcome {
+    Connected(tokio::net::UnixStream),
+    /// The proxy replied `BLOCKED`: the destination is denied by policy
+    Blocked,
+}
+
+/// Open a UDS connection to bw-proxy and perform its `CONNECT host port\n`
+/// handshake. Shared by every client-facing front-end (HTTP CONNECT, HTTP
+/// forward, SOCKS5) since they all tunnel through the same UDS protocol
+/// once the destination is known. Returns `Err` only for a genuine
+/// connection/protocol failure talking to bw-proxy (not for a deliberate
+/// policy denial, which is `Ok(ProxyOutcome::Blocked)`).
+///
+/// When `use_proxy_protocol_v2` is set, a PROXY protocol v2 header
+/// encoding `client_addr` as the source and `host`/`port` as the
+/// destination is sent ahead of the `CONNECT` line — see `proxy_protocol`.
+async fn connect_via_proxy(
     uds_path: &PathBuf,
-    req_type: http_connect::RequestType,
-    headers: Vec<u8>,
-    buffered_extra: Vec<u8>,
-) -> anyhow::Result<()> {
+    client_addr: std::net::SocketAddr,
+    host: &str,
+    port: u16,
+    use_proxy_protocol_v2: bool,
+) -> anyhow::Result<ProxyOutcome> {
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::UnixStream;
 
-    // Connect to bw-proxy via UDS
     tracing::debug!("Connecting to proxy at {uds_path:?}");
     let mut proxy = UnixStream::connect(uds_path).await?;
     tracing::debug!("Connected to proxy via UDS");
 
+    if use_proxy_protocol_v2 {
+        let header = proxy_protocol::encode_v2(client_addr, host, port);
+        proxy.write_all(&header).await?;
+    }
+
+    let proxy_request = format!("CONNECT {host} {port}\n");
+    tracing::debug!("Sending CONNECT to proxy: {proxy_request:?}");
+    proxy.write_all(proxy_request.as_bytes()).await?;
+    proxy.flush().await?;
+
+    // Read OK/BLOCKED/FAIL/ERROR response from proxy
+    let mut response = [0u8; 256];
+    let n = proxy.read(&mut response).await?;
+
+    if n == 0 {
+        anyhow::bail!("No response from proxy");
+    }
+
+    let response_str = String::from_utf8_lossy(&response[..n]);
+    let response_str = response_str.trim();
+    tracing::debug!("Proxy response: {response_str:?}");
+    if response_str.starts_with("OK") {
+        Ok(ProxyOutcome::Connected(proxy))
+    } else if response_str.starts_with("BLOCKED") {
+        Ok(ProxyOutcome::Blocked)
+    } else {
+        anyhow::bail!("Proxy rejected CONNECT: {response_str}");
+    }
+}
+
+/// Forward HTTP request to bw-proxy via UDS
+async fn forward_to_proxy(
+    client: &mut tokio::net::TcpStream,
+    uds_path: &PathBuf,
+    req_type: http_connect::RequestType,
+    headers: Vec<u8>,
+    buffered_extra: Vec<u8>,
+    block_action: &http_connect::BlockAction,
+    client_addr: std::net::SocketAddr,
+    use_proxy_protocol_v2: bool,
+) -> anyhow::Result<()> {
     match req_type {
         http_connect::RequestType::Connect { host, port } => {
             // HTTPS tunneling via CONNECT method
-            // Send CONNECT to UDS proxy (space-separated format: CONNECT host port)
-            let proxy_request = format!("CONNECT {host} {port}\n");
-            tracing::debug!("Sending CONNECT to proxy: {proxy_request:?}");
-            proxy.write_all(proxy_request.as_bytes()).await?;
-            proxy.flush().await?;
-
-            // Read OK/BLOCKED/etc response from proxy
-            let mut response = [0u8; 256];
-            let n = proxy.read(&mut response).await?;
-
-            if n == 0 {
-                anyhow::bail!("No response from proxy");
-            }
-
-            let response_str = String::from_utf8_lossy(&response[..n]);
-            tracing::debug!("Proxy response: {response_str:?}");
-            if response_str.starts_with("OK") {
-                // Send HTTP 200 Connection Established to client
-                http_connect::send_connect_success(client).await?;
-
-                // Write any pipelined data (e.g., TLS handshake) to proxy first
-                if !buffered_extra.is_empty() {
-                    tracing::debug!("Writing {len} bytes of pipelined data to proxy", len = buffered_extra.len());
-                    proxy.write_all(&buffered_extra).await?;
+            let mut proxy = match connect_via_proxy(uds_path, client_addr, &host, port, use_proxy_protocol_v2).await? {
+                ProxyOutcome::Connected(proxy) => proxy,
+                ProxyOutcome::Blocked => {
+                    return http_connect::send_blocked_response(client, &host, block_action).await;
                 }
+            };
 
-                // Tunnel bidirectionally between client and proxy (unbuffered)
-                tracing::debug!("Starting CONNECT tunnel between client and proxy");
-                tokio::io::copy_bidirectional(client, &mut proxy).await?;
-                tracing::debug!("Tunnel closed");
+            // Send HTTP 200 Connection Established to client
+            http_connect::send_connect_success(client).await?;
 
-                Ok(())
-            } else {
-                anyhow::bail!("Proxy rejected CONNECT: {response_str}");
+            // Write any pipelined data (e.g., TLS handshake) to proxy first
+            if !buffered_extra.is_empty() {
+                tracing::debug!("Writing {len} bytes of pipelined data to proxy", len = buffered_extra.len());
+                tokio::io::AsyncWriteExt::write_all(&mut proxy, &buffered_extra).await?;
             }
+
+            // Tunnel bidirectionally between client and proxy (unbuffered)
+            tracing::debug!("Starting CONNECT tunnel between client and proxy");
+            tokio::io::copy_bidirectional(client, &mut proxy).await?;
+            tracing::debug!("Tunnel closed");
+
+            Ok(())
         }
         http_connect::RequestType::Forward { host, port } => {
             // HTTP forward proxy - use CONNECT to establish tunnel, then forward request
-            let proxy_request = format!("CONNECT {host} {port}\n");
-            tracing::debug!("Sending CONNECT to proxy for HTTP: {proxy_request:?}");
-            proxy.write_all(proxy_request.as_bytes()).await?;
-            proxy.flush().await?;
+            let mut proxy = match connect_via_proxy(uds_path, client_addr, &host, port, use_proxy_protocol_v2).await? {
+                ProxyOutcome::Connected(proxy) => proxy,
+                ProxyOutcome::Blocked => {
+                    return http_connect::send_blocked_response(client, &host, block_action).await;
+                }
+            };
 
-            // Read OK/BLOCKED/etc response from proxy
-            let mut response = [0u8; 256];
-            let n = proxy.read(&mut response).await?;
+            // Forward the entire HTTP request headers to destination
+            tracing::debug!("Writing {len} bytes of HTTP headers to proxy", len = headers.len());
+            tokio::io::AsyncWriteExt::write_all(&mut proxy, &headers).await?;
 
-            if n == 0 {
-                anyhow::bail!("No response from proxy");
+            // Write any pipelined request body data to proxy
+            if !buffered_extra.is_empty() {
+                tracing::debug!("Writing {len} bytes of pipelined body to proxy", len = buffered_extra.len());
+                tokio::io::AsyncWriteExt::write_all(&mut proxy, &buffered_extra).await?;
             }
 
-            let response_str = String::from_utf8_lossy(&response[..n]);
-            tracing::debug!("Proxy response: {response_str:?}");
-            if response_str.starts_with("OK") {
-                // Forward the entire HTTP request headers to destination
-                tracing::debug!("Writing {len} bytes of HTTP headers to proxy", len = headers.len());
-                proxy.write_all(&headers).await?;
-
-                // Write any pipelined request body data to proxy
-                if !buffered_extra.is_empty() {
-                    tracing::debug!("Writing {len} bytes of pipelined body to proxy", len = buffered_extra.len());
-                    proxy.write_all(&buffered_extra).await?;
-                }
+            // Tunnel bidirectionally for response and any remaining data
+            tracing::debug!("Starting HTTP forward tunnel between client and proxy");
+            tokio::io::copy_bidirectional(client, &mut proxy).await?;
+            tracing::debug!("Tunnel closed");
+
+            Ok(())
+        }
+    }
+}
 
-                // Tunnel bidirectionally for response and any remaining data
-                tracing::debug!("Starting HTTP forward tunnel between client and proxy");
-                tokio::io::copy_bidirectional(client, &mut proxy).await?;
-                tracing::debug!("Tunnel closed");
+/// Listen for SOCKS5 clients and bridge each accepted CONNECT onto the same
+/// UDS proxy tunnel `handle_http_client` uses, for tools that only honor
+/// `ALL_PROXY`/SOCKS rather than `HTTP_PROXY`.
+async fn run_socks_server(
+    host: &str,
+    port: u16,
+    uds_path: &PathBuf,
+    use_proxy_protocol_v2: bool,
+) -> anyhow::Result<()> {
+    let addr = format!("{}:{}", host, port).parse::<std::net::SocketAddr>()?;
 
-                Ok(())
-            } else {
-                anyhow::bail!("Proxy rejected CONNECT for HTTP: {response_str}");
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("SOCKS5 proxy listening on {addr}");
+
+    let uds_path = uds_path.clone();
+    loop {
+        let (socket, peer_addr) = listener.accept().await?;
+        tracing::debug!("SOCKS5 client connected: {peer_addr}");
+
+        let uds_path = uds_path.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_socks_client(socket, &uds_path, peer_addr, use_proxy_protocol_v2).await {
+                tracing::warn!("Error handling SOCKS5 client {peer_addr}: {e}");
             }
+        });
+    }
+}
+
+/// Handle a single SOCKS5 client connection: greeting, CONNECT request,
+/// then bridge to bw-proxy via the shared UDS handshake.
+async fn handle_socks_client(
+    mut client: tokio::net::TcpStream,
+    uds_path: &PathBuf,
+    client_addr: std::net::SocketAddr,
+    use_proxy_protocol_v2: bool,
+) -> anyhow::Result<()> {
+    let request = socks5::handshake(&mut client).await?;
+
+    match connect_via_proxy(uds_path, client_addr, &request.host, request.port, use_proxy_protocol_v2).await {
+        Ok(ProxyOutcome::Connected(mut proxy)) => {
+            socks5::send_reply(&mut client, socks5::REP_SUCCEEDED).await?;
+
+            tracing::debug!("Starting SOCKS5 tunnel between client and proxy");
+            tokio::io::copy_bidirectional(&mut client, &mut proxy).await?;
+            tracing::debug!("Tunnel closed");
+
+            Ok(())
+        }
+        Ok(ProxyOutcome::Blocked) => {
+            // Distinguish the proxy deliberately refusing the destination
+            // from a connection/protocol failure below, same as
+            // `handle_http_client` does for its `--block-action`.
+            socks5::send_reply(&mut client, socks5::REP_CONNECTION_NOT_ALLOWED).await?;
+            Ok(())
+        }
+        Err(e) => {
+            socks5::send_reply(&mut client, socks5::REP_GENERAL_FAILURE).await?;
+            Err(e)
         }
     }
 }
@@ -0,0 +1,105 @@
+//! Spawn and supervise bw-proxy as a managed child process
+//!
+//! Normally bw-relay just connects to a pre-existing UDS (`--socket`) that
+//! something else already launched bw-proxy onto. `--spawn-proxy` instead
+//! has bw-relay launch bw-proxy itself: a private socket path is picked,
+//! the child is started against it, and bw-relay waits for it to actually
+//! be listening before starting its own front-ends. [`ProxyChild`] wraps
+//! the spawned child so that when it is dropped — the relay returning
+//! normally or with an error — the proxy is killed with it, instead of
+//! being left as an orphaned process sitting on a stale socket. A signal
+//! that ends bw-relay without unwinding does not run that drop, so the
+//! child is not killed in that case.
+//!
+//! `mode`/`config` are forwarded to the child's own `--mode`/`--config`
+//! verbatim rather than resolved here — bw-relay has no internal crate
+//! dependencies (see `RELAY_PROTOCOL_VERSION`'s doc comment in `main.rs`),
+//! so it can't link bwrap-core's config loader or bwrap-proxy's feed/anti-DoH
+//! augmentation to do that itself. That's fine: bw-proxy's own restrictive-mode
+//! startup applies feed/anti-DoH augmentation to whatever policy `--config`
+//! resolves, so a spawned child is covered the same way a daemon spawned by
+//! `bwrap_core::proxy_manager` is, without bw-relay needing to know about
+//! either.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::net::UnixStream;
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, Duration, Instant};
+
+/// How long to wait for a freshly spawned bw-proxy to bind its socket
+/// before giving up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A `bw-proxy` process bw-relay spawned and owns the lifetime of.
+///
+/// Dropping this kills the child (via `Command::kill_on_drop`) and removes
+/// its socket file, so callers just need to keep it alive for as long as
+/// they want the proxy running and let normal scope exit (or an explicit
+/// `drop`) tear it down.
+pub struct ProxyChild {
+    child: Child,
+    socket_path: PathBuf,
+}
+
+impl ProxyChild {
+    /// Spawn `proxy_binary` on a fresh private socket under `/tmp`, passing
+    /// `mode` and `config` through the same way bw-proxy's own CLI expects
+    /// them (`--mode`, `--config`), and block until it's accepting
+    /// connections.
+    pub async fn spawn(proxy_binary: &Path, mode: &str, config: Option<&Path>) -> anyhow::Result<Self> {
+        let socket_path = PathBuf::from(format!("/tmp/bw-relay-proxy-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+
+        let mut cmd = Command::new(proxy_binary);
+        cmd.arg("--socket").arg(&socket_path);
+        cmd.arg("--mode").arg(mode);
+        if let Some(config) = config {
+            cmd.arg("--config").arg(config);
+        }
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::null());
+        cmd.kill_on_drop(true);
+
+        let child = cmd
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn bw-proxy at {proxy_binary:?}: {e}"))?;
+
+        let proxy_child = Self { child, socket_path };
+        proxy_child.wait_until_listening().await?;
+        Ok(proxy_child)
+    }
+
+    async fn wait_until_listening(&self) -> anyhow::Result<()> {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if UnixStream::connect(&self.socket_path).await.is_ok() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "bw-proxy did not start listening on {:?} within {STARTUP_TIMEOUT:?}",
+                    self.socket_path
+                );
+            }
+            sleep(STARTUP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Socket path the spawned bw-proxy is listening on
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+}
+
+impl Drop for ProxyChild {
+    fn drop(&mut self) {
+        // `kill_on_drop(true)` above already arranges for `self.child` to
+        // be killed as part of this drop; this just additionally cleans up
+        // the socket file so a later relay invocation doesn't trip over a
+        // stale path left behind by a process that no longer exists.
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
@@ -0,0 +1,123 @@
+//! Build an optional PROXY protocol v2 header for the UDS hop to bw-proxy
+//!
+//! The plain `CONNECT host port\n` line `forward_to_proxy`/`handle_socks_client`
+//! send bw-proxy carries no information about which sandboxed client opened
+//! the connection — every tunnel looks the same in bw-proxy's logs. When
+//! `--proxy-protocol-v2` is set, [`encode_v2`] is used to prepend a binary
+//! PROXY protocol v2 header ahead of that line, encoding the client's own
+//! `peer_addr` as the source and the CONNECT destination as the
+//! destination, so bw-proxy can produce standardized, per-connection audit
+//! logs tying every allowed/blocked request back to a concrete client
+//! endpoint — the same technique ngrok-rust adopted via the
+//! `proxy-protocol` crate. The legacy text handshake with no header in
+//! front of it stays the default, for compatibility with proxy parsers
+//! that don't know to look for one.
+//!
+//! Only IPv4 and IPv6 TCP sources/destinations are represented in the
+//! fixed address block, as the spec requires. A CONNECT host is usually a
+//! DNS name rather than an IP literal; when it isn't an IP, the
+//! destination address in the fixed block is left as the unspecified
+//! address of the source's family and the real hostname is instead
+//! carried in a PP2_TYPE_AUTHORITY TLV, which the spec defines for exactly
+//! this purpose.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+const VERSION_COMMAND_PROXY: u8 = 0x21; // version 2, command PROXY
+const FAMILY_TCP4: u8 = 0x11; // AF_INET + SOCK_STREAM
+const FAMILY_TCP6: u8 = 0x21; // AF_INET6 + SOCK_STREAM
+const PP2_TYPE_AUTHORITY: u8 = 0x02;
+
+/// Build a PROXY protocol v2 header describing a connection from `src` to
+/// `dst_host:dst_port`, with `dst_host` carried verbatim in a
+/// PP2_TYPE_AUTHORITY TLV whenever it isn't itself an IP literal.
+pub fn encode_v2(src: SocketAddr, dst_host: &str, dst_port: u16) -> Vec<u8> {
+    let dst_ip: IpAddr = dst_host.parse().unwrap_or(match src.ip() {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    });
+
+    let mut addr_block = Vec::new();
+    let family = match (src.ip(), dst_ip) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            addr_block.extend_from_slice(&s.octets());
+            addr_block.extend_from_slice(&d.octets());
+            FAMILY_TCP4
+        }
+        (s, d) => {
+            // Mixed or all-IPv6 families: normalize both ends to IPv6 so the
+            // fixed-size address block stays self-consistent.
+            addr_block.extend_from_slice(&to_v6(s).octets());
+            addr_block.extend_from_slice(&to_v6(d).octets());
+            FAMILY_TCP6
+        }
+    };
+    addr_block.extend_from_slice(&src.port().to_be_bytes());
+    addr_block.extend_from_slice(&dst_port.to_be_bytes());
+
+    let mut tlvs = Vec::new();
+    if dst_host.parse::<IpAddr>().is_err() {
+        tlvs.push(PP2_TYPE_AUTHORITY);
+        tlvs.extend_from_slice(&(dst_host.len() as u16).to_be_bytes());
+        tlvs.extend_from_slice(dst_host.as_bytes());
+    }
+
+    let payload_len = (addr_block.len() + tlvs.len()) as u16;
+
+    let mut header = Vec::with_capacity(16 + addr_block.len() + tlvs.len());
+    header.extend_from_slice(&SIGNATURE);
+    header.push(VERSION_COMMAND_PROXY);
+    header.push(family);
+    header.extend_from_slice(&payload_len.to_be_bytes());
+    header.extend_from_slice(&addr_block);
+    header.extend_from_slice(&tlvs);
+    header
+}
+
+fn to_v6(addr: IpAddr) -> Ipv6Addr {
+    match addr {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_v2_ipv4_header_layout() {
+        let src: SocketAddr = "127.0.0.1:54321".parse().unwrap();
+        let header = encode_v2(src, "93.184.216.34", 443);
+
+        assert_eq!(&header[0..12], &SIGNATURE);
+        assert_eq!(header[12], VERSION_COMMAND_PROXY);
+        assert_eq!(header[13], FAMILY_TCP4);
+        let len = u16::from_be_bytes([header[14], header[15]]);
+        assert_eq!(len as usize, header.len() - 16);
+        assert_eq!(&header[16..20], &[127, 0, 0, 1]);
+        assert_eq!(&header[20..24], &[93, 184, 216, 34]);
+        assert_eq!(u16::from_be_bytes([header[24], header[25]]), 54321);
+        assert_eq!(u16::from_be_bytes([header[26], header[27]]), 443);
+    }
+
+    #[test]
+    fn test_encode_v2_hostname_destination_uses_authority_tlv() {
+        let src: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let header = encode_v2(src, "example.com", 443);
+
+        // Destination address in the fixed block is unspecified since
+        // "example.com" isn't an IP literal...
+        assert_eq!(&header[20..24], &[0, 0, 0, 0]);
+
+        // ...but the real hostname shows up verbatim in a PP2_TYPE_AUTHORITY TLV.
+        let tlv_start = 28;
+        assert_eq!(header[tlv_start], PP2_TYPE_AUTHORITY);
+        let tlv_len = u16::from_be_bytes([header[tlv_start + 1], header[tlv_start + 2]]) as usize;
+        let value = &header[tlv_start + 3..tlv_start + 3 + tlv_len];
+        assert_eq!(value, b"example.com");
+    }
+}
@@ -0,0 +1,134 @@
+//! SOCKS5 (RFC 1928) front-end for bw-relay's client-facing side
+//!
+//! `http_connect` only serves tools that honor `HTTP_PROXY`/`HTTPS_PROXY`
+//! (or speak plain HTTP CONNECT). Several CLI tools and libraries only
+//! honor `ALL_PROXY`/SOCKS, so this gives bw-relay a second listening port
+//! speaking SOCKS5 that maps onto the same UDS `CONNECT host port\n`
+//! handshake `forward_to_proxy` already speaks to bw-proxy.
+//!
+//! Only the CONNECT command is implemented (BIND/UDP_ASSOCIATE reply
+//! "command not supported", since bw-relay has no use for either); IPv4,
+//! IPv6, and domain-name address types are all accepted in the request.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_NO_ACCEPTABLE: u8 = 0xFF;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+pub const REP_SUCCEEDED: u8 = 0x00;
+pub const REP_GENERAL_FAILURE: u8 = 0x01;
+pub const REP_CONNECTION_NOT_ALLOWED: u8 = 0x02;
+const REP_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REP_ADDRESS_TYPE_NOT_SUPPORTED: u8 = 0x08;
+
+/// Destination a SOCKS5 CONNECT request asked for
+pub struct Socks5Connect {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Perform the SOCKS5 greeting (accepting only the no-auth method) and read
+/// the CONNECT request, returning the requested destination. On any
+/// protocol error the appropriate failure reply (if the handshake got far
+/// enough to send one) has already been written before the error returns.
+pub async fn handshake(stream: &mut TcpStream) -> anyhow::Result<Socks5Connect> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != VERSION {
+        anyhow::bail!("Unsupported SOCKS version: {}", header[0]);
+    }
+
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+
+    if !methods.contains(&METHOD_NO_AUTH) {
+        stream.write_all(&[VERSION, METHOD_NO_ACCEPTABLE]).await?;
+        anyhow::bail!("Client does not offer the no-auth SOCKS5 method");
+    }
+    stream.write_all(&[VERSION, METHOD_NO_AUTH]).await?;
+
+    let mut req_header = [0u8; 4];
+    stream.read_exact(&mut req_header).await?;
+    let [ver, cmd, _rsv, atyp] = req_header;
+    if ver != VERSION {
+        anyhow::bail!("Unsupported SOCKS version in request: {ver}");
+    }
+    if cmd != CMD_CONNECT {
+        send_reply(stream, REP_COMMAND_NOT_SUPPORTED).await?;
+        anyhow::bail!("Unsupported SOCKS5 command: {cmd}");
+    }
+
+    let host = match atyp {
+        ATYP_IPV4 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            Ipv4Addr::from(addr).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            Ipv6Addr::from(addr).to_string()
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            let mut domain = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut domain).await?;
+            String::from_utf8(domain).map_err(|_| anyhow::anyhow!("Invalid UTF-8 in SOCKS5 domain name"))?
+        }
+        other => {
+            send_reply(stream, REP_ADDRESS_TYPE_NOT_SUPPORTED).await?;
+            anyhow::bail!("Unsupported SOCKS5 address type: {other}");
+        }
+    };
+
+    let mut port_bytes = [0u8; 2];
+    stream.read_exact(&mut port_bytes).await?;
+    let port = u16::from_be_bytes(port_bytes);
+
+    Ok(Socks5Connect { host, port })
+}
+
+/// Send a SOCKS5 reply with a placeholder bound address (`0.0.0.0:0`) —
+/// real clients don't use that field once a CONNECT has succeeded or failed.
+pub async fn send_reply(stream: &mut TcpStream, rep: u8) -> anyhow::Result<()> {
+    let reply = [VERSION, rep, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0];
+    stream.write_all(&reply).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_handshake_rejects_non_no_auth_methods() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        // greeting offering only username/password auth (0x02)
+        client.write_all(&[VERSION, 1, 0x02]).await.unwrap();
+
+        assert!(handshake(&mut server).await.is_err());
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [VERSION, METHOD_NO_ACCEPTABLE]);
+    }
+
+    #[test]
+    fn test_reply_byte_layout() {
+        let reply = [VERSION, REP_SUCCEEDED, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0];
+        assert_eq!(reply.len(), 10);
+        assert_eq!(reply[0], 0x05);
+        assert_eq!(reply[1], 0x00);
+    }
+}
@@ -0,0 +1,259 @@
+//! Generic, config-driven multi-call dispatcher
+//!
+//! `bw-claude` and `bw-gemini` are near-identical: discover a CLI path,
+//! build a `ToolConfig`/`SandboxConfig`, exec. This binary replaces that
+//! per-tool duplication with a single dispatcher reading a `ToolRegistry`
+//! (see `bwrap_core::registry`): adding a new sandboxable tool means
+//! adding a registry entry, not compiling a new binary.
+//!
+//! The tool to run is resolved the same way cargo resolves multi-call
+//! binaries/aliases:
+//! 1. If invoked as `bw-<tool>` (e.g. via a symlink), `<tool>` is used.
+//! 2. Otherwise, if the first CLI argument isn't a flag and isn't the
+//!    `policy`/`group`/`version` admin subcommand, it's treated as the tool name
+//!    (`bw claude -- --help`).
+//! 3. Otherwise, `--tool <name>` must be given explicitly.
+
+use anyhow::{Context, Result};
+use bwrap_core::{
+    AdminCommand, CommitMode, CommonArgs, ConfigLoader, HomeAccessMode, LockedHosts, PermissionSet,
+    SandboxBuilder, SandboxConfig, SandboxLock, ToolConfig, ToolRegistry, UserMode, setup_policy,
+};
+use bwrap_proxy::PolicyEngine;
+use clap::Parser;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+    name = "bw",
+    about = "Sandbox any tool registered in the tool registry",
+    version
+)]
+struct Args {
+    /// Manage policy/group config instead of launching a tool
+    #[command(subcommand)]
+    admin: Option<AdminCommand>,
+
+    /// Tool to sandbox; only used when no tool is inferred from argv[0] (a
+    /// `bw-<tool>` symlink) or a leading `bw <tool>` subcommand
+    #[arg(long)]
+    tool: Option<String>,
+
+    /// Tool registry TOML (defaults to `~/.config/bw/tools.toml`, falling
+    /// back to the built-in claude/gemini entries if that doesn't exist)
+    #[arg(long, value_name = "PATH")]
+    tool_registry: Option<PathBuf>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let (tool_name_from_dispatch, argv) = split_dispatch_argv(env::args().collect());
+    let mut args = Args::parse_from(&argv);
+
+    if let Some(admin) = args.admin {
+        return bwrap_core::policy_admin::run(admin, args.common.proxy_config.as_deref())
+            .context("Policy/group admin command failed");
+    }
+
+    let tool_name = tool_name_from_dispatch
+        .or_else(|| args.tool.take())
+        .context("No tool specified: invoke via a bw-<tool> symlink, run `bw <tool> ...`, or pass --tool <name>")?;
+
+    // Apply the system/user/project config layer chain on top of the
+    // parsed CLI flags (CLI always wins; see `bwrap_core::config::layer`).
+    bwrap_core::apply_layered_config(&mut args.common).context("Failed to apply layered config")?;
+
+    // Initialize logging - only if BW_LOG env var or verbose flag
+    let _ = if args.common.verbose || env::var("BW_LOG").is_ok() {
+        let filter = env::var("BW_LOG").unwrap_or_else(|_| "info".to_string());
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
+            .try_init()
+    } else {
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::ERROR)
+            .with_writer(std::io::stderr)
+            .try_init()
+    };
+
+    // Handle --list-policies and --list-groups flags
+    if args.common.list_policies || args.common.list_groups {
+        let config = ConfigLoader::load_or_default(args.common.proxy_config.clone())
+            .context("Failed to load proxy configuration")?;
+
+        if args.common.list_policies {
+            println!("Available policies:\n");
+            for (name, policy) in &config.policy.policies {
+                println!("  {} - {}", name, policy.description.as_deref().unwrap_or("(no description)"));
+            }
+            println!();
+        }
+
+        if args.common.list_groups {
+            println!("Available host groups:\n");
+            for (name, group) in &config.network.groups {
+                println!("  {} - {}", name, group.description);
+            }
+            println!();
+        }
+
+        return Ok(());
+    }
+
+    // Resolve the tool from the registry: explicit --tool-registry, then
+    // ~/.config/bw/tools.toml, falling back to the built-in entries.
+    let registry_path = args.tool_registry.clone().or_else(default_registry_path);
+    let registry = match &registry_path {
+        Some(path) if path.exists() => {
+            ToolRegistry::load_from_file(path).context("Failed to load tool registry")?
+        }
+        _ => ToolRegistry::builtin(),
+    };
+    let entry = registry
+        .get(&tool_name)
+        .with_context(|| format!("Tool '{tool_name}' is not in the registry"))?
+        .clone();
+    let cli_path = entry
+        .resolve_path(&tool_name)
+        .with_context(|| format!("Could not find a CLI for tool '{tool_name}'"))?;
+
+    // Load configuration
+    let app_config = ConfigLoader::load_or_default(args.common.proxy_config.clone())
+        .context("Failed to load application configuration")?;
+
+    // Set up policy with tool-specific default
+    let policy_setup = setup_policy(&app_config, &args.common, &tool_name)
+        .await
+        .context("Failed to set up policy")?;
+
+    // Reproducible grant-set lockfile: if requested, verify the
+    // freshly-resolved grant set against any existing lock before mounting
+    // anything, then refresh the lock to match this run.
+    if let Some(lockfile_path) = &args.common.lockfile {
+        let (allow_hosts, deny_hosts) = PolicyEngine::expand_hostnames(
+            &policy_setup.policy.network.effective_allow_groups(),
+            &policy_setup.policy.network.deny_groups,
+            &app_config.network,
+        )
+        .context("Failed to expand policy host groups for lockfile")?;
+
+        SandboxLock::enforce(
+            lockfile_path,
+            &policy_setup.filesystem_spec,
+            &policy_setup.network_mode,
+            &LockedHosts { allow: allow_hosts, deny: deny_hosts, learned: vec![] },
+            args.common.allow_lock_drift,
+        )
+        .context("Sandbox grant set lockfile check failed")?;
+    }
+
+    // Determine target directory
+    let target_dir = if let Some(dir) = args.common.dir.as_ref() {
+        dir.canonicalize()
+            .context("Failed to canonicalize target directory")?
+    } else {
+        env::current_dir().context("Failed to get current directory")?
+    };
+
+    // Build tool configuration from the registry entry
+    let tool_config = ToolConfig {
+        name: tool_name.clone(),
+        cli_path,
+        default_args: entry.default_args,
+        cli_args: args.common.cli_args,
+        help_text: entry.help_text,
+    };
+
+    // Granular --allow-read/--allow-write/--deny-read/--deny-write/
+    // --allow-run rules (see `bwrap_core::permissions`)
+    let home_dir = env::var("HOME").context("HOME environment variable not set")?;
+    let permissions = PermissionSet::from_args(
+        &args.common.allow_read,
+        &args.common.allow_write,
+        &args.common.deny_read,
+        &args.common.deny_write,
+        &args.common.allow_run,
+        args.common.full_home_access,
+        &PathBuf::from(home_dir),
+    );
+
+    // Build sandbox configuration
+    let config = SandboxConfig {
+        tool_name: tool_name.clone(),
+        policy_name: policy_setup.policy_name,
+        tool_config,
+        target_dir,
+        network_mode: policy_setup.network_mode,
+        home_access: if args.common.full_home_access {
+            HomeAccessMode::Full
+        } else {
+            HomeAccessMode::Safe
+        },
+        user_mode: match (args.common.map_uid, args.common.map_gid) {
+            (Some(uid), Some(gid)) => UserMode::Mapped { uid, gid },
+            _ => UserMode::Host,
+        },
+        additional_ro_paths: args.common.allow_ro_paths,
+        additional_rw_paths: args.common.allow_rw_paths,
+        env_vars: HashMap::new(),
+        pass_through_env: args.common.pass_env_vars,
+        verbose: args.common.verbose,
+        shell: args.common.shell,
+        bw_relay_path: args.common.bw_relay_path,
+        permissions,
+        seccomp: policy_setup.seccomp_spec,
+        commit_mode: CommitMode::Direct,
+        pty: args.common.pty,
+    };
+
+    // Build and execute sandbox
+    let security_policy = app_config.security.tools.get(&tool_name);
+    let sandbox = SandboxBuilder::new(config, policy_setup.filesystem_spec, security_policy)
+        .context("Failed to create sandbox builder")?
+        .build()
+        .context("Failed to build sandbox")?;
+
+    let status = sandbox.exec().context("Failed to execute sandbox")?;
+
+    std::process::exit(status.code().unwrap_or(1))
+}
+
+fn default_registry_path() -> Option<PathBuf> {
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".config/bw/tools.toml"))
+}
+
+/// Peel a dispatch-implied tool name off of `argv`, returning the
+/// (possibly-None) tool name and the remaining argv clap should parse.
+///
+/// `argv[0]`'s basename takes priority (symlink dispatch, e.g.
+/// `bw-claude` -> `claude`); otherwise a leading positional argument that
+/// isn't a flag and isn't the `policy`/`group`/`version` admin subcommand is
+/// treated as the tool name and removed so clap doesn't try to parse it as a
+/// `CommonArgs` flag.
+fn split_dispatch_argv(argv: Vec<String>) -> (Option<String>, Vec<String>) {
+    let argv0_tool = PathBuf::from(&argv[0])
+        .file_name()
+        .and_then(|f| f.to_str())
+        .and_then(|f| f.strip_prefix("bw-"))
+        .map(|s| s.to_string());
+
+    if let Some(tool) = argv0_tool {
+        return (Some(tool), argv);
+    }
+
+    let mut rest = argv;
+    let tool = rest.get(1).filter(|a| {
+        !a.starts_with('-') && a.as_str() != "policy" && a.as_str() != "group" && a.as_str() != "version"
+    }).cloned();
+    if tool.is_some() {
+        rest.remove(1);
+    }
+    (tool, rest)
+}
@@ -30,6 +30,54 @@ pub struct CommonArgs {
     #[arg(long = "allow-rw", value_name = "PATH")]
     pub allow_rw_paths: Vec<PathBuf>,
 
+    /// Grant read access to a glob path pattern (can be used multiple
+    /// times). Unlike --allow-ro, overlaps with --deny-read are resolved
+    /// with deny always winning, independent of flag order; see
+    /// `bwrap_core::permissions`.
+    #[arg(long = "allow-read", value_name = "GLOB")]
+    pub allow_read: Vec<String>,
+
+    /// Grant write access to a glob path pattern (can be used multiple
+    /// times), deny-always-wins against --deny-write like --allow-read
+    #[arg(long = "allow-write", value_name = "GLOB")]
+    pub allow_write: Vec<String>,
+
+    /// Deny read access to a glob path pattern, overriding any overlapping
+    /// --allow-read (or --full-home-access) rule
+    #[arg(long = "deny-read", value_name = "GLOB")]
+    pub deny_read: Vec<String>,
+
+    /// Deny write access to a glob path pattern, overriding any overlapping
+    /// --allow-write (or --full-home-access) rule
+    #[arg(long = "deny-write", value_name = "GLOB")]
+    pub deny_write: Vec<String>,
+
+    /// Restrict the sandbox's top-level process to this comma-separated
+    /// list of executable names (e.g. --allow-run=claude,git). Unset means
+    /// unrestricted. Does not confine what that process execs afterward;
+    /// see `bwrap_core::permissions`.
+    #[arg(long = "allow-run", value_name = "EXE,...", value_delimiter = ',')]
+    pub allow_run: Vec<String>,
+
+    /// Unshare the user namespace and map the sandboxed process to this uid
+    /// inside the sandbox (e.g. 0 for a root-like identity), rather than
+    /// the caller's real uid. Requires --map-gid; see
+    /// `bwrap_core::config::UserMode`.
+    #[arg(long, value_name = "UID", requires = "map_gid")]
+    pub map_uid: Option<u32>,
+
+    /// Paired with --map-uid: the gid to map the sandboxed process to
+    /// inside the new user namespace.
+    #[arg(long, value_name = "GID", requires = "map_uid")]
+    pub map_gid: Option<u32>,
+
+    /// Give the sandboxed process a real devpts instance for PTY
+    /// allocation instead of bind-mounting the host's /dev. Always on in
+    /// --shell mode; pass this to also enable it for the tool's own CLI
+    /// (e.g. if it shells out to a pager or editor).
+    #[arg(long)]
+    pub pty: bool,
+
     /// Set working directory in sandbox (default: current directory)
     #[arg(long, value_name = "PATH")]
     pub dir: Option<PathBuf>,
@@ -72,6 +120,49 @@ pub struct CommonArgs {
     #[arg(long)]
     pub list_groups: bool,
 
+    /// Write/verify a reproducible grant-set lockfile at this path (see
+    /// `bwrap_core::lockfile`). If the file already exists, the
+    /// freshly-resolved filesystem/network/host grants must match it
+    /// exactly or the sandbox refuses to start.
+    #[arg(long, value_name = "PATH")]
+    pub lockfile: Option<PathBuf>,
+
+    /// If the freshly-resolved grant set doesn't match an existing
+    /// --lockfile, warn instead of refusing to start
+    #[arg(long, requires = "lockfile")]
+    pub allow_lock_drift: bool,
+
+    /// Instead of silently blocking a CONNECT to a host the policy denies,
+    /// pause the connection and ask on the controlling terminal: allow
+    /// once, allow for the rest of this session (kept in memory, not
+    /// written anywhere), allow always (persist to --proxy-config), deny
+    /// once, or deny always (persist a deny rule). Falls back to deny if
+    /// the terminal doesn't answer within --policy-prompt-timeout-secs.
+    #[arg(long)]
+    pub policy_prompt: bool,
+
+    /// How long to wait for an answer to a policy prompt before falling
+    /// back to deny. Ignored unless --policy-prompt is set.
+    #[arg(long, default_value_t = 30)]
+    pub policy_prompt_timeout_secs: u64,
+
+    /// Explicit config file layering system (system/user/project) defaults
+    /// for these flags on top of (see `bwrap_core::config::layer`). CLI
+    /// flags always take precedence over a layered value.
+    #[arg(long, value_name = "PATH", conflicts_with = "no_config")]
+    pub config: Option<PathBuf>,
+
+    /// Skip the system/user/project config layer chain entirely and use
+    /// only the flags passed on the command line
+    #[arg(long)]
+    pub no_config: bool,
+
+    /// Reject any watched config file layer larger than this many bytes,
+    /// rather than reparse it on every edit (see
+    /// `bwrap_core::config::ConfigWatcher`)
+    #[arg(long, value_name = "BYTES", default_value_t = crate::config::DEFAULT_MAX_CONFIG_SIZE)]
+    pub max_config_size: u64,
+
     /// Tool arguments (use -- to separate from bw-* options)
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     pub cli_args: Vec<String>,
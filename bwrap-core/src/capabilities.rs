@@ -0,0 +1,157 @@
+//! Capability probing for the installed `bwrap` binary
+//!
+//! Several `MountMode` variants (`ReadOnlyTry`, `Overlay`/`OverlaySrc`,
+//! `TmpOverlay`, `BindData`) depend on bubblewrap flags that didn't exist in
+//! its earliest releases. Probing for them once at
+//! startup, rather than discovering a missing flag mid-mount, lets the
+//! `bw-claude version` subcommand (see `crate::policy_admin`) tell a user
+//! up front which mount modes their installed `bwrap` can actually serve,
+//! instead of them reading strace output after a launch fails.
+
+use crate::mount::MountMode;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Bubblewrap flags this crate knows how to use, whether or not the
+/// installed `bwrap` actually supports them. `--size` isn't used by any
+/// `MountMode` yet, but is probed ahead of that so the capability set is
+/// already complete when that mount mode lands.
+const PROBED_FLAGS: &[&str] =
+    &["--overlay", "--overlay-src", "--ro-bind-try", "--bind-data", "--tmp-overlay", "--size", "--dev"];
+
+/// What the installed `bwrap` binary supports, detected by scraping its
+/// `--version` and `--help` output once at startup
+#[derive(Debug, Clone, Default)]
+pub struct BwrapCapabilities {
+    /// Version string reported by `bwrap --version` (e.g. `"bubblewrap 0.8.0"`),
+    /// or `None` if `bwrap` couldn't be found or executed at all
+    pub version: Option<String>,
+    flags: HashSet<&'static str>,
+}
+
+impl BwrapCapabilities {
+    /// Probe `bwrap_path` (typically `Path::new("bwrap")`, resolved via
+    /// `$PATH` the same way `sandbox::build_command` invokes it) for its
+    /// version and supported flags. Never fails: a `bwrap` that can't be
+    /// executed just yields an all-unsupported capability set, so callers
+    /// can still report "not detected" instead of aborting.
+    pub fn probe(bwrap_path: &Path) -> Self {
+        let version = Command::new(bwrap_path)
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+        let help = Command::new(bwrap_path)
+            .arg("--help")
+            .output()
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default();
+
+        // Matched with a trailing space so e.g. "--dev" doesn't spuriously
+        // match inside "--dev-bind"'s help line.
+        let flags = PROBED_FLAGS
+            .iter()
+            .copied()
+            .filter(|flag| help.contains(&format!("{flag} ")))
+            .collect();
+
+        Self { version, flags }
+    }
+
+    /// Whether `flag` (e.g. `"--overlay"`) appeared in the probed `--help` output
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    /// Whether `mode` can actually be used with the probed `bwrap`. The
+    /// mount modes that have existed since bubblewrap's earliest releases
+    /// (plain bind mounts, tmpfs, proc, symlink, remount-ro, dev-bind) are
+    /// always reported supported; only the newer, optional flags are
+    /// gated on having actually been seen in `--help`.
+    pub fn supports_mount_mode(&self, mode: &MountMode) -> bool {
+        match mode {
+            MountMode::ReadOnlyTry => self.has_flag("--ro-bind-try"),
+            MountMode::OverlaySrc | MountMode::Overlay { .. } => {
+                self.has_flag("--overlay") && self.has_flag("--overlay-src")
+            }
+            MountMode::Dev => self.has_flag("--dev"),
+            MountMode::TmpOverlay => self.has_flag("--tmp-overlay"),
+            MountMode::BindData { .. } => self.has_flag("--bind-data"),
+            MountMode::ReadOnly
+            | MountMode::ReadWrite
+            | MountMode::Tmpfs
+            | MountMode::RemountRo
+            | MountMode::Symlink { .. }
+            | MountMode::Proc
+            | MountMode::DevBind => true,
+        }
+    }
+
+    /// One `(name, usable)` pair per named `MountMode` kind, for display in
+    /// the `bw-claude version` subcommand. Variants that carry fields
+    /// (`Symlink`, `Overlay`, `BindData`) are represented by a placeholder value since
+    /// only their discriminant affects `supports_mount_mode`.
+    pub fn mount_mode_support(&self) -> Vec<(&'static str, bool)> {
+        let representatives: &[(&'static str, MountMode)] = &[
+            ("ReadOnly", MountMode::ReadOnly),
+            ("ReadWrite", MountMode::ReadWrite),
+            ("ReadOnlyTry", MountMode::ReadOnlyTry),
+            ("Tmpfs", MountMode::Tmpfs),
+            ("RemountRo", MountMode::RemountRo),
+            ("Symlink", MountMode::Symlink { target: PathBuf::new() }),
+            ("Proc", MountMode::Proc),
+            ("DevBind", MountMode::DevBind),
+            ("Dev", MountMode::Dev),
+            ("OverlaySrc", MountMode::OverlaySrc),
+            ("Overlay", MountMode::Overlay { workdir: PathBuf::new() }),
+            ("TmpOverlay", MountMode::TmpOverlay),
+            ("BindData", MountMode::BindData { ro: true, contents: vec![], target_fd: 0 }),
+        ];
+
+        representatives
+            .iter()
+            .map(|(name, mode)| (*name, self.supports_mount_mode(mode)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_missing_binary_yields_no_capabilities() {
+        let caps = BwrapCapabilities::probe(Path::new("/nonexistent/bwrap-binary-for-test"));
+        assert_eq!(caps.version, None);
+        assert!(!caps.has_flag("--overlay"));
+        let support: std::collections::HashMap<_, _> = caps.mount_mode_support().into_iter().collect();
+        assert!(support["Tmpfs"]);
+        assert!(!support["Overlay"]);
+    }
+
+    #[test]
+    fn test_core_mount_modes_always_supported() {
+        let caps = BwrapCapabilities::default();
+        assert!(caps.supports_mount_mode(&MountMode::ReadOnly));
+        assert!(caps.supports_mount_mode(&MountMode::ReadWrite));
+        assert!(caps.supports_mount_mode(&MountMode::Tmpfs));
+        assert!(caps.supports_mount_mode(&MountMode::Proc));
+        assert!(caps.supports_mount_mode(&MountMode::DevBind));
+        assert!(!caps.supports_mount_mode(&MountMode::ReadOnlyTry));
+        assert!(!caps.supports_mount_mode(&MountMode::Overlay { workdir: PathBuf::new() }));
+    }
+
+    #[test]
+    fn test_dev_flag_detection_does_not_false_positive_on_dev_bind() {
+        let mut caps = BwrapCapabilities::default();
+        caps.flags.insert("--dev-bind");
+        // Only --dev-bind was "detected" here; --dev must not be implied by it.
+        assert!(!caps.supports_mount_mode(&MountMode::Dev));
+        caps.flags.insert("--dev");
+        assert!(caps.supports_mount_mode(&MountMode::Dev));
+    }
+}
@@ -0,0 +1,160 @@
+//! Environment-variable overrides for the layered config chain
+//!
+//! On top of the file-based chain in `ConfigLoader::load_with_priority`,
+//! individual values can be overridden with `BW_`-prefixed environment
+//! variables keyed by path, e.g. `BW_NETWORK__GROUPS__DEV__HOSTS` overrides
+//! `network.groups.dev.hosts`. A `.` in the path becomes `__` and each
+//! segment is lowercased (no other character mapping is done), mirroring
+//! the scheme Cargo's `GlobalContext` uses for `CARGO_*` overrides. These are applied last, on top of the fully merged
+//! file chain and before policy resolution, so they work even in
+//! environments (e.g. CI) that can only pass environment variables.
+
+use super::schema::Config;
+use crate::error::{Result, SandboxError};
+use std::env;
+
+const ENV_PREFIX: &str = "BW_";
+
+/// Apply `BW_`-prefixed environment-variable overrides on top of `config`
+pub fn apply_env_overrides(config: Config) -> Result<Config> {
+    let mut value = toml::Value::try_from(&config).map_err(|e| {
+        SandboxError::ConfigError(format!("Failed to serialize config for env overrides: {e}"))
+    })?;
+
+    for (key, raw_value) in env::vars() {
+        if let Some(path) = parse_override_key(&key) {
+            set_path(&mut value, &path, parse_override_value(&raw_value));
+        }
+    }
+
+    value.try_into().map_err(|e| {
+        SandboxError::ConfigError(format!("Failed to apply environment overrides: {e}"))
+    })
+}
+
+/// Parse `BW_NETWORK__GROUPS__DEV__HOSTS` into `["network", "groups", "dev", "hosts"]`.
+/// Variables with no `__` separator (e.g. `BW_CLAUDE_CONFIG`, a file path
+/// rather than a config key) are not config overrides and are ignored.
+fn parse_override_key(key: &str) -> Option<Vec<String>> {
+    let rest = key.strip_prefix(ENV_PREFIX)?;
+    if !rest.contains("__") {
+        return None;
+    }
+    Some(rest.split("__").map(|segment| segment.to_lowercase()).collect())
+}
+
+/// Parse a raw environment-variable string into a TOML value: a
+/// comma-separated string becomes an array, `true`/`false` and numbers are
+/// recognized, and anything else is kept as a string.
+fn parse_override_value(raw: &str) -> toml::Value {
+    if raw.contains(',') {
+        return toml::Value::Array(raw.split(',').map(|part| parse_scalar(part.trim())).collect());
+    }
+    parse_scalar(raw)
+}
+
+fn parse_scalar(raw: &str) -> toml::Value {
+    if let Ok(b) = raw.parse::<bool>() {
+        toml::Value::Boolean(b)
+    } else if let Ok(i) = raw.parse::<i64>() {
+        toml::Value::Integer(i)
+    } else if let Ok(f) = raw.parse::<f64>() {
+        toml::Value::Float(f)
+    } else {
+        toml::Value::String(raw.to_string())
+    }
+}
+
+/// Set `new_value` at `path` within `value`, creating intermediate tables as needed
+fn set_path(value: &mut toml::Value, path: &[String], new_value: toml::Value) {
+    let Some((head, rest)) = path.split_first() else {
+        return;
+    };
+
+    if !value.is_table() {
+        *value = toml::Value::Table(toml::value::Table::new());
+    }
+    let table = value.as_table_mut().expect("value was just made a table");
+
+    if rest.is_empty() {
+        table.insert(head.clone(), new_value);
+    } else {
+        let entry = table
+            .entry(head.clone())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+        set_path(entry, rest, new_value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_override_key_splits_path_segments() {
+        assert_eq!(
+            parse_override_key("BW_NETWORK__GROUPS__DEV__HOSTS"),
+            Some(vec![
+                "network".to_string(),
+                "groups".to_string(),
+                "dev".to_string(),
+                "hosts".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_override_key_ignores_vars_without_path_segments() {
+        assert_eq!(parse_override_key("BW_CLAUDE_CONFIG"), None);
+    }
+
+    #[test]
+    fn test_parse_override_key_ignores_unrelated_vars() {
+        assert_eq!(parse_override_key("PATH"), None);
+    }
+
+    #[test]
+    fn test_parse_override_value_comma_separated_is_array() {
+        assert_eq!(
+            parse_override_value("foo.com, bar.com"),
+            toml::Value::Array(vec![
+                toml::Value::String("foo.com".to_string()),
+                toml::Value::String("bar.com".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_override_value_scalar_types() {
+        assert_eq!(parse_override_value("true"), toml::Value::Boolean(true));
+        assert_eq!(parse_override_value("42"), toml::Value::Integer(42));
+        assert_eq!(
+            parse_override_value("restrictive"),
+            toml::Value::String("restrictive".to_string())
+        );
+    }
+
+    #[test]
+    fn test_set_path_creates_intermediate_tables() {
+        let mut value = toml::Value::Table(toml::value::Table::new());
+        set_path(
+            &mut value,
+            &["network".to_string(), "block_doh".to_string()],
+            toml::Value::Boolean(false),
+        );
+        assert_eq!(
+            value.get("network").and_then(|n| n.get("block_doh")),
+            Some(&toml::Value::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_apply_env_overrides_sets_nested_value() {
+        env::set_var("BW_NETWORK__BLOCK_DOH", "false");
+        let result = apply_env_overrides(Config::default());
+        env::remove_var("BW_NETWORK__BLOCK_DOH");
+
+        let config = result.expect("override should apply cleanly");
+        assert!(!config.network.block_doh);
+    }
+}
@@ -0,0 +1,306 @@
+//! Layered TOML config for the `CommonArgs` flags every invocation retypes
+//!
+//! Complements `ConfigLoader` (which layers the proxy's network/policy
+//! config file) with a smaller chain for everyday flags like `--allow-ro`,
+//! `--pass-env`, and `--policy`: system (`/etc/bw/config.toml`) < user
+//! (`~/.config/bw/config.toml`) < project (`.bw.toml`, discovered by
+//! walking up from the current directory) < an explicit `--config` file <
+//! the CLI flags parsed into `CommonArgs` itself. List-valued keys (RO/RW
+//! paths, pass-env vars) are concatenated across layers rather than
+//! replaced, so a project file can add to what the user file already
+//! grants; scalar keys (`policy`, `bw_relay_path`) take the most specific
+//! layer that sets them. `--no-config` skips this chain entirely and
+//! leaves `CommonArgs` exactly as parsed.
+//!
+//! Path-valued keys (`allow_ro_paths`, `allow_rw_paths`, `bw_relay_path`)
+//! are resolved relative to the file that set them, not the process's
+//! current directory, the same rule `ConfigLoader` applies to the
+//! network/policy config chain — see
+//! `PartialCommonConfig::resolve_relative_paths`.
+
+use crate::args::CommonArgs;
+use crate::error::Result;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SYSTEM_CONFIG_PATH: &str = "/etc/bw/config.toml";
+const PROJECT_CONFIG_FILE: &str = ".bw.toml";
+
+/// One layer of `CommonArgs` defaults, as read from a TOML file. Every
+/// field is optional (or an appendable list) so an unset key in one layer
+/// leaves whatever an earlier, lower-priority layer set untouched.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PartialCommonConfig {
+    #[serde(default)]
+    pub no_network: Option<bool>,
+    #[serde(default)]
+    pub full_home_access: Option<bool>,
+    #[serde(default)]
+    pub allow_ro_paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub allow_rw_paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub allow_read: Vec<String>,
+    #[serde(default)]
+    pub allow_write: Vec<String>,
+    #[serde(default)]
+    pub deny_read: Vec<String>,
+    #[serde(default)]
+    pub deny_write: Vec<String>,
+    #[serde(default)]
+    pub allow_run: Vec<String>,
+    #[serde(default)]
+    pub pass_env_vars: Vec<String>,
+    #[serde(default)]
+    pub policy: Option<String>,
+    #[serde(default)]
+    pub bw_relay_path: Option<PathBuf>,
+    #[serde(default)]
+    pub map_uid: Option<u32>,
+    #[serde(default)]
+    pub map_gid: Option<u32>,
+    /// Per-tool override tables, e.g. `[claude]` / `[gemini]`, keyed by
+    /// tool name. Tool-specific flags (like Claude's
+    /// `no_skip_permissions`) live in each bw-* binary, not here,
+    /// so this stays a generic bag each binary can query its own keys from
+    /// instead of `bwrap_core` needing to know every tool's flag set.
+    #[serde(flatten)]
+    pub tools: IndexMap<String, toml::Value>,
+}
+
+impl PartialCommonConfig {
+    fn load_from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&contents)?;
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            config.resolve_relative_paths(dir);
+        }
+        Ok(config)
+    }
+
+    /// Resolve `allow_ro_paths`/`allow_rw_paths`/`bw_relay_path` against
+    /// `dir` (this layer file's own directory) when they're relative, so a
+    /// shared config included from elsewhere doesn't silently resolve
+    /// mount sources against whatever directory happened to invoke it
+    /// instead — mirrors `ConfigLoader`'s
+    /// `resolve_config_relative_paths`/`is_config_relative` for the
+    /// network/policy config chain.
+    fn resolve_relative_paths(&mut self, dir: &Path) {
+        for path in self.allow_ro_paths.iter_mut().chain(self.allow_rw_paths.iter_mut()) {
+            if super::loader::is_config_relative(path) {
+                *path = dir.join(&*path);
+            }
+        }
+        if let Some(ref mut relay_path) = self.bw_relay_path {
+            if super::loader::is_config_relative(relay_path) {
+                *relay_path = dir.join(&*relay_path);
+            }
+        }
+    }
+
+    /// Merge `other` on top of `self`: lists are concatenated (`self`'s
+    /// entries first), scalars are overridden only where `other` sets them.
+    fn merge(mut self, other: Self) -> Self {
+        self.no_network = other.no_network.or(self.no_network);
+        self.full_home_access = other.full_home_access.or(self.full_home_access);
+        self.allow_ro_paths.extend(other.allow_ro_paths);
+        self.allow_rw_paths.extend(other.allow_rw_paths);
+        self.allow_read.extend(other.allow_read);
+        self.allow_write.extend(other.allow_write);
+        self.deny_read.extend(other.deny_read);
+        self.deny_write.extend(other.deny_write);
+        self.allow_run.extend(other.allow_run);
+        self.pass_env_vars.extend(other.pass_env_vars);
+        self.policy = other.policy.or(self.policy);
+        self.bw_relay_path = other.bw_relay_path.or(self.bw_relay_path);
+        self.map_uid = other.map_uid.or(self.map_uid);
+        self.map_gid = other.map_gid.or(self.map_gid);
+        for (name, value) in other.tools {
+            self.tools.insert(name, value);
+        }
+        self
+    }
+
+    /// Look up a boolean key in a tool's override table, e.g.
+    /// `tool_bool("claude", "no_skip_permissions")`.
+    pub fn tool_bool(&self, tool: &str, key: &str) -> Option<bool> {
+        self.tools.get(tool)?.get(key)?.as_bool()
+    }
+}
+
+/// Finds and merges the `CommonArgs` config file chain; see module docs
+/// for the layer order.
+pub struct ConfigLayer;
+
+impl ConfigLayer {
+    /// `/etc/bw/config.toml`, if present
+    pub fn find_system_config() -> Option<PathBuf> {
+        let p = PathBuf::from(SYSTEM_CONFIG_PATH);
+        p.exists().then_some(p)
+    }
+
+    /// `~/.config/bw/config.toml`, if present
+    pub fn find_user_config() -> Option<PathBuf> {
+        let home = env::var("HOME").ok()?;
+        let p = PathBuf::from(home).join(".config/bw/config.toml");
+        p.exists().then_some(p)
+    }
+
+    /// Walk up from `start_dir` looking for `.bw.toml`
+    pub fn find_project_config(start_dir: &Path) -> Option<PathBuf> {
+        let mut current = start_dir.to_path_buf();
+        loop {
+            let candidate = current.join(PROJECT_CONFIG_FILE);
+            if candidate.exists() {
+                return Some(candidate);
+            }
+            if !current.pop() {
+                return None;
+            }
+        }
+    }
+
+    /// Load and merge every file layer (system < user < project <
+    /// `explicit`) into one `PartialCommonConfig`
+    pub fn load(start_dir: &Path, explicit: Option<&Path>) -> Result<PartialCommonConfig> {
+        let mut merged = PartialCommonConfig::default();
+
+        for path in [Self::find_system_config(), Self::find_user_config(), Self::find_project_config(start_dir)]
+            .into_iter()
+            .flatten()
+        {
+            merged = merged.merge(PartialCommonConfig::load_from_file(&path)?);
+        }
+
+        if let Some(explicit) = explicit {
+            merged = merged.merge(PartialCommonConfig::load_from_file(explicit)?);
+        }
+
+        Ok(merged)
+    }
+}
+
+/// Load the `CommonArgs` config layer chain and apply it to `args` as
+/// defaults, with the CLI flags `args` was already parsed from taking
+/// precedence. No-op if `args.no_config` is set.
+///
+/// Boolean flags (`no_network`, `full_home_access`) can only be turned on
+/// by a config layer, never forced back off: clap parses them as bare
+/// presence flags, so there's no way to tell "not passed" from "explicitly
+/// false" once parsing is done. This matches the enable-only flags most
+/// config+CLI systems settle on for this shape of boolean.
+///
+/// Returns the loaded layer (empty if `--no-config` was passed) so callers
+/// can also pull per-tool keys out of it via `PartialCommonConfig::tool_bool`.
+pub fn apply_layered_config(args: &mut CommonArgs) -> Result<PartialCommonConfig> {
+    if args.no_config {
+        return Ok(PartialCommonConfig::default());
+    }
+
+    let start_dir = args.dir.clone().unwrap_or(env::current_dir()?);
+    let layer = ConfigLayer::load(&start_dir, args.config.as_deref())?;
+
+    args.no_network = args.no_network || layer.no_network.unwrap_or(false);
+    args.full_home_access = args.full_home_access || layer.full_home_access.unwrap_or(false);
+
+    let mut ro_paths = layer.allow_ro_paths.clone();
+    ro_paths.append(&mut args.allow_ro_paths);
+    args.allow_ro_paths = ro_paths;
+
+    let mut rw_paths = layer.allow_rw_paths.clone();
+    rw_paths.append(&mut args.allow_rw_paths);
+    args.allow_rw_paths = rw_paths;
+
+    let mut allow_read = layer.allow_read.clone();
+    allow_read.append(&mut args.allow_read);
+    args.allow_read = allow_read;
+
+    let mut allow_write = layer.allow_write.clone();
+    allow_write.append(&mut args.allow_write);
+    args.allow_write = allow_write;
+
+    let mut deny_read = layer.deny_read.clone();
+    deny_read.append(&mut args.deny_read);
+    args.deny_read = deny_read;
+
+    let mut deny_write = layer.deny_write.clone();
+    deny_write.append(&mut args.deny_write);
+    args.deny_write = deny_write;
+
+    let mut allow_run = layer.allow_run.clone();
+    allow_run.append(&mut args.allow_run);
+    args.allow_run = allow_run;
+
+    let mut pass_env = layer.pass_env_vars.clone();
+    pass_env.append(&mut args.pass_env_vars);
+    args.pass_env_vars = pass_env;
+
+    args.policy = args.policy.take().or_else(|| layer.policy.clone());
+    args.bw_relay_path = args.bw_relay_path.take().or_else(|| layer.bw_relay_path.clone());
+    args.map_uid = args.map_uid.or(layer.map_uid);
+    args.map_gid = args.map_gid.or(layer.map_gid);
+
+    Ok(layer)
+}
+
+/// Apply a tool's `[tool_name]` boolean override from the layered config
+/// to `value`, honoring a CLI flag that already turned it on (mirrors
+/// `apply_layered_config`'s enable-only semantics for plain bool flags).
+pub fn apply_tool_bool(value: bool, layer: &PartialCommonConfig, tool_name: &str, key: &str) -> bool {
+    value || layer.tool_bool(tool_name, key).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bwrap-layer-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_relative_paths_resolve_against_config_dir() {
+        let dir = tmp_dir("relative-paths");
+        let config_path = dir.join(".bw.toml");
+        fs::write(
+            &config_path,
+            r#"
+allow_ro_paths = ["vendor"]
+allow_rw_paths = ["build"]
+bw_relay_path = "bin/bw-relay"
+"#,
+        )
+        .unwrap();
+
+        let layer = PartialCommonConfig::load_from_file(&config_path).unwrap();
+        assert_eq!(layer.allow_ro_paths, vec![dir.join("vendor")]);
+        assert_eq!(layer.allow_rw_paths, vec![dir.join("build")]);
+        assert_eq!(layer.bw_relay_path, Some(dir.join("bin/bw-relay")));
+    }
+
+    #[test]
+    fn test_absolute_and_tilde_paths_are_left_alone() {
+        let dir = tmp_dir("absolute-tilde-paths");
+        let config_path = dir.join(".bw.toml");
+        fs::write(
+            &config_path,
+            r#"
+allow_ro_paths = ["/srv/shared"]
+bw_relay_path = "~/.local/bin/bw-relay"
+"#,
+        )
+        .unwrap();
+
+        let layer = PartialCommonConfig::load_from_file(&config_path).unwrap();
+        assert_eq!(layer.allow_ro_paths, vec![PathBuf::from("/srv/shared")]);
+        assert_eq!(layer.bw_relay_path, Some(PathBuf::from("~/.local/bin/bw-relay")));
+    }
+}
+
@@ -82,6 +82,8 @@ impl LearningRecorder {
                     description: self.session_name.clone(),
                     hosts: Vec::new(),
                     hosts_deny: Vec::new(),
+                    ipv4_ranges: Vec::new(),
+                    ipv6_ranges: Vec::new(),
                     groups: Vec::new(),
                 });
 
@@ -117,6 +119,8 @@ impl LearningRecorder {
                     description: denied_group_name,
                     hosts: Vec::new(),
                     hosts_deny: Vec::new(),
+                    ipv4_ranges: Vec::new(),
+                    ipv6_ranges: Vec::new(),
                     groups: Vec::new(),
                 });
 
@@ -134,6 +138,17 @@ impl LearningRecorder {
     }
 
     /// Flush the in-memory config to disk
+    ///
+    /// The learned allow/deny host list can reveal what a sandboxed tool
+    /// talked to, so the file is created directly with
+    /// `common.proxy.learning_output_mode` (`0600` by default) rather than
+    /// written with default permissions and narrowed afterward, and
+    /// written via a same-directory temp file + `fsync` + rename so a
+    /// reader never observes a partially written or truncated file even
+    /// across a crash. A session that has grown past
+    /// `common.proxy.learning_output_max_size` fails the flush with
+    /// `SandboxError::ConfigTooLarge` rather than silently writing an
+    /// ever-larger file, unless `BW_ALLOW_LARGE_CONFIG` is set.
     pub fn flush(&self) -> Result<()> {
         let output_path = self.output_path.lock().ok()
             .and_then(|path| path.as_ref().cloned());
@@ -145,7 +160,23 @@ impl LearningRecorder {
             let toml_str = toml::to_string_pretty(&*config)
                 .map_err(|e| SandboxError::ConfigError(format!("Failed to serialize config: {e}")))?;
 
-            fs::write(&path, toml_str)?;
+            let mode = config.common.proxy.learning_output_mode;
+            let owner = config.common.proxy.learning_output_owner.clone();
+            let max_size = config.common.proxy.learning_output_max_size;
+            drop(config);
+
+            if !super::loader::large_config_allowed() && toml_str.len() as u64 > max_size {
+                return Err(SandboxError::ConfigTooLarge {
+                    path,
+                    size: toml_str.len() as u64,
+                    limit: max_size,
+                });
+            }
+
+            write_with_mode(&path, &toml_str, mode)?;
+            if let Some(owner) = owner {
+                std::os::unix::fs::chown(&path, owner.uid, owner.gid)?;
+            }
         }
 
         Ok(())
@@ -228,6 +259,33 @@ impl LearningRecorderTrait for LearningRecorder {
     }
 }
 
+/// Write `contents` to `path` atomically: create a sibling temp file with
+/// `mode` from the start (rather than via `fs::write` + a separate
+/// `set_permissions` call, which would leave a window where the file has
+/// the umask-derived default permissions instead of `mode`), `fsync` it,
+/// then `rename` over `path` so a reader never sees a truncated or
+/// half-written file even if the process crashes mid-flush.
+fn write_with_mode(path: &std::path::Path, contents: &str, mode: u32) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let tmp_name = path.file_name().and_then(|n| n.to_str()).map(|n| format!(".{n}.tmp")).unwrap_or_else(|| ".learned.tmp".to_string());
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
 /// Statistics about recorded learning data
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LearningStats {
@@ -347,4 +405,75 @@ mod tests {
         assert!(content.contains("blocked.com"));
         assert!(content.contains("malware.com"));
     }
+
+    #[test]
+    fn test_flush_defaults_to_mode_0600() {
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::NamedTempFile;
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let file_path = temp_file.path().to_path_buf();
+
+        let recorder = LearningRecorder::with_output_path("mode_session", file_path.clone()).unwrap();
+        recorder.record_host("example.com");
+        recorder.flush().unwrap();
+
+        let perms = fs::metadata(&file_path).unwrap().permissions();
+        assert_eq!(perms.mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_flush_honors_configured_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("bwrap-learning-test-mode-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("learned.toml");
+
+        let recorder = LearningRecorder::with_output_path("custom_mode_session", file_path.clone()).unwrap();
+        if let Ok(mut config) = recorder.config.lock() {
+            config.common.proxy.learning_output_mode = 0o640;
+        }
+        recorder.record_host("example.com");
+        recorder.flush().unwrap();
+
+        let perms = fs::metadata(&file_path).unwrap().permissions();
+        assert_eq!(perms.mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn test_flush_rejects_output_over_configured_max_size() {
+        let dir = std::env::temp_dir().join(format!("bwrap-learning-test-size-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("learned.toml");
+
+        let recorder = LearningRecorder::with_output_path("size_session", file_path.clone()).unwrap();
+        if let Ok(mut config) = recorder.config.lock() {
+            config.common.proxy.learning_output_max_size = 4;
+        }
+        recorder.record_host("example.com");
+
+        let err = recorder.flush().unwrap_err();
+        assert!(matches!(err, SandboxError::ConfigTooLarge { .. }), "expected ConfigTooLarge, got {err:?}");
+        assert!(!file_path.exists(), "oversized flush must not leave a partial file behind");
+    }
+
+    #[test]
+    fn test_flush_atomic_rename_leaves_no_temp_file() {
+        use tempfile::NamedTempFile;
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let file_path = temp_file.path().to_path_buf();
+
+        let recorder = LearningRecorder::with_output_path("atomic_session", file_path.clone()).unwrap();
+        recorder.record_host("example.com");
+        recorder.flush().unwrap();
+
+        let tmp_name = format!(".{}.tmp", file_path.file_name().unwrap().to_str().unwrap());
+        let tmp_path = file_path.with_file_name(tmp_name);
+        assert!(!tmp_path.exists(), "flush should rename its temp file away, not leave it behind");
+        assert!(file_path.exists());
+    }
 }
@@ -2,15 +2,74 @@
 
 use super::schema::Config;
 use super::builtin;
+use super::merge::Merge;
 use crate::error::{Result, SandboxError};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use indexmap::IndexMap;
 
+/// System-wide config file, the lowest-priority file layer (below the user
+/// and project files); see `ConfigLoader::load_with_priority`.
+const SYSTEM_CONFIG_PATH: &str = "/etc/bwrap/config.toml";
+
+/// Older system-wide config path, checked as a fallback when
+/// `SYSTEM_CONFIG_PATH` isn't present, for installs from before the
+/// bw-claude -> bwrap rename (mirrors the same dual-naming fallback
+/// `find_user_config` already does for the per-user locations).
+const LEGACY_SYSTEM_CONFIG_PATH: &str = "/etc/bw-claude/config.toml";
+
+/// Default ceiling on a single config file's size, checked by
+/// `load_from_file_checked` before the file is even read. Generous for any
+/// hand-written TOML, but enough to reject a pathological file (a symlink
+/// into something huge or never-ending) from wedging a `ConfigWatcher`
+/// reload loop. Override via `--max-config-size`.
+pub const DEFAULT_MAX_CONFIG_SIZE: u64 = 1024 * 1024;
+
+/// Default ceiling `load_from_file` enforces on its own (see its doc
+/// comment) — much larger than `DEFAULT_MAX_CONFIG_SIZE` since it has to
+/// accommodate a long-running `LearningRecorder` session's output, not
+/// just a hand-written config. Bypass with `BW_ALLOW_LARGE_CONFIG=1` for a
+/// file that's legitimately this big.
+pub const DEFAULT_LEARNING_OUTPUT_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+const ALLOW_LARGE_CONFIG_ENV: &str = "BW_ALLOW_LARGE_CONFIG";
+
+pub(crate) fn large_config_allowed() -> bool {
+    matches!(env::var(ALLOW_LARGE_CONFIG_ENV), Ok(v) if v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Shared by `load_from_file`/`load_from_file_checked`: reject `path`
+/// outright if it's over `max_size` bytes, without reading it into memory
+/// first.
+fn check_size(path: &Path, max_size: u64) -> Result<()> {
+    let metadata = fs::metadata(path).map_err(|source| SandboxError::ConfigLoad {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    if metadata.len() > max_size {
+        return Err(SandboxError::ConfigTooLarge {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            limit: max_size,
+        });
+    }
+    Ok(())
+}
+
 pub struct ConfigLoader;
 
 impl ConfigLoader {
+    /// Find the system-wide config file, if present
+    pub fn find_system_config() -> Option<PathBuf> {
+        let p = PathBuf::from(SYSTEM_CONFIG_PATH);
+        if p.exists() {
+            return Some(p);
+        }
+        let p = PathBuf::from(LEGACY_SYSTEM_CONFIG_PATH);
+        p.exists().then_some(p)
+    }
+
     /// Find user config by checking environment and standard locations
     pub fn find_user_config() -> Option<PathBuf> {
         // 1. $BW_CLAUDE_CONFIG
@@ -21,16 +80,26 @@ impl ConfigLoader {
             }
         }
 
-        // 2. $XDG_CONFIG_HOME/bw-claude/config.toml
+        // 2. $XDG_CONFIG_HOME/bwrap/config.toml, falling back to the older
+        // $XDG_CONFIG_HOME/bw-claude/config.toml for existing installs
         if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
+            let p = PathBuf::from(&xdg).join("bwrap/config.toml");
+            if p.exists() {
+                return Some(p);
+            }
             let p = PathBuf::from(xdg).join("bw-claude/config.toml");
             if p.exists() {
                 return Some(p);
             }
         }
 
-        // 3. ~/.config/bw-claude/config.toml
+        // 3. ~/.config/bwrap/config.toml, falling back to the older
+        // ~/.config/bw-claude/config.toml for existing installs
         if let Ok(home) = env::var("HOME") {
+            let p = PathBuf::from(&home).join(".config/bwrap/config.toml");
+            if p.exists() {
+                return Some(p);
+            }
             let p = PathBuf::from(home).join(".config/bw-claude/config.toml");
             if p.exists() {
                 return Some(p);
@@ -40,11 +109,17 @@ impl ConfigLoader {
         None
     }
 
-    /// Find project config by searching up directory tree for .bwconfig.toml
+    /// Find project config by searching up the directory tree for
+    /// `.bwrap.toml`, falling back to the older `.bwconfig.toml` name at
+    /// each level for existing installs
     pub fn find_project_config() -> Option<PathBuf> {
         let mut current = env::current_dir().ok()?;
 
         loop {
+            let project_config = current.join(".bwrap.toml");
+            if project_config.exists() {
+                return Some(project_config);
+            }
             let project_config = current.join(".bwconfig.toml");
             if project_config.exists() {
                 return Some(project_config);
@@ -64,14 +139,65 @@ impl ConfigLoader {
     }
 
     /// Load config from a file
+    ///
+    /// Rejects files over `DEFAULT_LEARNING_OUTPUT_MAX_SIZE` unless
+    /// `BW_ALLOW_LARGE_CONFIG` is set — a blanket safety net distinct from
+    /// `load_from_file_checked`'s caller-supplied `max_size` (used by
+    /// `ConfigWatcher`/`load_with_priority` for their own, usually
+    /// tighter, limit). This matters most for `LearningRecorder`, which
+    /// reads its own output file back through this same path
+    /// (`with_output_path`/`set_output_path`) to resume a session, and
+    /// whose file only grows over a long-running session.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Config> {
         let path = path.as_ref();
+        if !large_config_allowed() {
+            check_size(path, DEFAULT_LEARNING_OUTPUT_MAX_SIZE)?;
+        }
+        Self::load_from_file_unchecked(path)
+    }
+
+    /// Like `load_from_file`, but reject the file outright if it's over
+    /// `max_size` bytes rather than read a pathological file into memory
+    /// just to parse it. Used by `ConfigWatcher`, where a reload is
+    /// triggered by arbitrary filesystem events and there's no human in the
+    /// loop to notice a file has gone wrong before it's picked up.
+    pub fn load_from_file_checked<P: AsRef<Path>>(path: P, max_size: u64) -> Result<Config> {
+        let path = path.as_ref();
+        check_size(path, max_size)?;
+        Self::load_from_file_unchecked(path)
+    }
+
+    /// Parse and migrate `path` with no size guard at all — `load_from_file`
+    /// and `load_from_file_checked` each apply their own before calling this.
+    fn load_from_file_unchecked(path: &Path) -> Result<Config> {
         let contents = fs::read_to_string(path).map_err(|source| SandboxError::ConfigLoad {
             path: path.to_path_buf(),
             source,
         })?;
 
-        let config: Config = toml::from_str(&contents)?;
+        let raw: toml::Value = toml::from_str(&contents)?;
+        let declared_version = raw
+            .get("common")
+            .and_then(|c| c.get("config_version"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(super::schema::CURRENT_CONFIG_VERSION)
+            .to_string();
+
+        let migrated = if declared_version == super::schema::CURRENT_CONFIG_VERSION {
+            raw
+        } else {
+            tracing::info!(
+                "Migrating config {} from version {} to {}",
+                path.display(),
+                declared_version,
+                super::schema::CURRENT_CONFIG_VERSION
+            );
+            super::migration::migrate_to_current(raw, &declared_version)?
+        };
+
+        let mut config: Config = migrated.try_into()?;
+        stamp_sources(&mut config, path);
+        resolve_config_relative_paths(&mut config, path);
         Ok(config)
     }
 
@@ -80,33 +206,13 @@ impl ConfigLoader {
         Ok(builtin::get_builtin().clone())
     }
 
-    /// Merge user config on top of built-in config
-    /// User config takes precedence: groups and policies are extended,
-    /// tool-specific settings override built-in
+    /// Merge user config on top of built-in config via `Merge`: named-map
+    /// fields (groups, filesystem configs, policies, tool configs, security
+    /// policies, seccomp profiles) are key-merged entry-by-entry rather
+    /// than replaced wholesale, and scalar/`Option` fields from
+    /// `override_cfg` (the higher-priority layer) win where it sets them.
     pub fn merge_configs(mut base: Config, override_cfg: Config) -> Config {
-        // Merge network groups: extend with overrides
-        for (name, group) in override_cfg.network.groups {
-            base.network.groups.insert(name, group);
-        }
-
-        // Merge filesystem configs: extend with overrides
-        for (name, fs_config) in override_cfg.filesystem.configs {
-            base.filesystem.configs.insert(name, fs_config);
-        }
-
-        // Merge policies: extend with overrides
-        for (name, policy) in override_cfg.policy.policies {
-            base.policy.policies.insert(name, policy);
-        }
-
-        // Merge tool configs: extend with overrides
-        for (name, tool_config) in override_cfg.tools {
-            base.tools.insert(name, tool_config);
-        }
-
-        // Override common config with user settings
-        base.common = override_cfg.common;
-
+        base.merge(override_cfg);
         base
     }
 
@@ -138,40 +244,61 @@ impl ConfigLoader {
     }
 
     /// Load with full config priority order
-    /// Priority: built-in < user < project < explicit
+    /// Priority: built-in < system < user < project < explicit < environment
+    ///
+    /// Mirrors the layered model Cargo's `GlobalContext` uses: each file
+    /// layer is merged over the previous one with `merge_configs`'s
+    /// `Merge` semantics (see `config::merge`), and `BW_`-prefixed environment variables are
+    /// applied last, on top of the fully merged result (see
+    /// `env_overrides::apply_env_overrides`).
     pub fn load_with_priority(explicit_config: Option<PathBuf>) -> Result<Config> {
+        Self::load_with_priority_checked(explicit_config, DEFAULT_MAX_CONFIG_SIZE)
+    }
+
+    /// Like `load_with_priority`, but enforce `max_size` on every file layer
+    /// via `load_from_file_checked` (see `ConfigWatcher`).
+    pub fn load_with_priority_checked(explicit_config: Option<PathBuf>, max_size: u64) -> Result<Config> {
         let mut configs = Vec::new();
 
         // 1. Built-in (lowest priority)
         configs.push(Self::load_builtin()?);
 
-        // 2. User config
+        // 2. System config
+        if let Some(system_path) = Self::find_system_config() {
+            tracing::debug!("Loading system config from {:?}", system_path);
+            configs.push(Self::load_from_file_checked(&system_path, max_size)?);
+        }
+
+        // 3. User config
         if let Some(user_path) = Self::find_user_config() {
             tracing::debug!("Loading user config from {:?}", user_path);
-            configs.push(Self::load_from_file(&user_path)?);
+            configs.push(Self::load_from_file_checked(&user_path, max_size)?);
         }
 
-        // 3. Project config
+        // 4. Project config
         if let Some(project_path) = Self::find_project_config() {
             tracing::debug!("Loading project config from {:?}", project_path);
-            configs.push(Self::load_from_file(&project_path)?);
+            configs.push(Self::load_from_file_checked(&project_path, max_size)?);
         }
 
-        // 4. Explicit --config option (highest priority)
+        // 5. Explicit --config option
         if let Some(explicit_path) = explicit_config {
             tracing::debug!("Loading explicit config from {:?}", explicit_path);
-            configs.push(Self::load_from_file(&explicit_path)?);
+            configs.push(Self::load_from_file_checked(&explicit_path, max_size)?);
         }
 
-        // Merge all configs, later ones override earlier ones
-        Ok(configs
+        // Merge all file layers, later ones override earlier ones
+        let merged = configs
             .into_iter()
             .reduce(|acc, cfg| Self::merge_configs(acc, cfg))
-            .unwrap_or_default())
+            .unwrap_or_default();
+
+        // 6. Environment-variable overrides (highest priority)
+        super::env_overrides::apply_env_overrides(merged)
     }
 
     /// Load config from optional path or default with built-in merge
-    /// Priority: built-in < user < project < explicit
+    /// Priority: built-in < system < user < project < explicit < environment
     pub fn load_or_default(path: Option<PathBuf>) -> Result<Config> {
         Self::load_with_priority(path)
     }
@@ -181,14 +308,178 @@ impl ConfigLoader {
         Self::load_or_default(path)
     }
 
-    /// Ensure config directory exists
+    /// Start watching the config chain (system/user/project/explicit) and
+    /// return a `WatchedConfig` whose `load()` stays current with
+    /// `load_with_priority`'s merged result — for long-running sandbox/proxy
+    /// tasks that want a lock-free view of the live config without needing
+    /// a resolved `PolicyEngine` for one specific policy (see
+    /// `ConfigWatcher` for that case instead).
+    pub fn watch(explicit_config: Option<PathBuf>) -> Result<super::watcher::WatchedConfig> {
+        super::watcher::WatchedConfig::start(explicit_config)
+    }
+
+    /// Ensure config directory exists, at the first location returned by
+    /// `find_writable_config_location` (falling back to the plain
+    /// `default_config_path` if, oddly, nothing is writable at all — the
+    /// subsequent write will then fail with a clear `io::Error` instead of
+    /// this function silently picking an unwritable path)
     pub fn ensure_config_dir() -> std::io::Result<PathBuf> {
-        let config_path = Self::default_config_path();
+        let config_path = Self::find_writable_config_location()
+            .map(|location| location.path)
+            .unwrap_or_else(Self::default_config_path);
         if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)?;
+            create_dir_all_mode(parent, 0o700)?;
         }
         Ok(config_path)
     }
+
+    /// Walk the same tiers `find_system_config`/`find_user_config` read
+    /// from (highest-priority, most system-wide, first) and return the
+    /// first whose parent directory this process can actually write to —
+    /// so `ensure_config_dir` and a "save learned config" flow write
+    /// somewhere that'll actually succeed, falling back gracefully from
+    /// `/etc` down to the per-user config dir when running unprivileged.
+    /// Unlike `find_system_config`/`find_user_config`, candidates don't
+    /// need to already exist — `parent_dir_is_writable` walks up to the
+    /// nearest existing ancestor, since `ensure_config_dir` will create
+    /// the rest with `create_dir_all_mode`.
+    pub fn find_writable_config_location() -> Option<WritableConfigLocation> {
+        Self::config_location_candidates()
+            .into_iter()
+            .find(|candidate| parent_dir_is_writable(&candidate.path))
+    }
+
+    /// Ordered candidate config locations, highest priority (most
+    /// system-wide) first; see `find_writable_config_location`.
+    fn config_location_candidates() -> Vec<WritableConfigLocation> {
+        let mut candidates = vec![
+            WritableConfigLocation { path: PathBuf::from(SYSTEM_CONFIG_PATH), tier: ConfigTier::System },
+            WritableConfigLocation { path: PathBuf::from(LEGACY_SYSTEM_CONFIG_PATH), tier: ConfigTier::System },
+        ];
+
+        if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
+            candidates.push(WritableConfigLocation { path: PathBuf::from(&xdg).join("bwrap/config.toml"), tier: ConfigTier::User });
+            candidates.push(WritableConfigLocation { path: PathBuf::from(xdg).join("bw-claude/config.toml"), tier: ConfigTier::User });
+        }
+        if let Ok(home) = env::var("HOME") {
+            candidates.push(WritableConfigLocation { path: PathBuf::from(&home).join(".config/bwrap/config.toml"), tier: ConfigTier::User });
+            candidates.push(WritableConfigLocation { path: PathBuf::from(home).join(".config/bw-claude/config.toml"), tier: ConfigTier::User });
+        }
+
+        candidates
+    }
+}
+
+/// Which tier of `ConfigLoader::find_writable_config_location`'s search a
+/// result came from, so callers can report where they're about to write
+/// (e.g. "saving learned config to the system-wide location").
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigTier {
+    /// `/etc/bwrap/config.toml` or its legacy `/etc/bw-claude` name
+    System,
+    /// `$XDG_CONFIG_HOME` or `~/.config`, under either the `bwrap` or
+    /// legacy `bw-claude` subdirectory
+    User,
+}
+
+/// A candidate config path paired with which search tier it came from;
+/// see `ConfigLoader::find_writable_config_location`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WritableConfigLocation {
+    pub path: PathBuf,
+    pub tier: ConfigTier,
+}
+
+/// True if `path`'s parent directory exists and is writable by this
+/// process, or — if the parent doesn't exist yet — if the nearest
+/// existing ancestor is, since that's as far as `create_dir_all_mode`
+/// would need to reach. Probes with an actual write-and-remove rather
+/// than just inspecting permission bits, since those alone miss
+/// read-only mounts, ACLs, and the non-owner/non-group case.
+fn parent_dir_is_writable(path: &Path) -> bool {
+    let Some(parent) = path.parent() else { return false };
+
+    let mut candidate = parent;
+    loop {
+        if candidate.exists() {
+            break;
+        }
+        match candidate.parent() {
+            Some(next) => candidate = next,
+            None => return false,
+        }
+    }
+    if !candidate.is_dir() {
+        return false;
+    }
+
+    let probe = candidate.join(format!(".bwrap-writable-check-{}", std::process::id()));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Like `fs::create_dir_all`, but newly created directories are made with
+/// `mode` rather than the umask-derived default, so config directories
+/// that may hold learned host lists (see `LearningRecorder::flush`) aren't
+/// left world-readable.
+fn create_dir_all_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new().recursive(true).mode(mode).create(path)
+}
+
+/// Record which file this layer's named `filesystem.configs`/`policy.policies`/
+/// `seccomp.profiles` entries came from, so a later resolution error (see
+/// `config::resolver`)
+/// can name where a bad `extends` or policy reference actually lives,
+/// rather than just the name that failed to resolve.
+fn stamp_sources(config: &mut Config, path: &Path) {
+    for name in config.filesystem.configs.keys() {
+        config.filesystem.sources.insert(name.clone(), path.to_path_buf());
+    }
+    for name in config.policy.policies.keys() {
+        config.policy.sources.insert(name.clone(), path.to_path_buf());
+    }
+    for name in config.seccomp.profiles.keys() {
+        config.seccomp.sources.insert(name.clone(), path.to_path_buf());
+    }
+}
+
+/// Resolve config-declared free-standing paths (`common.proxy.socket_dir`,
+/// `common.proxy.learning_output`) against this file's own directory when
+/// they're relative, so a shared config included from elsewhere (a user or
+/// system config referenced from an unrelated project) doesn't silently
+/// resolve against whatever directory happened to invoke it instead.
+///
+/// This does *not* apply to `FilesystemSpec`'s path-list fields
+/// (`ro_paths`, `ro_home_dirs`, ...): those are rule lists resolved against
+/// the sandboxed target directory (or `$HOME`) at mount time, by design —
+/// see `config::pathglob`'s module docs.
+fn resolve_config_relative_paths(config: &mut Config, path: &Path) {
+    let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) else {
+        return;
+    };
+
+    let proxy = &mut config.common.proxy;
+    if is_config_relative(&proxy.socket_dir) {
+        proxy.socket_dir = dir.join(&proxy.socket_dir);
+    }
+    if is_config_relative(&proxy.learning_output) {
+        proxy.learning_output = dir.join(&proxy.learning_output);
+    }
+}
+
+/// A path counts as config-relative if it's relative and not `~`-prefixed;
+/// `~/...` paths are home-relative by convention rather than relative to
+/// whichever config file set them. Shared with `config::layer`, whose
+/// `PartialCommonConfig::resolve_relative_paths` applies the same rule to
+/// `allow_ro_paths`/`allow_rw_paths`/`bw_relay_path`.
+pub(super) fn is_config_relative(path: &Path) -> bool {
+    path.is_relative() && !path.starts_with("~")
 }
 
 /// Create an empty config with no settings
@@ -205,16 +496,26 @@ fn empty_config() -> Config {
                 default_mode: "restrictive".to_string(),
                 socket_dir: PathBuf::from("/tmp"),
                 learning_output: PathBuf::from("~/.config/bw-claude/learned-domains.toml"),
+                learning_output_mode: 0o600,
+                learning_output_owner: None,
+                learning_output_max_size: DEFAULT_LEARNING_OUTPUT_MAX_SIZE,
+                upstream: super::schema::UpstreamProxyConfig::None,
             },
         },
         network: NetworkConfig::default(),
         filesystem: super::schema::FilesystemConfig {
             configs: IndexMap::new(),
+            sources: IndexMap::new(),
         },
         policy: super::schema::PolicyConfig {
             policies: IndexMap::new(),
+            sources: IndexMap::new(),
         },
         tools: IndexMap::new(),
+        security: super::schema::SecurityConfig {
+            tools: IndexMap::new(),
+        },
+        seccomp: super::schema::SeccompConfig::default(),
     }
 }
 
@@ -252,4 +553,215 @@ default_mode = "open"
         let result: std::result::Result<Config, _> = toml::from_str(toml_str);
         assert!(result.is_ok());
     }
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bwrap-loader-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_relative_learning_output_resolves_against_config_dir() {
+        let dir = tmp_dir("learning-output");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[common.proxy]
+learning_output = "logs/learned.toml"
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_from_file(&config_path).unwrap();
+        assert_eq!(config.common.proxy.learning_output, dir.join("logs/learned.toml"));
+    }
+
+    #[test]
+    fn test_tilde_learning_output_is_left_alone() {
+        let dir = tmp_dir("learning-output-tilde");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[common.proxy]
+learning_output = "~/.config/bw-claude/learned-domains.toml"
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_from_file(&config_path).unwrap();
+        assert_eq!(
+            config.common.proxy.learning_output,
+            PathBuf::from("~/.config/bw-claude/learned-domains.toml")
+        );
+    }
+
+    #[test]
+    fn test_absolute_learning_output_is_left_alone() {
+        let dir = tmp_dir("learning-output-absolute");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[common.proxy]
+learning_output = "/var/log/bwrap/learned.toml"
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_from_file(&config_path).unwrap();
+        assert_eq!(config.common.proxy.learning_output, PathBuf::from("/var/log/bwrap/learned.toml"));
+    }
+
+    #[test]
+    fn test_create_dir_all_mode_sets_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tmp_dir("create-dir-mode");
+        let target = dir.join("nested/config");
+        create_dir_all_mode(&target, 0o700).unwrap();
+
+        let perms = fs::metadata(&target).unwrap().permissions();
+        assert_eq!(perms.mode() & 0o777, 0o700);
+    }
+
+    #[test]
+    fn test_parent_dir_is_writable_for_existing_writable_dir() {
+        let dir = tmp_dir("writable-parent");
+        assert!(parent_dir_is_writable(&dir.join("config.toml")));
+    }
+
+    #[test]
+    fn test_parent_dir_is_writable_walks_up_to_existing_ancestor() {
+        let dir = tmp_dir("writable-ancestor");
+        // `nested/deeper` doesn't exist yet, but `dir` does and is writable.
+        assert!(parent_dir_is_writable(&dir.join("nested/deeper/config.toml")));
+    }
+
+    #[test]
+    fn test_parent_dir_is_writable_false_for_readonly_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tmp_dir("readonly-parent");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o500)).unwrap();
+
+        let result = parent_dir_is_writable(&dir.join("config.toml"));
+
+        // Restore so tmp_dir's own cleanup on a later run can remove it.
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        assert!(!result, "a read-only directory must not be reported writable");
+    }
+
+    #[test]
+    fn test_first_writable_candidate_prefers_system_tier_when_writable() {
+        let dir = tmp_dir("candidate-order");
+        let system_path = dir.join("etc-config.toml");
+        let user_path = dir.join("user-config.toml");
+
+        let candidates = vec![
+            WritableConfigLocation { path: system_path.clone(), tier: ConfigTier::System },
+            WritableConfigLocation { path: user_path, tier: ConfigTier::User },
+        ];
+
+        let found = candidates.into_iter().find(|c| parent_dir_is_writable(&c.path)).unwrap();
+        assert_eq!(found.path, system_path);
+        assert_eq!(found.tier, ConfigTier::System);
+    }
+
+    #[test]
+    fn test_load_from_file_migrates_legacy_version() {
+        let dir = tmp_dir("migrate-legacy");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[common]
+config_version = "0.9"
+
+[network.groups.corp]
+ip_ranges = ["10.0.0.0/8", "fd00::/8"]
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_from_file(&config_path).unwrap();
+        assert_eq!(config.common.config_version, super::super::schema::CURRENT_CONFIG_VERSION);
+        let group = &config.network.groups["corp"];
+        assert_eq!(group.ipv4_ranges, vec!["10.0.0.0/8".to_string()]);
+        assert_eq!(group.ipv6_ranges, vec!["fd00::/8".to_string()]);
+    }
+
+    #[test]
+    fn test_load_from_file_leaves_current_version_unmigrated() {
+        let dir = tmp_dir("migrate-current");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[common]
+config_version = "1.0"
+verbose = true
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_from_file(&config_path).unwrap();
+        assert_eq!(config.common.config_version, super::super::schema::CURRENT_CONFIG_VERSION);
+        assert!(config.common.verbose);
+    }
+
+    #[test]
+    fn test_check_size_rejects_over_limit_and_allows_within() {
+        let dir = tmp_dir("check-size");
+        let path = dir.join("blob");
+        fs::write(&path, "0123456789").unwrap();
+
+        assert!(check_size(&path, 5).is_err());
+        assert!(check_size(&path, 10).is_ok());
+    }
+
+    #[test]
+    fn test_load_from_file_checked_rejects_oversized_file() {
+        let dir = tmp_dir("oversized");
+        let config_path = dir.join("config.toml");
+        fs::write(&config_path, "[common]\nverbose = true\n").unwrap();
+
+        let err = ConfigLoader::load_from_file_checked(&config_path, 4).unwrap_err();
+        assert!(matches!(err, SandboxError::ConfigTooLarge { limit: 4, .. }));
+    }
+
+    #[test]
+    fn test_load_from_file_checked_allows_file_within_limit() {
+        let dir = tmp_dir("within-limit");
+        let config_path = dir.join("config.toml");
+        fs::write(&config_path, "[common]\nverbose = true\n").unwrap();
+
+        let config = ConfigLoader::load_from_file_checked(&config_path, DEFAULT_MAX_CONFIG_SIZE).unwrap();
+        assert!(config.common.verbose);
+    }
+
+    #[test]
+    fn test_stamp_sources_records_defining_file() {
+        let dir = tmp_dir("sources");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"
+[filesystem.configs.dev]
+ro_paths = ["src"]
+
+[policy.policies.dev]
+description = "dev policy"
+filesystem = "dev"
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::load_from_file(&config_path).unwrap();
+        assert_eq!(config.filesystem.sources.get("dev"), Some(&config_path));
+        assert_eq!(config.policy.sources.get("dev"), Some(&config_path));
+    }
 }
@@ -0,0 +1,501 @@
+//! `Merge` trait for deep, layered config composition
+//!
+//! `ConfigLoader::merge_configs` used to hand-roll field-by-field merging of
+//! `Config` on top of a lower-priority layer, which meant every named-map
+//! field (`filesystem.configs`, `policy.policies`, `seccomp.profiles`, ...)
+//! had to be remembered by hand, and it always replaced a same-named entry
+//! wholesale rather than merging its fields — a user policy that only set
+//! `description` would silently drop whatever `network`/`filesystem` a
+//! builtin policy of the same name had defined.
+//!
+//! `Merge` gives each type ownership of its own merge semantics instead:
+//! scalar/`Option` fields from the higher-priority layer (`other`) override
+//! when set, list fields are concatenated (`self`'s entries first, same as
+//! `ConfigLayer`'s `PartialCommonConfig::merge`), and named-map fields are
+//! key-merged with a per-entry recursive `merge` rather than replaced. The
+//! exception is access-granting lists (`HostGroup`, `NetworkPolicy`,
+//! `SeccompSpec` and `SecurityPolicy` allow fields), which are intersected
+//! instead, so a higher-priority layer can only keep or narrow them.
+
+use indexmap::IndexMap;
+use std::hash::Hash;
+
+use super::schema::{
+    Config, CommonConfig, FilesystemConfig, FilesystemSpec, NetworkPolicy, Policy, PolicyConfig,
+    ProxyConfig, SecurityConfig, SecurityPolicy, SeccompConfig, SeccompSpec, ToolConfig,
+    UpstreamProxyConfig,
+};
+use bwrap_proxy::config::{DefaultMode, FeedConfig, HostGroup, NetworkConfig, NetworkMode};
+
+/// Merge `other` into `self` in place, with `other` (the higher-priority
+/// layer) winning wherever it sets something.
+pub trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+/// Key-merge two maps: an entry only `self` has is kept, an entry only
+/// `other` has is inserted, and an entry both have is merged recursively
+/// rather than `other`'s copy replacing `self`'s outright.
+impl<K, V> Merge for IndexMap<K, V>
+where
+    K: Eq + Hash,
+    V: Merge,
+{
+    fn merge(&mut self, other: Self) {
+        for (key, value) in other {
+            match self.get_mut(&key) {
+                Some(existing) => existing.merge(value),
+                None => {
+                    self.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+impl Merge for Config {
+    fn merge(&mut self, other: Self) {
+        self.common.merge(other.common);
+        self.network.merge(other.network);
+        self.filesystem.merge(other.filesystem);
+        self.policy.merge(other.policy);
+        self.tools.merge(other.tools);
+        self.security.merge(other.security);
+        self.seccomp.merge(other.seccomp);
+    }
+}
+
+impl Merge for CommonConfig {
+    fn merge(&mut self, other: Self) {
+        self.config_version = other.config_version;
+        self.verbose = other.verbose;
+        self.proxy.merge(other.proxy);
+    }
+}
+
+impl Merge for ProxyConfig {
+    fn merge(&mut self, other: Self) {
+        self.default_mode = other.default_mode;
+        self.socket_dir = other.socket_dir;
+        self.learning_output = other.learning_output;
+        self.learning_output_mode = other.learning_output_mode;
+        if other.learning_output_owner.is_some() {
+            self.learning_output_owner = other.learning_output_owner;
+        }
+        self.learning_output_max_size = other.learning_output_max_size;
+        // `UpstreamProxyConfig::None` is what an unset `upstream` table
+        // deserializes to, so it doubles as "this layer didn't say
+        // anything" and only a non-`None` override takes effect.
+        if !matches!(other.upstream, UpstreamProxyConfig::None) {
+            self.upstream = other.upstream;
+        }
+    }
+}
+
+impl Merge for NetworkConfig {
+    fn merge(&mut self, other: Self) {
+        self.groups.merge(other.groups);
+        self.feeds.merge(other.feeds);
+        self.block_doh = other.block_doh;
+    }
+}
+
+impl Merge for HostGroup {
+    /// `hosts`/`ipv4_ranges`/`ipv6_ranges`/`groups` are allow-widening: each
+    /// entry only ever grants more access, so — same reasoning as
+    /// `SecurityPolicy` below — a higher-priority layer may only keep or
+    /// narrow the set a lower one defined, not add to it (`other` having
+    /// nothing in common with `self` here collapses the group empty, which
+    /// is the safe failure mode). `hosts_deny` is the opposite: every entry
+    /// only takes access away, so widening it can never loosen the
+    /// effective policy and it's still concatenated.
+    fn merge(&mut self, other: Self) {
+        if !other.description.is_empty() {
+            self.description = other.description;
+        }
+        self.hosts.retain(|h| other.hosts.contains(h));
+        self.hosts_deny.extend(other.hosts_deny);
+        self.ipv4_ranges.retain(|r| other.ipv4_ranges.contains(r));
+        self.ipv6_ranges.retain(|r| other.ipv6_ranges.contains(r));
+        self.groups.retain(|g| other.groups.contains(g));
+    }
+}
+
+impl Merge for FeedConfig {
+    fn merge(&mut self, other: Self) {
+        // No list-valued fields to concatenate; a redefined feed is a
+        // wholesale replacement of the one a lower layer declared.
+        *self = other;
+    }
+}
+
+impl Merge for FilesystemConfig {
+    fn merge(&mut self, other: Self) {
+        self.configs.merge(other.configs);
+        for (name, source) in other.sources {
+            self.sources.insert(name, source);
+        }
+    }
+}
+
+impl Merge for FilesystemSpec {
+    fn merge(&mut self, other: Self) {
+        if other.description.is_some() {
+            self.description = other.description;
+        }
+        self.ro_home_dirs.extend(other.ro_home_dirs);
+        self.rw_home_dirs.extend(other.rw_home_dirs);
+        self.tmp_overlay_home_dirs.extend(other.tmp_overlay_home_dirs);
+        self.ro_home_files.extend(other.ro_home_files);
+        self.rw_home_files.extend(other.rw_home_files);
+        self.essential_etc_files.extend(other.essential_etc_files);
+        self.essential_etc_dirs.extend(other.essential_etc_dirs);
+        self.system_paths.extend(other.system_paths);
+        self.ro_paths.extend(other.ro_paths);
+        self.rw_paths.extend(other.rw_paths);
+        self.extends.extend(other.extends);
+    }
+}
+
+impl Merge for PolicyConfig {
+    fn merge(&mut self, other: Self) {
+        self.policies.merge(other.policies);
+        for (name, source) in other.sources {
+            self.sources.insert(name, source);
+        }
+    }
+}
+
+impl Merge for Policy {
+    fn merge(&mut self, other: Self) {
+        if other.description.is_some() {
+            self.description = other.description;
+        }
+        self.network.merge(other.network);
+        if other.filesystem.is_some() {
+            self.filesystem = other.filesystem;
+        }
+        if other.seccomp.is_some() {
+            self.seccomp = other.seccomp;
+        }
+    }
+}
+
+/// Restrictiveness rank for `NetworkMode`: higher is more restrictive.
+/// `Open` (unrestricted) is least restrictive, `Disabled` (no network at
+/// all) is most; `Proxy` (filtered through the policy engine) sits between.
+fn network_mode_rank(mode: &NetworkMode) -> u8 {
+    match mode {
+        NetworkMode::Open => 0,
+        NetworkMode::Proxy => 1,
+        NetworkMode::Disabled => 2,
+    }
+}
+
+/// The more restrictive of the two `NetworkMode`s, so merging never lets
+/// a layer widen it — only narrow or leave it unchanged.
+fn narrower_network_mode(current: NetworkMode, other: NetworkMode) -> NetworkMode {
+    if network_mode_rank(&other) >= network_mode_rank(&current) {
+        other
+    } else {
+        current
+    }
+}
+
+/// The more restrictive of the two `DefaultMode`s: `Deny` wins over
+/// `Allow` regardless of which side set it, so merging never lets a layer
+/// flip an allowlist-only policy into a denylist-only one.
+fn narrower_default_mode(current: DefaultMode, other: DefaultMode) -> DefaultMode {
+    match (current, other) {
+        (DefaultMode::Deny, _) | (_, DefaultMode::Deny) => DefaultMode::Deny,
+        _ => DefaultMode::Allow,
+    }
+}
+
+impl Merge for NetworkPolicy {
+    /// `network`/`default` are allow-widening exactly like `allow_groups`
+    /// below: `network` picks how much of the network a sandboxed process
+    /// can reach at all (`Open` > `Proxy` > `Disabled`) and `default` picks
+    /// the fallback verdict when no group matches (`Allow` is looser than
+    /// `Deny`). Letting a higher-priority layer override either outright —
+    /// e.g. a project `.bwrap.toml` setting `network = "open"` on a policy
+    /// the system/user tier resolved to `Proxy` — would bypass the
+    /// allow_groups intersection below entirely, so both are narrowed
+    /// (more-restrictive-wins) the same way. `allow_groups`/`groups` (its
+    /// back-compat alias) are allow-widening for the same reason — same
+    /// intersect-not-extend reasoning as `HostGroup`'s allow fields above,
+    /// so a policy resolved by name (e.g. the builtin "claude" policy)
+    /// can't have its actual `allow_groups` content loosened by a layer
+    /// discovered below the trusted system/user tier, even though the name
+    /// itself still resolves the same policy. `deny_groups` only ever
+    /// takes access away, so it's still concatenated.
+    fn merge(&mut self, other: Self) {
+        self.network = narrower_network_mode(self.network.clone(), other.network);
+        self.default = narrower_default_mode(self.default.clone(), other.default);
+        self.allow_groups.retain(|g| other.allow_groups.contains(g));
+        self.deny_groups.extend(other.deny_groups);
+        self.groups.retain(|g| other.groups.contains(g));
+    }
+}
+
+impl Merge for ToolConfig {
+    fn merge(&mut self, other: Self) {
+        self.enabled = other.enabled;
+        if other.proxy_mode.is_some() {
+            self.proxy_mode = other.proxy_mode;
+        }
+        if other.default_policy.is_some() {
+            self.default_policy = other.default_policy;
+        }
+    }
+}
+
+impl Merge for SecurityConfig {
+    fn merge(&mut self, other: Self) {
+        self.tools.merge(other.tools);
+    }
+}
+
+impl Merge for SecurityPolicy {
+    /// Like the allow fields of `HostGroup`, `NetworkPolicy` and
+    /// `SeccompSpec`, a higher-priority
+    /// layer here *narrows* rather than extends: `SecurityPolicy` is a
+    /// capability ceiling (see its doc comment), and priority runs
+    /// built-in < system < user < project < explicit < env, with the
+    /// project layer discovered by walking up from the sandboxed tool's own
+    /// working directory. A union would let a project-local
+    /// `.bwrap.toml`/`.bwconfig.toml` — including one inside an untrusted
+    /// repo the tool is pointed at — simply add env vars, RW prefixes, or
+    /// `allow_full_home_access = true` on top of whatever a system admin
+    /// locked down. Intersecting means a later layer can only ever keep
+    /// this tool's envelope the same size or shrink it.
+    fn merge(&mut self, other: Self) {
+        self.allowed_env_vars.retain(|v| other.allowed_env_vars.contains(v));
+        self.allowed_rw_path_prefixes
+            .retain(|p| other.allowed_rw_path_prefixes.contains(p));
+        self.allow_full_home_access = self.allow_full_home_access && other.allow_full_home_access;
+    }
+}
+
+impl Merge for SeccompConfig {
+    fn merge(&mut self, other: Self) {
+        self.profiles.merge(other.profiles);
+        for (name, source) in other.sources {
+            self.sources.insert(name, source);
+        }
+    }
+}
+
+impl Merge for SeccompSpec {
+    /// `allow_syscalls` is allow-widening (a non-empty list switches the
+    /// whole profile to allowlist mode, and each entry only ever permits
+    /// more) and `extends` pulls in another named profile's rules wholesale,
+    /// so both get the same intersect-not-extend treatment as `HostGroup`'s
+    /// allow fields above — a layer below the trusted system/user tier
+    /// can't widen an existing profile by adding syscalls or grafting on an
+    /// extra `extends` reference. `deny_syscalls` only ever blocks more, so
+    /// widening it can never loosen the effective profile and it's still
+    /// concatenated.
+    fn merge(&mut self, other: Self) {
+        if other.description.is_some() {
+            self.description = other.description;
+        }
+        self.deny_syscalls.extend(other.deny_syscalls);
+        self.allow_syscalls.retain(|s| other.allow_syscalls.contains(s));
+        self.extends.retain(|e| other.extends.contains(e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_merge_keeps_self_only_entries_and_key_merges_shared_ones() {
+        let mut base: IndexMap<String, FilesystemSpec> = IndexMap::new();
+        base.insert(
+            "dev".to_string(),
+            FilesystemSpec {
+                ro_paths: vec!["src".to_string()],
+                ..Default::default()
+            },
+        );
+        base.insert("only-base".to_string(), FilesystemSpec::default());
+
+        let mut overrides: IndexMap<String, FilesystemSpec> = IndexMap::new();
+        overrides.insert(
+            "dev".to_string(),
+            FilesystemSpec {
+                ro_paths: vec!["tests".to_string()],
+                ..Default::default()
+            },
+        );
+        overrides.insert("only-override".to_string(), FilesystemSpec::default());
+
+        base.merge(overrides);
+
+        assert!(base.contains_key("only-base"));
+        assert!(base.contains_key("only-override"));
+        assert_eq!(base["dev"].ro_paths, vec!["src".to_string(), "tests".to_string()]);
+    }
+
+    #[test]
+    fn proxy_config_upstream_override_only_applies_when_set() {
+        let mut base = ProxyConfig {
+            upstream: UpstreamProxyConfig::Global { url: "http://base:8080".to_string() },
+            ..ProxyConfig::default()
+        };
+        let other = ProxyConfig::default();
+
+        base.merge(other);
+
+        assert!(matches!(base.upstream, UpstreamProxyConfig::Global { .. }));
+    }
+
+    #[test]
+    fn security_policy_merge_intersects_instead_of_unioning() {
+        let mut system = SecurityPolicy {
+            allowed_env_vars: vec!["GEMINI_API_KEY".to_string()],
+            allowed_rw_path_prefixes: vec!["./build".to_string()],
+            allow_full_home_access: false,
+        };
+        let project = SecurityPolicy {
+            allowed_env_vars: vec!["GEMINI_API_KEY".to_string(), "AWS_SECRET_KEY".to_string()],
+            allowed_rw_path_prefixes: vec!["./build".to_string(), "/".to_string()],
+            allow_full_home_access: true,
+        };
+
+        system.merge(project);
+
+        // The project layer only ever narrows the system-set ceiling: it
+        // cannot add AWS_SECRET_KEY, widen the RW prefix to "/", or flip on
+        // full home access.
+        assert_eq!(system.allowed_env_vars, vec!["GEMINI_API_KEY".to_string()]);
+        assert_eq!(system.allowed_rw_path_prefixes, vec!["./build".to_string()]);
+        assert!(!system.allow_full_home_access);
+    }
+
+    #[test]
+    fn security_policy_merge_can_narrow_further() {
+        let mut system = SecurityPolicy {
+            allowed_env_vars: vec!["GEMINI_API_KEY".to_string(), "AWS_SECRET_KEY".to_string()],
+            allowed_rw_path_prefixes: vec!["./build".to_string()],
+            allow_full_home_access: true,
+        };
+        let project = SecurityPolicy {
+            allowed_env_vars: vec!["GEMINI_API_KEY".to_string()],
+            allowed_rw_path_prefixes: vec![],
+            allow_full_home_access: true,
+        };
+
+        system.merge(project);
+
+        assert_eq!(system.allowed_env_vars, vec!["GEMINI_API_KEY".to_string()]);
+        assert!(system.allowed_rw_path_prefixes.is_empty());
+        assert!(system.allow_full_home_access);
+    }
+
+    #[test]
+    fn network_policy_merge_intersects_allow_but_extends_deny() {
+        let mut system = NetworkPolicy {
+            allow_groups: vec!["github".to_string()],
+            deny_groups: vec!["tracking".to_string()],
+            ..NetworkPolicy::default()
+        };
+        let project = NetworkPolicy {
+            allow_groups: vec!["github".to_string(), "attacker-controlled".to_string()],
+            deny_groups: vec!["ads".to_string()],
+            ..NetworkPolicy::default()
+        };
+
+        system.merge(project);
+
+        // A lower-trust layer can't widen the resolved policy's allow_groups...
+        assert_eq!(system.allow_groups, vec!["github".to_string()]);
+        // ...but widening deny_groups only takes access away, so it's fine.
+        assert_eq!(system.deny_groups, vec!["tracking".to_string(), "ads".to_string()]);
+    }
+
+    #[test]
+    fn network_policy_merge_narrows_network_and_default_but_never_widens() {
+        // A project layer trying to loosen a filtered, deny-by-default
+        // policy into full open access must not succeed.
+        let mut system = NetworkPolicy {
+            network: NetworkMode::Proxy,
+            default: DefaultMode::Deny,
+            ..NetworkPolicy::default()
+        };
+        let project = NetworkPolicy {
+            network: NetworkMode::Open,
+            default: DefaultMode::Allow,
+            ..NetworkPolicy::default()
+        };
+
+        system.merge(project);
+
+        assert_eq!(system.network, NetworkMode::Proxy);
+        assert_eq!(system.default, DefaultMode::Deny);
+
+        // A project layer IS allowed to narrow further, e.g. down to no
+        // network access at all / its own stricter default.
+        let mut system = NetworkPolicy {
+            network: NetworkMode::Proxy,
+            default: DefaultMode::Allow,
+            ..NetworkPolicy::default()
+        };
+        let project = NetworkPolicy {
+            network: NetworkMode::Disabled,
+            default: DefaultMode::Deny,
+            ..NetworkPolicy::default()
+        };
+
+        system.merge(project);
+
+        assert_eq!(system.network, NetworkMode::Disabled);
+        assert_eq!(system.default, DefaultMode::Deny);
+    }
+
+    #[test]
+    fn host_group_merge_intersects_allow_fields_but_extends_hosts_deny() {
+        let mut system = HostGroup {
+            hosts: vec!["github.com".to_string()],
+            hosts_deny: vec!["evil.github.com".to_string()],
+            ipv4_ranges: vec!["140.82.0.0/16".to_string()],
+            ..HostGroup::default()
+        };
+        let project = HostGroup {
+            hosts: vec!["github.com".to_string(), "attacker.example".to_string()],
+            hosts_deny: vec!["also-evil.github.com".to_string()],
+            ipv4_ranges: vec![],
+            ..HostGroup::default()
+        };
+
+        system.merge(project);
+
+        assert_eq!(system.hosts, vec!["github.com".to_string()]);
+        assert_eq!(
+            system.hosts_deny,
+            vec!["evil.github.com".to_string(), "also-evil.github.com".to_string()]
+        );
+        assert!(system.ipv4_ranges.is_empty());
+    }
+
+    #[test]
+    fn seccomp_spec_merge_intersects_allow_syscalls_but_extends_deny_syscalls() {
+        let mut system = SeccompSpec {
+            allow_syscalls: vec!["read".to_string(), "write".to_string()],
+            deny_syscalls: vec!["ptrace".to_string()],
+            ..SeccompSpec::default()
+        };
+        let project = SeccompSpec {
+            allow_syscalls: vec!["read".to_string(), "write".to_string(), "execve".to_string()],
+            deny_syscalls: vec!["mount".to_string()],
+            ..SeccompSpec::default()
+        };
+
+        system.merge(project);
+
+        assert_eq!(system.allow_syscalls, vec!["read".to_string(), "write".to_string()]);
+        assert_eq!(system.deny_syscalls, vec!["ptrace".to_string(), "mount".to_string()]);
+    }
+}
@@ -0,0 +1,177 @@
+//! Migration of config files from an older `config_version` up to
+//! `CURRENT_CONFIG_VERSION`
+//!
+//! A `Migration` rewrites the raw, still-untyped `toml::Value` rather than
+//! `Config` itself, so a field that was renamed or dropped between
+//! versions can be moved or discarded before `serde` ever sees it — with
+//! `#[serde(deny_unknown_fields)]` on bwrap-core's own schema structs, an
+//! unmigrated stale field there would otherwise fail the whole parse. The
+//! `[network]` types come from `bwrap_proxy` without that attribute, so a
+//! stale field under `network` (such as `ip_ranges`) is instead silently
+//! dropped by `serde`. `ConfigLoader::load_from_file` runs this chain
+//! transitively (e.g. 0.9 -> 1.0 -> 1.1) before deserializing, the same
+//! `oldconfig` compatibility path other config-driven daemons keep so
+//! users' existing files keep loading across releases.
+
+use super::schema::CURRENT_CONFIG_VERSION;
+use crate::error::Result;
+
+/// One step in the migration chain: rewrites a config from `from_version`
+/// to `to_version`
+pub trait Migration {
+    fn from_version(&self) -> &str;
+    fn to_version(&self) -> &str;
+    fn migrate(&self, value: toml::Value) -> Result<toml::Value>;
+}
+
+/// The full migration chain, ordered oldest-first. `migrate_to_current`
+/// walks however many of these apply starting from a file's declared
+/// version; new steps are appended here as the schema changes.
+fn migrations() -> Vec<Box<dyn Migration>> {
+    vec![Box::new(SplitLegacyIpRanges)]
+}
+
+/// 0.9 -> 1.0: `network.groups.*.ip_ranges` (a single mixed list of IPv4
+/// and IPv6 CIDRs) was split into separate `ipv4_ranges`/`ipv6_ranges`
+/// fields (see `HostGroup`), since `LearningRecorder::record_ip` and
+/// `HostMatcher` need to know which address family a range belongs to
+/// without parsing it first. A 0.9 file's combined list is split here by
+/// the presence of a `:` (CIDRs are either dotted-decimal IPv4 or
+/// colon-separated IPv6, so this is enough to route each entry without
+/// pulling in a CIDR-parsing crate just for this one-time rewrite); an
+/// entry that's neither is dropped (and logged).
+struct SplitLegacyIpRanges;
+
+impl Migration for SplitLegacyIpRanges {
+    fn from_version(&self) -> &str {
+        "0.9"
+    }
+
+    fn to_version(&self) -> &str {
+        "1.0"
+    }
+
+    fn migrate(&self, mut value: toml::Value) -> Result<toml::Value> {
+        let Some(groups) = value
+            .get_mut("network")
+            .and_then(|n| n.get_mut("groups"))
+            .and_then(|g| g.as_table_mut())
+        else {
+            return Ok(value);
+        };
+
+        for (name, group) in groups.iter_mut() {
+            let Some(table) = group.as_table_mut() else {
+                continue;
+            };
+            let Some(toml::Value::Array(legacy_ranges)) = table.remove("ip_ranges") else {
+                continue;
+            };
+
+            let mut ipv4 = Vec::new();
+            let mut ipv6 = Vec::new();
+            for range in legacy_ranges {
+                let Some(cidr) = range.as_str() else { continue };
+                if cidr.contains(':') {
+                    ipv6.push(toml::Value::String(cidr.to_string()));
+                } else if cidr.contains('.') {
+                    ipv4.push(toml::Value::String(cidr.to_string()));
+                } else {
+                    tracing::warn!("Dropping unparsable legacy ip_ranges entry '{cidr}' in group '{name}'");
+                }
+            }
+
+            if !ipv4.is_empty() {
+                table.insert("ipv4_ranges".to_string(), toml::Value::Array(ipv4));
+            }
+            if !ipv6.is_empty() {
+                table.insert("ipv6_ranges".to_string(), toml::Value::Array(ipv6));
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Apply every migration that chains from `declared_version` up through
+/// `CURRENT_CONFIG_VERSION`, stamping `common.config_version` with the
+/// final version reached. A file already at (or ahead of) the current
+/// version, or one whose declared version has no registered migration
+/// step, is returned unchanged (aside from the version stamp) — `serde`
+/// gets the final say on whether its fields still make sense.
+pub fn migrate_to_current(mut value: toml::Value, declared_version: &str) -> Result<toml::Value> {
+    let mut version = declared_version.to_string();
+    let chain = migrations();
+
+    while version != CURRENT_CONFIG_VERSION {
+        let Some(step) = chain.iter().find(|m| m.from_version() == version) else {
+            break;
+        };
+        value = step.migrate(value)?;
+        version = step.to_version().to_string();
+    }
+
+    stamp_version(&mut value, &version);
+    Ok(value)
+}
+
+fn stamp_version(value: &mut toml::Value, version: &str) {
+    let table = match value {
+        toml::Value::Table(table) => table,
+        _ => return,
+    };
+    let common = table
+        .entry("common")
+        .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+    if let Some(common_table) = common.as_table_mut() {
+        common_table.insert("config_version".to_string(), toml::Value::String(version.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_legacy_combined_ip_ranges_by_address_family() {
+        let raw: toml::Value = toml::from_str(
+            r#"
+[network.groups.corp]
+ip_ranges = ["10.0.0.0/8", "fd00::/8", "not-a-cidr"]
+"#,
+        )
+        .unwrap();
+
+        let migrated = migrate_to_current(raw, "0.9").unwrap();
+
+        let group = &migrated["network"]["groups"]["corp"];
+        assert_eq!(group["ipv4_ranges"].as_array().unwrap().len(), 1);
+        assert_eq!(group["ipv6_ranges"].as_array().unwrap().len(), 1);
+        assert!(group.get("ip_ranges").is_none());
+        assert_eq!(migrated["common"]["config_version"].as_str(), Some(CURRENT_CONFIG_VERSION));
+    }
+
+    #[test]
+    fn leaves_current_version_config_untouched() {
+        let raw: toml::Value = toml::from_str(
+            r#"
+[common]
+verbose = true
+"#,
+        )
+        .unwrap();
+
+        let migrated = migrate_to_current(raw, CURRENT_CONFIG_VERSION).unwrap();
+        assert_eq!(migrated["common"]["verbose"].as_bool(), Some(true));
+        assert_eq!(migrated["common"]["config_version"].as_str(), Some(CURRENT_CONFIG_VERSION));
+    }
+
+    #[test]
+    fn unknown_declared_version_is_left_for_serde_to_judge() {
+        let raw: toml::Value = toml::from_str("[common]\nverbose = true\n").unwrap();
+        let migrated = migrate_to_current(raw, "0.1").unwrap();
+        // No migration registered for "0.1" -> no step runs, so the final
+        // version reached (and stamped) is still "0.1".
+        assert_eq!(migrated["common"]["config_version"].as_str(), Some("0.1"));
+    }
+}
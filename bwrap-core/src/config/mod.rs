@@ -10,15 +10,31 @@ pub mod loader;
 pub mod resolver;
 pub mod learning;
 pub mod builtin;
+pub mod watcher;
+pub mod env_overrides;
+pub mod pathglob;
+pub mod layer;
+pub mod merge;
+pub mod migration;
 
 // Re-export commonly used types
-pub use sandbox::{HomeAccessMode, SandboxConfig, ToolConfig, NetworkMode};
+pub use sandbox::{CommitMode, HomeAccessMode, SandboxConfig, ToolConfig, NetworkMode, UserMode};
 pub use schema::{
-    Config, CommonConfig, FilesystemConfig, FilesystemSpec,
+    Config, CommonConfig, FileOwner, FilesystemConfig, FilesystemSpec,
     NetworkPolicy, Policy, PolicyConfig, ProxyConfig, ProxyMode,
+    SeccompConfig, SeccompSpec,
+    SecurityConfig, SecurityPolicy, UpstreamDomainRule, UpstreamProxyConfig,
+    CURRENT_CONFIG_VERSION,
 };
+pub use migration::{migrate_to_current, Migration};
+pub use merge::Merge;
 // Re-export network types from bwrap-proxy
 pub use bwrap_proxy::config::{DefaultMode, HostGroup, NetworkConfig};
-pub use loader::ConfigLoader;
-pub use resolver::{resolve_filesystem_config, resolve_policy};
+pub use loader::{
+    ConfigLoader, ConfigTier, WritableConfigLocation, DEFAULT_LEARNING_OUTPUT_MAX_SIZE, DEFAULT_MAX_CONFIG_SIZE,
+};
+pub use resolver::{resolve_filesystem_config, resolve_policy, resolve_seccomp_config};
 pub use learning::LearningRecorder;
+pub use watcher::{ConfigWatcher, SharedConfig, WatchedConfig};
+pub use pathglob::expand_path_rules;
+pub use layer::{apply_layered_config, apply_tool_bool, ConfigLayer, PartialCommonConfig};
@@ -0,0 +1,185 @@
+//! Glob-pattern and gitignore-style rule expansion for `FilesystemSpec` path lists
+//!
+//! `FilesystemSpec`'s path lists (`ro_paths`, `rw_paths`, `ro_home_dirs`, ...)
+//! are ordered rule sequences rather than plain literal paths: an entry may
+//! be a glob (`**/target`, `node_modules`) and a `!`-prefixed entry subtracts
+//! from whatever a broader pattern matched so far, in the order the entries
+//! appear. A negation removes only paths an earlier entry itself produced;
+//! it does not carve a subpath out of a directory an earlier entry matched,
+//! which stays in the result whole. This mirrors watchexec's ignore/glob matching, and composes
+//! correctly across `extends` because `merge_filesystem_specs` already
+//! concatenates these lists in order rather than set-unioning them — a
+//! parent's `**/target` plus a child's `!**/target/release` resolves the
+//! same regardless of which config declared which rule.
+//!
+//! Expansion itself happens against a concrete base directory (the project's
+//! target dir, or `$HOME` for the home-relative lists) once that directory is
+//! known, which is at mount time in `SandboxBuilder` rather than at config
+//! resolution time — `resolve_filesystem_config` only composes the rule
+//! lists, since it runs before the target directory has been determined.
+
+use indexmap::IndexSet;
+use std::path::{Path, PathBuf};
+
+/// Expand an ordered list of glob rules (optionally `!`-negated) against
+/// `base`, honoring a `.bwrapignore` file in `base` if present, and return
+/// the concrete, existing paths that survive, in first-matched order.
+pub fn expand_path_rules(rules: &[String], base: &Path) -> Vec<PathBuf> {
+    let mut matches: IndexSet<PathBuf> = IndexSet::new();
+
+    for rule in rules.iter().chain(bwrapignore_rules(base).iter()) {
+        apply_rule(rule, base, &mut matches);
+    }
+
+    matches.into_iter().filter(|path| path.exists()).collect()
+}
+
+fn apply_rule(rule: &str, base: &Path, matches: &mut IndexSet<PathBuf>) {
+    let (negate, pattern) = match rule.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, rule),
+    };
+
+    for path in glob_expand(base, pattern) {
+        if negate {
+            matches.shift_remove(&path);
+        } else {
+            matches.insert(path);
+        }
+    }
+}
+
+fn glob_expand(base: &Path, pattern: &str) -> Vec<PathBuf> {
+    let pattern = pattern.trim_end_matches('/');
+
+    // Literal entries (no glob metacharacters) pass through as a single
+    // path, same as a plain path list entry before glob support existed, so
+    // existing configs keep working unchanged.
+    if !pattern.contains(['*', '?', '[']) {
+        return vec![resolve_base(base, pattern)];
+    }
+
+    let full_pattern = resolve_base(base, pattern);
+    match glob::glob(&full_pattern.to_string_lossy()) {
+        Ok(paths) => paths.filter_map(std::result::Result::ok).collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn resolve_base(base: &Path, pattern: &str) -> PathBuf {
+    let path = Path::new(pattern);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base.join(path)
+    }
+}
+
+/// Read `.bwrapignore` in `base`, if present, as additional ordered rules.
+///
+/// Follows gitignore sense (a bare line excludes) rather than our
+/// include-by-default rule sequence, so each plain line is inverted into a
+/// negation of whatever config rules already matched. A `!`-prefixed line
+/// is gitignore's re-include exception, but `base` is frequently an
+/// untrusted directory this function's own caller doesn't control (e.g.
+/// `ro_paths`/`rw_paths` resolve against the sandboxed project's own
+/// `target_dir`) — honoring it would invert into a plain, non-negated rule
+/// that gets *inserted*, letting that untrusted `.bwrapignore` re-include a
+/// path a trusted system/user config deliberately carved out with its own
+/// `!`-negation. So `.bwrapignore` can only ever subtract from the
+/// resolved set, never add to it: a `!` line is dropped rather than
+/// inverted.
+fn bwrapignore_rules(base: &Path) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(base.join(".bwrapignore")) else {
+        return vec![];
+    };
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .map(|line| format!("!{line}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bwrap-pathglob-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_literal_entry_passes_through() {
+        let base = tmp_dir("literal");
+        fs::create_dir_all(base.join(".cargo")).unwrap();
+
+        let expanded = expand_path_rules(&[".cargo".to_string()], &base);
+        assert_eq!(expanded, vec![base.join(".cargo")]);
+    }
+
+    #[test]
+    fn test_glob_expands_multiple_matches() {
+        let base = tmp_dir("glob");
+        fs::create_dir_all(base.join("a/target")).unwrap();
+        fs::create_dir_all(base.join("b/target")).unwrap();
+
+        let expanded = expand_path_rules(&["*/target".to_string()], &base);
+        assert_eq!(expanded.len(), 2);
+        assert!(expanded.contains(&base.join("a/target")));
+        assert!(expanded.contains(&base.join("b/target")));
+    }
+
+    #[test]
+    fn test_negation_subtracts_from_earlier_match() {
+        let base = tmp_dir("negate");
+        fs::create_dir_all(base.join("a/target")).unwrap();
+        fs::create_dir_all(base.join("b/target")).unwrap();
+
+        let expanded = expand_path_rules(
+            &["*/target".to_string(), "!b/target".to_string()],
+            &base,
+        );
+        assert_eq!(expanded, vec![base.join("a/target")]);
+    }
+
+    #[test]
+    fn test_bwrapignore_excludes_match() {
+        let base = tmp_dir("ignore");
+        fs::create_dir_all(base.join("a/target")).unwrap();
+        fs::create_dir_all(base.join("b/target")).unwrap();
+        fs::write(base.join(".bwrapignore"), "b/target\n").unwrap();
+
+        let expanded = expand_path_rules(&["*/target".to_string()], &base);
+        assert_eq!(expanded, vec![base.join("a/target")]);
+    }
+
+    #[test]
+    fn test_bwrapignore_reinclude_line_cannot_undo_a_trusted_negation() {
+        let base = tmp_dir("ignore-reinclude");
+        fs::create_dir_all(base.join("data/secrets")).unwrap();
+        fs::write(base.join(".bwrapignore"), "!data/secrets\n").unwrap();
+
+        // A trusted system/user rule list carries a `!data/secrets`
+        // negation (which removes nothing here, since only `data` itself
+        // was matched); an untrusted `.bwrapignore` `!data/secrets` line
+        // must not turn into a rule that adds `data/secrets` back.
+        let expanded = expand_path_rules(
+            &["data".to_string(), "!data/secrets".to_string()],
+            &base,
+        );
+        assert_eq!(expanded, vec![base.join("data")]);
+    }
+
+    #[test]
+    fn test_nonexistent_literal_entry_is_dropped() {
+        let base = tmp_dir("missing");
+        let expanded = expand_path_rules(&["does-not-exist".to_string()], &base);
+        assert!(expanded.is_empty());
+    }
+}
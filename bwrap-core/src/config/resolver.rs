@@ -1,6 +1,6 @@
 //! Configuration resolution with support for composition and validation
 
-use super::schema::{Config, FilesystemSpec, Policy};
+use super::schema::{Config, FilesystemSpec, Policy, SeccompSpec};
 use crate::error::{Result, SandboxError};
 use std::collections::HashSet;
 
@@ -10,19 +10,23 @@ pub fn resolve_filesystem_config(
     name: &str,
 ) -> Result<FilesystemSpec> {
     let mut visited = HashSet::new();
-    resolve_filesystem_recursive(config, name, &mut visited)
+    let key_path = format!("filesystem.configs.{name}");
+    resolve_filesystem_recursive(config, name, &mut visited, &key_path)
 }
 
 fn resolve_filesystem_recursive(
     config: &Config,
     name: &str,
     visited: &mut HashSet<String>,
+    key_path: &str,
 ) -> Result<FilesystemSpec> {
     if visited.contains(name) {
-        return Err(SandboxError::ConfigError(format!(
-            "Circular reference in filesystem config: {}",
-            name
-        )));
+        return Err(located_error(
+            config,
+            name,
+            key_path,
+            format!("Circular reference in filesystem config: {}", name),
+        ));
     }
     visited.insert(name.to_string());
 
@@ -31,7 +35,9 @@ fn resolve_filesystem_recursive(
         .configs
         .get(name)
         .ok_or_else(|| {
-            SandboxError::ConfigError(format!("Filesystem config not found: {}", name))
+            SandboxError::ConfigError(format!(
+                "{key_path}: Filesystem config not found: {name}"
+            ))
         })?
         .clone();
 
@@ -43,8 +49,9 @@ fn resolve_filesystem_recursive(
     // Resolve all extended configs and merge
     let mut merged = FilesystemSpec::default();
 
-    for parent_name in &spec.extends {
-        let parent = resolve_filesystem_recursive(config, parent_name, visited)?;
+    for (i, parent_name) in spec.extends.iter().enumerate() {
+        let parent_key_path = format!("{key_path}.extends[{i}]");
+        let parent = resolve_filesystem_recursive(config, parent_name, visited, &parent_key_path)?;
         merged = merge_filesystem_specs(merged, parent);
     }
 
@@ -54,18 +61,38 @@ fn resolve_filesystem_recursive(
     Ok(merged)
 }
 
+/// Build a config error naming the key path a lookup failed at (e.g.
+/// `filesystem.configs.dev.extends[0]`) and, if `name` is a config that was
+/// loaded from a file, which file that was — see `ConfigLoader`'s `sources`
+/// bookkeeping.
+fn located_error(config: &Config, name: &str, key_path: &str, message: String) -> SandboxError {
+    match config.filesystem.sources.get(name) {
+        Some(path) => SandboxError::ConfigError(format!(
+            "{key_path} (defined in {}): {message}",
+            path.display()
+        )),
+        None => SandboxError::ConfigError(format!("{key_path}: {message}")),
+    }
+}
+
 fn merge_filesystem_specs(
     base: FilesystemSpec,
     override_spec: FilesystemSpec,
 ) -> FilesystemSpec {
-    // For filesystem configs, we extend arrays rather than replace them
-    // This allows building up configurations by composing smaller pieces
+    // For filesystem configs, we extend arrays rather than replace them.
+    // This allows building up configurations by composing smaller pieces,
+    // and preserves rule order across `extends` so a parent's glob plus a
+    // child's `!`-negation (see `expand_path_rules`) still compose correctly
+    // instead of being set-unioned.
     let mut ro_home_dirs = base.ro_home_dirs;
     ro_home_dirs.extend(override_spec.ro_home_dirs);
 
     let mut rw_home_dirs = base.rw_home_dirs;
     rw_home_dirs.extend(override_spec.rw_home_dirs);
 
+    let mut tmp_overlay_home_dirs = base.tmp_overlay_home_dirs;
+    tmp_overlay_home_dirs.extend(override_spec.tmp_overlay_home_dirs);
+
     let mut ro_home_files = base.ro_home_files;
     ro_home_files.extend(override_spec.ro_home_files);
 
@@ -91,6 +118,7 @@ fn merge_filesystem_specs(
         description: override_spec.description.or(base.description),
         ro_home_dirs,
         rw_home_dirs,
+        tmp_overlay_home_dirs,
         ro_home_files,
         rw_home_files,
         essential_etc_files,
@@ -102,12 +130,181 @@ fn merge_filesystem_specs(
     }
 }
 
+/// Resolve a seccomp profile by name, handling extends/composition
+pub fn resolve_seccomp_config(config: &Config, name: &str) -> Result<SeccompSpec> {
+    let mut visited = HashSet::new();
+    let key_path = format!("seccomp.profiles.{name}");
+    resolve_seccomp_recursive(config, name, &mut visited, &key_path)
+}
+
+fn resolve_seccomp_recursive(
+    config: &Config,
+    name: &str,
+    visited: &mut HashSet<String>,
+    key_path: &str,
+) -> Result<SeccompSpec> {
+    if visited.contains(name) {
+        return Err(located_seccomp_error(
+            config,
+            name,
+            key_path,
+            format!("Circular reference in seccomp profile: {}", name),
+        ));
+    }
+    visited.insert(name.to_string());
+
+    let spec = config
+        .seccomp
+        .profiles
+        .get(name)
+        .ok_or_else(|| {
+            SandboxError::ConfigError(format!(
+                "{key_path}: Seccomp profile not found: {name}"
+            ))
+        })?
+        .clone();
+
+    if spec.extends.is_empty() {
+        return Ok(spec);
+    }
+
+    // Start from an empty spec, not `SeccompSpec::default()` — that default
+    // carries the shipped denylist, which would then apply unconditionally
+    // to every profile with an `extends` chain regardless of what the
+    // chain actually resolves to.
+    let mut merged = SeccompSpec {
+        description: None,
+        deny_syscalls: vec![],
+        allow_syscalls: vec![],
+        extends: vec![],
+    };
+
+    for (i, parent_name) in spec.extends.iter().enumerate() {
+        let parent_key_path = format!("{key_path}.extends[{i}]");
+        let parent = resolve_seccomp_recursive(config, parent_name, visited, &parent_key_path)?;
+        merged = merge_seccomp_specs(merged, parent);
+    }
+
+    merged = merge_seccomp_specs(merged, spec);
+
+    Ok(merged)
+}
+
+fn located_seccomp_error(config: &Config, name: &str, key_path: &str, message: String) -> SandboxError {
+    match config.seccomp.sources.get(name) {
+        Some(path) => SandboxError::ConfigError(format!(
+            "{key_path} (defined in {}): {message}",
+            path.display()
+        )),
+        None => SandboxError::ConfigError(format!("{key_path}: {message}")),
+    }
+}
+
+fn merge_seccomp_specs(base: SeccompSpec, override_spec: SeccompSpec) -> SeccompSpec {
+    let mut deny_syscalls = base.deny_syscalls;
+    deny_syscalls.extend(override_spec.deny_syscalls);
+
+    let mut allow_syscalls = base.allow_syscalls;
+    allow_syscalls.extend(override_spec.allow_syscalls);
+
+    SeccompSpec {
+        description: override_spec.description.or(base.description),
+        deny_syscalls,
+        allow_syscalls,
+        extends: vec![],
+    }
+}
+
 /// Resolve a policy by name
 pub fn resolve_policy(config: &Config, name: &str) -> Result<Policy> {
-    config
-        .policy
-        .policies
-        .get(name)
-        .cloned()
-        .ok_or_else(|| SandboxError::ConfigError(format!("Policy not found: {}", name)))
+    config.policy.policies.get(name).cloned().ok_or_else(|| {
+        SandboxError::ConfigError(format!("policy.policies.{name}: Policy not found: {name}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn spec_extending(names: &[&str]) -> FilesystemSpec {
+        FilesystemSpec {
+            extends: names.iter().map(|s| s.to_string()).collect(),
+            ..FilesystemSpec::default()
+        }
+    }
+
+    #[test]
+    fn test_not_found_error_names_key_path() {
+        let config = Config::default();
+        let err = resolve_filesystem_config(&config, "missing").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "filesystem.configs.missing: Filesystem config not found: missing"
+        );
+    }
+
+    #[test]
+    fn test_missing_extends_target_names_its_own_key_path() {
+        let mut config = Config::default();
+        config.filesystem.configs.insert("dev".to_string(), spec_extending(&["base"]));
+
+        let err = resolve_filesystem_config(&config, "dev").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "filesystem.configs.dev.extends[0]: Filesystem config not found: base"
+        );
+    }
+
+    #[test]
+    fn test_circular_reference_names_defining_file() {
+        let mut config = Config::default();
+        config.filesystem.configs.insert("a".to_string(), spec_extending(&["b"]));
+        config.filesystem.configs.insert("b".to_string(), spec_extending(&["a"]));
+        config.filesystem.sources.insert("a".to_string(), PathBuf::from("/etc/bwrap/config.toml"));
+
+        let err = resolve_filesystem_config(&config, "a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "filesystem.configs.a.extends[0].extends[0] (defined in /etc/bwrap/config.toml): \
+             Circular reference in filesystem config: a"
+        );
+    }
+
+    #[test]
+    fn test_policy_not_found_names_key_path() {
+        let config = Config::default();
+        let err = resolve_policy(&config, "missing").unwrap_err();
+        assert_eq!(err.to_string(), "policy.policies.missing: Policy not found: missing");
+    }
+
+    #[test]
+    fn test_seccomp_profile_not_found_names_key_path() {
+        let config = Config::default();
+        let err = resolve_seccomp_config(&config, "missing").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "seccomp.profiles.missing: Seccomp profile not found: missing"
+        );
+    }
+
+    #[test]
+    fn test_seccomp_extends_merges_deny_lists() {
+        let mut config = Config::default();
+        config.seccomp.profiles.insert(
+            "base".to_string(),
+            SeccompSpec { deny_syscalls: vec!["ptrace".to_string()], ..SeccompSpec::default() },
+        );
+        config.seccomp.profiles.insert(
+            "strict".to_string(),
+            SeccompSpec {
+                deny_syscalls: vec!["mount".to_string()],
+                extends: vec!["base".to_string()],
+                ..SeccompSpec::default()
+            },
+        );
+
+        let resolved = resolve_seccomp_config(&config, "strict").unwrap();
+        assert_eq!(resolved.deny_syscalls, vec!["ptrace".to_string(), "mount".to_string()]);
+    }
 }
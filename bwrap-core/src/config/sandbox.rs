@@ -1,5 +1,7 @@
 //! Configuration types and constants for sandboxing
 
+use super::schema::SeccompSpec;
+use crate::permissions::PermissionSet;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
@@ -25,6 +27,10 @@ pub struct SandboxConfig {
     /// Home directory access mode
     pub home_access: HomeAccessMode,
 
+    /// User-namespace mode: whether the guest runs as the caller's real
+    /// uid/gid or a remapped one; see `UserMode`
+    pub user_mode: UserMode,
+
     /// Additional read-only paths to mount
     pub additional_ro_paths: Vec<PathBuf>,
 
@@ -45,6 +51,30 @@ pub struct SandboxConfig {
 
     /// Optional explicit path to bw-relay binary (for filtered proxy mode)
     pub bw_relay_path: Option<PathBuf>,
+
+    /// Deno-style granular `--allow-read`/`--allow-write`/`--deny-read`/
+    /// `--deny-write`/`--allow-run` rules, evaluated by
+    /// `SandboxBuilder::setup_mounts` in addition to `additional_ro_paths`/
+    /// `additional_rw_paths` and `home_access`; see `crate::permissions`.
+    pub permissions: PermissionSet,
+
+    /// Syscall filter profile compiled into classic-BPF and handed to
+    /// bwrap via `--seccomp <fd>` by `SandboxBuilder::build_command`; see
+    /// `crate::seccomp`. Resolved from the active policy's `seccomp` field
+    /// (falling back to `SeccompSpec::default()`), same as
+    /// `filesystem_spec` is resolved from its `filesystem` field.
+    pub seccomp: SeccompSpec,
+
+    /// How `target_dir` is exposed inside the sandbox: bound read-write
+    /// directly, or as a discardable overlay upper layer; see `CommitMode`.
+    pub commit_mode: CommitMode,
+
+    /// Give the guest a fresh bwrap-managed `/dev` with its own devpts
+    /// instance instead of bind-mounting the host's `/dev`, so PTY-
+    /// allocating programs work correctly. Always on when `shell` is set,
+    /// regardless of this field; set this to also enable it for the tool's
+    /// own CLI.
+    pub pty: bool,
 }
 
 impl SandboxConfig {
@@ -104,6 +134,24 @@ pub enum NetworkMode {
     },
 }
 
+/// How `SandboxConfig::target_dir` is exposed inside the sandbox
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CommitMode {
+    /// Bind-mount `target_dir` read-write directly: edits land on the real
+    /// tree immediately, as the sandbox always worked before overlay mode
+    #[default]
+    Direct,
+
+    /// Mount `target_dir` read-only as the lower layer of an overlay with
+    /// a writable upper layer on a host temp dir, so edits land there
+    /// instead of the real tree. `Sandbox::overlay_upper_dir` exposes that
+    /// directory for the caller to diff/review and selectively copy back;
+    /// `Sandbox::commit_overlay` copies all of it back in one shot. Either
+    /// way, anything not committed is discarded when `Sandbox` drops, the
+    /// same as `tmp_export_dir` is today.
+    Overlay,
+}
+
 /// Home directory access mode
 #[derive(Debug, Clone, PartialEq)]
 pub enum HomeAccessMode {
@@ -113,3 +161,25 @@ pub enum HomeAccessMode {
     /// Full home directory access (unsafe)
     Full,
 }
+
+/// User-namespace mode for the sandboxed process
+///
+/// `Host` (the default) runs the guest as the caller's real uid/gid, same
+/// as bwrap without `--unshare-user` — files it creates under a rw mount
+/// are owned by that uid both inside and outside the sandbox. `Mapped`
+/// unshares the user namespace and remaps the guest to a fixed uid/gid
+/// (e.g. 0 for a root-like identity, or a dedicated nobody-like id),
+/// hiding the caller's real uid from the guest and giving agent-created
+/// files a reproducible owner regardless of which host account ran it.
+/// Bind-mounted files still carry their real host ownership, so a mapped
+/// uid with no matching entry in that mount's owning namespace will see
+/// them as `nobody`-owned, same as any other bwrap user-namespace mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum UserMode {
+    /// Run as the caller's real uid/gid (no `--unshare-user`)
+    #[default]
+    Host,
+
+    /// Unshare the user namespace and map the guest to this uid/gid
+    Mapped { uid: u32, gid: u32 },
+}
@@ -0,0 +1,327 @@
+//! Hot-reload of policy/config files without restarting the proxy
+//!
+//! Watches the layered config files (builtin + user overrides) with
+//! `notify`, re-parses on change, rebuilds the `PolicyEngine` (refreshing
+//! any remote blocklist feeds along the way), and swaps it into a shared
+//! `arc_swap::ArcSwap` that the running `ProxyServer` reads from on every
+//! connection. A parse/validation failure during reload is logged and the
+//! previous good engine is kept live. Feeds also get their own periodic
+//! refresh tick, independent of file-change events.
+//!
+//! The raw, merged `Config` behind each reload is also published through
+//! `shared_config()`, for consumers that need more than just the resolved
+//! `PolicyEngine` (e.g. re-reading filesystem/seccomp specs on an edit).
+//! Every reparse is size-guarded by `max_config_size` (see
+//! `ConfigLoader::load_from_file_checked`) so a pathological file can't turn
+//! every edit into an expensive or unbounded read.
+
+use super::loader::{ConfigLoader, DEFAULT_MAX_CONFIG_SIZE};
+use super::resolver::resolve_policy;
+use super::schema::Config;
+use crate::error::Result;
+use arc_swap::ArcSwap;
+use bwrap_proxy::config::FeedMode;
+use bwrap_proxy::filter::{anti_doh_group_name, augment_with_anti_doh, augment_with_feeds, feed_group_names, shortest_refresh_interval};
+use bwrap_proxy::{PolicyEngine, SharedPolicyEngine};
+use notify::{RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long to wait after a change event before rebuilding, so a burst of
+/// rapid edits (e.g. an editor's save-as-temp-then-rename) only triggers
+/// one rebuild instead of one per event.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// The live, merged `Config` behind a `ConfigWatcher`, swapped in on every
+/// successful reload. `None` until the first successful load.
+pub type SharedConfig = Arc<ArcSwap<Option<Config>>>;
+
+/// Watches config files and keeps a `SharedPolicyEngine` up to date
+pub struct ConfigWatcher {
+    engine: SharedPolicyEngine,
+    config: SharedConfig,
+    // Keep the watcher alive for the lifetime of the ConfigWatcher
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Start watching the config chain and rebuild `policy_name`'s engine on
+    /// change, rejecting any file layer over `max_config_size` bytes rather
+    /// than reparsing it (see `ConfigLoader::load_from_file_checked`)
+    pub async fn start(explicit_config: Option<PathBuf>, policy_name: String) -> Result<Self> {
+        Self::start_with_max_size(explicit_config, policy_name, DEFAULT_MAX_CONFIG_SIZE).await
+    }
+
+    /// Like `start`, but with an explicit config-file size ceiling (e.g.
+    /// from `--max-config-size`) instead of `DEFAULT_MAX_CONFIG_SIZE`
+    pub async fn start_with_max_size(
+        explicit_config: Option<PathBuf>,
+        policy_name: String,
+        max_config_size: u64,
+    ) -> Result<Self> {
+        let initial_config = ConfigLoader::load_with_priority_checked(explicit_config.clone(), max_config_size).ok();
+        let config: SharedConfig = Arc::new(ArcSwap::from_pointee(initial_config.clone()));
+        let engine: SharedPolicyEngine = Arc::new(ArcSwap::from_pointee(
+            Self::build_engine(explicit_config.clone(), &policy_name, max_config_size)
+                .await
+                .ok(),
+        ));
+
+        let watch_paths = watch_paths(explicit_config.clone());
+
+        // Change events (file edits and periodic feed ticks alike) are
+        // coalesced on a single task: every signal resets a DEBOUNCE timer,
+        // and the engine is only rebuilt once signals stop arriving for a
+        // full DEBOUNCE window.
+        let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+        {
+            let engine_for_reload = engine.clone();
+            let config_for_reload = config.clone();
+            let explicit_config_for_reload = explicit_config.clone();
+            let policy_name_for_reload = policy_name.clone();
+            tokio::spawn(async move {
+                while rx.recv().await.is_some() {
+                    // Drain any further signals that land within the
+                    // debounce window, so a burst collapses into one rebuild.
+                    while tokio::time::timeout(DEBOUNCE, rx.recv()).await.is_ok() {}
+
+                    // Reload the raw config first: if it fails to parse (or
+                    // a layer is over max_config_size), keep the last-good
+                    // config *and* engine live rather than rebuild the
+                    // engine from a config we know is bad.
+                    match ConfigLoader::load_with_priority_checked(
+                        explicit_config_for_reload.clone(),
+                        max_config_size,
+                    ) {
+                        Ok(new_config) => {
+                            config_for_reload.store(Arc::new(Some(new_config)));
+                        }
+                        Err(e) => {
+                            tracing::error!(
+                                "Config reload failed, keeping last-good config live: {e}"
+                            );
+                            continue;
+                        }
+                    }
+
+                    match Self::build_engine(
+                        explicit_config_for_reload.clone(),
+                        &policy_name_for_reload,
+                        max_config_size,
+                    )
+                    .await
+                    {
+                        Ok(new_engine) => {
+                            engine_for_reload.store(Arc::new(Some(new_engine)));
+                            tracing::info!(
+                                "Policy '{}' reloaded from updated config",
+                                policy_name_for_reload
+                            );
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                "Config reload failed for policy '{}', keeping previous engine live: {e}",
+                                policy_name_for_reload
+                            );
+                        }
+                    }
+                }
+            });
+        }
+
+        // If any blocklist feeds are configured, also tick on their shortest
+        // refresh interval so entries stay current even without a file edit.
+        if let Some(interval) = initial_config
+            .as_ref()
+            .and_then(|config| shortest_refresh_interval(&config.network))
+        {
+            let tx_for_feeds = tx.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await; // first tick fires immediately; skip it
+                loop {
+                    ticker.tick().await;
+                    let _ = tx_for_feeds.send(());
+                }
+            });
+        }
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!("Config watch error: {e}");
+                    return;
+                }
+            };
+
+            if !matches!(
+                event.kind,
+                notify::EventKind::Modify(_) | notify::EventKind::Create(_)
+            ) {
+                return;
+            }
+
+            let _ = tx.send(());
+        })
+        .map_err(|e| crate::error::SandboxError::ConfigError(format!("Failed to create watcher: {e}")))?;
+
+        for path in &watch_paths {
+            if path.exists() {
+                if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
+                    tracing::debug!("Could not watch {:?}: {e}", path);
+                }
+            }
+        }
+
+        Ok(Self {
+            engine,
+            config,
+            _watcher: watcher,
+        })
+    }
+
+    /// Get the shared handle to hand to `ProxyServerConfig`
+    pub fn shared(&self) -> SharedPolicyEngine {
+        self.engine.clone()
+    }
+
+    /// Get the shared handle to the raw, merged `Config` behind this
+    /// watcher, for consumers that need more than the resolved
+    /// `PolicyEngine` (e.g. to re-resolve a filesystem or seccomp spec on
+    /// an edit)
+    pub fn shared_config(&self) -> SharedConfig {
+        self.config.clone()
+    }
+
+    async fn build_engine(explicit_config: Option<PathBuf>, policy_name: &str, max_config_size: u64) -> Result<PolicyEngine> {
+        let config = ConfigLoader::load_with_priority_checked(explicit_config, max_config_size)?;
+        let policy = resolve_policy(&config, policy_name)?;
+        let network_config = augment_with_feeds(&config.network).await;
+        let network_config = augment_with_anti_doh(&network_config);
+
+        let mut allow_groups = policy.network.effective_allow_groups();
+        allow_groups.extend(feed_group_names(&network_config, FeedMode::Allow));
+        let mut deny_groups = policy.network.deny_groups.clone();
+        deny_groups.extend(feed_group_names(&network_config, FeedMode::Deny));
+        deny_groups.extend(anti_doh_group_name(&network_config));
+
+        PolicyEngine::from_network_policy(
+            allow_groups,
+            deny_groups,
+            policy.network.default.clone(),
+            &network_config,
+        )
+        .map_err(|e| crate::error::SandboxError::ConfigError(e.to_string()))
+    }
+}
+
+/// Every file that participates in `ConfigLoader::load_with_priority`'s
+/// chain, other than the static built-in — the set a `notify::Watcher`
+/// needs to watch so an edit to any layer triggers a reload
+fn watch_paths(explicit_config: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(p) = ConfigLoader::find_system_config() {
+        paths.push(p);
+    }
+    if let Some(p) = ConfigLoader::find_user_config() {
+        paths.push(p);
+    }
+    if let Some(p) = ConfigLoader::find_project_config() {
+        paths.push(p);
+    }
+    if let Some(p) = explicit_config {
+        paths.push(p);
+    }
+    paths
+}
+
+/// A live, merged `Config` kept current by a background `notify` watcher —
+/// a lighter-weight alternative to `ConfigWatcher` for callers that just
+/// want the merged config itself (e.g. to read the learned-domain list or a
+/// filesystem/seccomp spec), rather than a resolved `PolicyEngine` for one
+/// specific named policy. Read the current value lock-free with `load()`.
+///
+/// Build one via `ConfigLoader::watch`.
+pub struct WatchedConfig {
+    config: Arc<ArcSwap<Config>>,
+    // Keep the watcher alive for the lifetime of the WatchedConfig
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl WatchedConfig {
+    /// Start watching the config chain (system/user/project/explicit) and
+    /// keep `load()` current with `load_with_priority`'s merged result.
+    /// Rejects reparses whose config file layers exceed
+    /// `DEFAULT_MAX_CONFIG_SIZE` the same way `ConfigWatcher` does.
+    pub fn start(explicit_config: Option<PathBuf>) -> Result<Self> {
+        let initial = ConfigLoader::load_with_priority_checked(explicit_config.clone(), DEFAULT_MAX_CONFIG_SIZE)?;
+        let config = Arc::new(ArcSwap::from_pointee(initial));
+
+        let paths = watch_paths(explicit_config.clone());
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+        {
+            let config_for_reload = config.clone();
+            let explicit_for_reload = explicit_config.clone();
+            tokio::spawn(async move {
+                while rx.recv().await.is_some() {
+                    while tokio::time::timeout(DEBOUNCE, rx.recv()).await.is_ok() {}
+
+                    match ConfigLoader::load_with_priority_checked(
+                        explicit_for_reload.clone(),
+                        DEFAULT_MAX_CONFIG_SIZE,
+                    ) {
+                        Ok(new_config) => {
+                            config_for_reload.store(Arc::new(new_config));
+                            tracing::info!("Config reloaded from updated file");
+                        }
+                        Err(e) => {
+                            tracing::error!("Config reload failed, keeping previous config live: {e}");
+                        }
+                    }
+                }
+            });
+        }
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!("Config watch error: {e}");
+                    return;
+                }
+            };
+
+            if !matches!(
+                event.kind,
+                notify::EventKind::Modify(_) | notify::EventKind::Create(_)
+            ) {
+                return;
+            }
+
+            let _ = tx.send(());
+        })
+        .map_err(|e| crate::error::SandboxError::ConfigError(format!("Failed to create watcher: {e}")))?;
+
+        for path in &paths {
+            if path.exists() {
+                if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
+                    tracing::debug!("Could not watch {:?}: {e}", path);
+                }
+            }
+        }
+
+        Ok(Self {
+            config,
+            _watcher: watcher,
+        })
+    }
+
+    /// Read the current merged config lock-free
+    pub fn load(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+}
@@ -28,6 +28,37 @@ pub enum SandboxError {
     #[error("Environment variable {0} not found")]
     EnvVarNotFound(String),
 
+    #[error("Capability '{capability}' denied for tool '{tool}' by security policy")]
+    CapabilityDenied { tool: String, capability: String },
+
+    #[error("Executable '{exe}' is not permitted to run for tool '{tool}' (see --allow-run)")]
+    ExecutableNotAllowed { tool: String, exe: String },
+
+    #[error("Failed to compile seccomp filter: {0}")]
+    SeccompCompile(String),
+
+    #[error("Failed to set up seccomp filter fd: {0}")]
+    SeccompSetup(#[source] std::io::Error),
+
+    #[error("Failed to set up bind-data mount fd: {0}")]
+    BindDataSetup(#[source] std::io::Error),
+
+    #[error("Failed to read config file {path}: {source}")]
+    ConfigLoad {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse config file: {0}")]
+    ConfigParse(#[from] toml::de::Error),
+
+    #[error("Config file {path:?} is {size} bytes, over the {limit}-byte limit (see --max-config-size)")]
+    ConfigTooLarge { path: PathBuf, size: u64, limit: u64 },
+
+    #[error("{0}")]
+    ConfigError(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
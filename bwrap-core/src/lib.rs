@@ -4,23 +4,44 @@
 //! sandboxing of LLM CLI tools.
 
 pub mod args;
+pub mod capabilities;
 pub mod config;
 pub mod env;
 pub mod error;
+pub mod lockfile;
+pub mod memfd;
 pub mod mount;
 pub mod network;
+pub mod nftables;
+pub mod permissions;
 pub mod policy;
+pub mod policy_admin;
+pub mod prompt;
 pub mod proxy;
+pub mod proxy_manager;
+pub mod registry;
 pub mod sandbox;
+pub mod seccomp;
 
 pub use args::CommonArgs;
+pub use capabilities::BwrapCapabilities;
 pub use config::{
-    Config, ConfigLoader, DefaultMode, FilesystemConfig, FilesystemSpec, HomeAccessMode,
-    NetworkMode, NetworkPolicy, Policy, PolicyConfig, ProxyMode, SandboxConfig, ToolConfig,
-    resolve_filesystem_config, resolve_policy,
+    apply_layered_config, apply_tool_bool, CommitMode, Config, ConfigLayer, ConfigLoader, ConfigTier,
+    ConfigWatcher, DefaultMode, DEFAULT_LEARNING_OUTPUT_MAX_SIZE, DEFAULT_MAX_CONFIG_SIZE, FileOwner,
+    FilesystemConfig, FilesystemSpec, HomeAccessMode, Merge,
+    migrate_to_current, Migration, NetworkMode, NetworkPolicy, PartialCommonConfig, Policy, PolicyConfig,
+    ProxyMode, SandboxConfig, SeccompConfig, SeccompSpec, SecurityConfig, SecurityPolicy, SharedConfig,
+    ToolConfig, UpstreamDomainRule, UpstreamProxyConfig, UserMode, WatchedConfig, WritableConfigLocation,
+    CURRENT_CONFIG_VERSION, resolve_filesystem_config, resolve_policy, resolve_seccomp_config,
 };
 pub use error::{Result, SandboxError};
+pub use lockfile::{LockedHosts, LockedNetwork, SandboxLock};
 pub use network::determine_network_mode;
+pub use permissions::{AccessMode, PermissionSet};
 pub use policy::{setup_policy, PolicySetup};
+pub use policy_admin::{AdminCommand, GroupAction, PolicyAction};
+pub use prompt::{PolicyPromptOptions, PromptServer};
 pub use proxy::create_proxy_task;
+pub use proxy_manager::{ensure_daemon, DaemonStatus};
+pub use registry::{ToolRegistry, ToolRegistryEntry};
 pub use sandbox::{Sandbox, SandboxBuilder};
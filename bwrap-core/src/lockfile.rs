@@ -0,0 +1,323 @@
+//! Reproducible sandbox lockfile
+//!
+//! Mirrors the lock-format pattern wasm-pkg-tools uses for dependency
+//! resolution: once `resolve_filesystem_config`, `resolve_policy`, and
+//! (optionally) the `LearningRecorder` have all run, `SandboxLock::capture`
+//! snapshots exactly what the sandbox was granted — the resolved
+//! `FilesystemSpec`, the effective network mode, and the concrete
+//! allow/deny/learned host sets — into a versioned `bwrap.lock` TOML file,
+//! with a SHA-256 digest per section plus a combined top-level digest.
+//!
+//! On a later run, re-capturing the lock from the freshly-resolved config
+//! and comparing against a previously saved one (`SandboxLock::drift_from`)
+//! tells the caller which sections, if any, drifted, so a
+//! reviewed-and-approved grant set can't silently widen underneath the
+//! user.
+
+use crate::config::{FilesystemSpec, NetworkMode};
+use crate::error::{Result, SandboxError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// Current lockfile format version; bump when the schema changes in a way
+/// that would make an old lockfile unsafe to compare against.
+const LOCK_VERSION: u32 = 1;
+
+/// The effective, fully-expanded host grant set for a sandbox run: concrete
+/// allow/deny hostnames with groups already expanded (not referenced by
+/// name), plus any hosts observed by the `LearningRecorder` this run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LockedHosts {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub learned: Vec<String>,
+}
+
+/// The effective network mode, stripped of fields that vary run-to-run
+/// (e.g. the proxy's per-session socket path) so the digest reflects only
+/// what was actually granted
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LockedNetwork {
+    pub mode: String,
+    #[serde(default)]
+    pub policy_name: Option<String>,
+}
+
+impl LockedNetwork {
+    fn capture(network_mode: &NetworkMode) -> Self {
+        match network_mode {
+            NetworkMode::Enabled => Self { mode: "enabled".to_string(), policy_name: None },
+            NetworkMode::Disabled => Self { mode: "disabled".to_string(), policy_name: None },
+            NetworkMode::Filtered { policy_name, .. } => Self {
+                mode: "filtered".to_string(),
+                policy_name: Some(policy_name.clone()),
+            },
+        }
+    }
+}
+
+/// A canonicalized section of the lock plus its SHA-256 digest
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LockedSection<T> {
+    pub digest: String,
+    pub value: T,
+}
+
+impl<T: Serialize> LockedSection<T> {
+    fn capture(value: T) -> Result<Self> {
+        let digest = canonical_digest(&value)?;
+        Ok(Self { digest, value })
+    }
+}
+
+/// A fully-resolved sandbox grant set, locked for reproducibility
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SandboxLock {
+    pub version: u32,
+    pub filesystem: LockedSection<FilesystemSpec>,
+    pub network: LockedSection<LockedNetwork>,
+    pub hosts: LockedSection<LockedHosts>,
+    pub digest: String,
+}
+
+impl SandboxLock {
+    /// Capture a lock snapshot from a resolved filesystem spec, network
+    /// mode, and effective host sets. Collections are sorted/deduplicated
+    /// first so the digest only reflects the grant set, not discovery order.
+    pub fn capture(
+        filesystem_spec: &FilesystemSpec,
+        network_mode: &NetworkMode,
+        hosts: &LockedHosts,
+    ) -> Result<Self> {
+        let filesystem = LockedSection::capture(canonicalize_filesystem_spec(filesystem_spec))?;
+        let network = LockedSection::capture(LockedNetwork::capture(network_mode))?;
+        let hosts = LockedSection::capture(canonicalize_hosts(hosts))?;
+
+        let combined = format!("{}{}{}", filesystem.digest, network.digest, hosts.digest);
+
+        Ok(Self {
+            version: LOCK_VERSION,
+            filesystem,
+            network,
+            hosts,
+            digest: hex_digest(combined.as_bytes()),
+        })
+    }
+
+    /// Load a previously saved lock from disk
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|source| SandboxError::ConfigLoad {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&contents)
+            .map_err(|e| SandboxError::ConfigError(format!("Failed to parse lockfile {path:?}: {e}")))
+    }
+
+    /// Write this lock to disk as TOML
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| SandboxError::ConfigError(format!("Failed to serialize lockfile: {e}")))?;
+        fs::write(path, contents).map_err(SandboxError::Io)
+    }
+
+    /// Compare this freshly-resolved lock against a previously saved one.
+    /// Returns the names of sections whose digest drifted (empty if none).
+    pub fn drift_from(&self, previous: &SandboxLock) -> Vec<&'static str> {
+        let mut drifted = Vec::new();
+        if self.filesystem.digest != previous.filesystem.digest {
+            drifted.push("filesystem");
+        }
+        if self.network.digest != previous.network.digest {
+            drifted.push("network");
+        }
+        if self.hosts.digest != previous.hosts.digest {
+            drifted.push("hosts");
+        }
+        drifted
+    }
+
+    /// Capture the current grant set and reconcile it against `path`: if a
+    /// lock is already there and it's drifted from the freshly-resolved
+    /// grant set, refuse (returning `SandboxError::ConfigError`) unless
+    /// `warn_only` is set, in which case the drift is only logged. Unless it
+    /// refuses, the freshly-captured lock is written back to `path` so it
+    /// stays current for the next run.
+    pub fn enforce(
+        path: &Path,
+        filesystem_spec: &FilesystemSpec,
+        network_mode: &NetworkMode,
+        hosts: &LockedHosts,
+        warn_only: bool,
+    ) -> Result<Self> {
+        let current = Self::capture(filesystem_spec, network_mode, hosts)?;
+
+        if path.exists() {
+            let previous = Self::load(path)?;
+            let drifted = current.drift_from(&previous);
+            if !drifted.is_empty() {
+                let message = format!(
+                    "Sandbox grant set drifted from {:?} in section(s): {}",
+                    path,
+                    drifted.join(", ")
+                );
+                if warn_only {
+                    tracing::warn!("{message}");
+                } else {
+                    return Err(SandboxError::ConfigError(format!(
+                        "{message} (pass --allow-lock-drift to proceed anyway)"
+                    )));
+                }
+            }
+        }
+
+        current.save(path)?;
+        Ok(current)
+    }
+}
+
+fn canonicalize_filesystem_spec(spec: &FilesystemSpec) -> FilesystemSpec {
+    let mut spec = spec.clone();
+    spec.ro_home_dirs = sorted_dedup(&spec.ro_home_dirs);
+    spec.rw_home_dirs = sorted_dedup(&spec.rw_home_dirs);
+    spec.tmp_overlay_home_dirs = sorted_dedup(&spec.tmp_overlay_home_dirs);
+    spec.ro_home_files = sorted_dedup(&spec.ro_home_files);
+    spec.rw_home_files = sorted_dedup(&spec.rw_home_files);
+    spec.essential_etc_files = sorted_dedup(&spec.essential_etc_files);
+    spec.essential_etc_dirs = sorted_dedup(&spec.essential_etc_dirs);
+    spec.system_paths = sorted_dedup(&spec.system_paths);
+    spec.ro_paths = normalize_paths(&spec.ro_paths);
+    spec.rw_paths = normalize_paths(&spec.rw_paths);
+    spec.extends = sorted_dedup(&spec.extends);
+    spec
+}
+
+/// Normalize each path string (collapse `.`/repeated separators) before
+/// sorting, so equivalent path sets hash identically regardless of spelling
+fn normalize_paths(paths: &[String]) -> Vec<String> {
+    sorted_dedup(
+        &paths
+            .iter()
+            .map(|p| {
+                Path::new(p)
+                    .components()
+                    .collect::<std::path::PathBuf>()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn canonicalize_hosts(hosts: &LockedHosts) -> LockedHosts {
+    LockedHosts {
+        allow: sorted_dedup(&hosts.allow),
+        deny: sorted_dedup(&hosts.deny),
+        learned: sorted_dedup(&hosts.learned),
+    }
+}
+
+fn sorted_dedup(items: &[String]) -> Vec<String> {
+    items.iter().cloned().collect::<BTreeSet<_>>().into_iter().collect()
+}
+
+/// Hash a stable, sorted-key TOML serialization of `value`, so the digest
+/// only depends on content, not field/insertion order.
+pub(crate) fn canonical_digest<T: Serialize>(value: &T) -> Result<String> {
+    let value = toml::Value::try_from(value)
+        .map_err(|e| SandboxError::ConfigError(format!("Failed to serialize lock section: {e}")))?;
+    let canonical = toml::to_string(&sort_keys(value))
+        .map_err(|e| SandboxError::ConfigError(format!("Failed to canonicalize lock section: {e}")))?;
+    Ok(hex_digest(canonical.as_bytes()))
+}
+
+/// Recursively sort table keys so two structurally-equal TOML values always
+/// serialize to the same bytes
+fn sort_keys(value: toml::Value) -> toml::Value {
+    match value {
+        toml::Value::Table(table) => {
+            let mut keys: Vec<_> = table.keys().cloned().collect();
+            keys.sort();
+            let mut sorted = toml::value::Table::new();
+            for key in keys {
+                let entry = table[&key].clone();
+                sorted.insert(key, sort_keys(entry));
+            }
+            toml::Value::Table(sorted)
+        }
+        toml::Value::Array(items) => toml::Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+pub(crate) fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_spec() -> FilesystemSpec {
+        FilesystemSpec {
+            ro_home_dirs: vec!["b".to_string(), "a".to_string()],
+            ..FilesystemSpec::default()
+        }
+    }
+
+    fn sample_hosts() -> LockedHosts {
+        LockedHosts {
+            allow: vec!["b.example.com".to_string(), "a.example.com".to_string()],
+            deny: vec![],
+            learned: vec![],
+        }
+    }
+
+    #[test]
+    fn test_capture_is_order_independent() {
+        let a = SandboxLock::capture(&sample_spec(), &NetworkMode::Enabled, &sample_hosts()).unwrap();
+
+        let mut reordered_spec = sample_spec();
+        reordered_spec.ro_home_dirs.reverse();
+        let mut reordered_hosts = sample_hosts();
+        reordered_hosts.allow.reverse();
+        let b = SandboxLock::capture(&reordered_spec, &NetworkMode::Enabled, &reordered_hosts).unwrap();
+
+        assert_eq!(a.digest, b.digest);
+    }
+
+    #[test]
+    fn test_drift_detects_widened_hosts() {
+        let before = SandboxLock::capture(&sample_spec(), &NetworkMode::Enabled, &sample_hosts()).unwrap();
+
+        let mut widened_hosts = sample_hosts();
+        widened_hosts.allow.push("evil.example.com".to_string());
+        let after = SandboxLock::capture(&sample_spec(), &NetworkMode::Enabled, &widened_hosts).unwrap();
+
+        assert_eq!(after.drift_from(&before), vec!["hosts"]);
+    }
+
+    #[test]
+    fn test_no_drift_when_unchanged() {
+        let lock = SandboxLock::capture(&sample_spec(), &NetworkMode::Enabled, &sample_hosts()).unwrap();
+        assert!(lock.drift_from(&lock).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trips() {
+        let lock = SandboxLock::capture(&sample_spec(), &NetworkMode::Enabled, &sample_hosts()).unwrap();
+        let path = std::env::temp_dir().join(format!("bwrap-lock-test-{:x}", std::ptr::addr_of!(lock) as usize));
+
+        lock.save(&path).unwrap();
+        let loaded = SandboxLock::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(lock.digest, loaded.digest);
+    }
+}
@@ -0,0 +1,57 @@
+//! Anonymous in-memory files (`memfd_create`) for handing data to bwrap
+//!
+//! Shared by `crate::seccomp` (the compiled BPF filter program) and
+//! `MountMode::BindData` (arbitrary file contents): both need to get a
+//! buffer in front of bwrap without ever writing it to a real path on disk.
+//! `memfd_create` gives an anonymous fd with no path, nothing left behind
+//! to clean up; `pre_exec_dup2` then hands that fd to the sandboxed child
+//! at a fixed number across `exec`.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+extern "C" {
+    fn memfd_create(name: *const std::os::raw::c_char, flags: std::os::raw::c_uint) -> i32;
+    #[link_name = "dup2"]
+    fn libc_dup2(oldfd: i32, newfd: i32) -> i32;
+}
+
+/// Write `contents` into a new anonymous `memfd_create`d file, rewound to
+/// the start so bwrap reads the whole thing. `name` is only used for
+/// debugging (visible as the memfd's name in `/proc/self/fd`) and carries
+/// no other meaning.
+pub fn write_to_memfd(name: &str, contents: &[u8]) -> std::io::Result<File> {
+    let name = CString::new(name).unwrap_or_else(|_| CString::new("bw-memfd").unwrap());
+    let fd = unsafe { memfd_create(name.as_ptr(), 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(contents)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// Install a `pre_exec` hook on `cmd` that `dup2`s `memfd` onto `target_fd`
+/// in the child after `fork` but before `exec`. `pre_exec` runs in the
+/// single-threaded child, so `dup2` there is safe where it wouldn't be from
+/// the (possibly multi-threaded) parent.
+///
+/// Safety: `dup2` is async-signal-safe, and the closure only touches
+/// `memfd`, which it owns — no shared state, no allocation, no panics that
+/// could escape across the fork boundary.
+pub fn pre_exec_dup2(cmd: &mut Command, memfd: File, target_fd: RawFd) {
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc_dup2(memfd.as_raw_fd(), target_fd) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
@@ -1,7 +1,9 @@
 //! Mount point management for sandbox
 
 use std::ffi::OsString;
+use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
 
 /// A mount point in the sandbox
 #[derive(Debug, Clone)]
@@ -42,6 +44,62 @@ pub enum MountMode {
 
     /// /dev bind mount
     DevBind,
+
+    /// A fresh bwrap-managed `/dev` (`--dev DEST`) rather than a bind mount
+    /// of the host's: gives the guest its own devtmpfs-style nodes, a
+    /// `/dev/shm` tmpfs, and — critically for shell mode — its own devpts
+    /// instance with `/dev/pts/ptmx` wired up, so PTY-allocating programs
+    /// (pagers, editors, tmux) get real terminals instead of misbehaving
+    /// against the host's `/dev/pts`
+    Dev,
+
+    /// Add a read-only lower layer to the overlay about to be mounted by a
+    /// following `Overlay` mount point (`--overlay-src`); `source` is the
+    /// lower directory, `target` is unused
+    OverlaySrc,
+
+    /// Mount an overlayfs stacking every preceding `OverlaySrc` lower layer
+    /// under a writable upper layer (`--overlay <upper> <workdir> <dest>`);
+    /// `source` is the upper (rw) directory, `target` is the mount
+    /// destination inside the sandbox
+    Overlay { workdir: PathBuf },
+
+    /// A throwaway copy-on-write layer over whatever is already mounted at
+    /// `target` (`--tmp-overlay <dest>`): writes land in an anonymous
+    /// tmpfs-backed upper layer that vanishes when the sandbox exits,
+    /// without ever touching the host. Ideal for letting a tool scribble in
+    /// a `~/.cache`-style directory it needs write access to but whose
+    /// contents don't need to persist or be exposed to the host.
+    TmpOverlay,
+
+    /// Materialize `contents` as a file at `target` straight from memory
+    /// (`--bind-data`/`--ro-bind-data <fd> <dest>`), for generated data
+    /// (a synthesized `resolv.conf`, an ephemeral credentials file) that
+    /// has no reason to ever exist as a host temp file. `source` is
+    /// unused. `target_fd` is the fd number this mount's contents will be
+    /// handed to bwrap on, assigned at construction time (see
+    /// `next_bind_data_fd`) so `to_args` can reference it and
+    /// `SandboxBuilder::build_command` can set up the matching memfd.
+    BindData { ro: bool, contents: Vec<u8>, target_fd: RawFd },
+}
+
+/// First fd number handed out to a `MountMode::BindData` mount, chosen
+/// clear of stdio and `crate::seccomp::SECCOMP_TARGET_FD` (200) — the only
+/// other fixed fd this crate dup's into the sandboxed child. Each
+/// `BindData` mount gets the next number in sequence; see
+/// `next_bind_data_fd`.
+const BIND_DATA_BASE_FD: RawFd = 201;
+
+static NEXT_BIND_DATA_FD: AtomicI32 = AtomicI32::new(BIND_DATA_BASE_FD);
+
+/// Hand out a fresh, process-unique fd number for a `BindData` mount's
+/// contents. A plain atomic counter (rather than threading a counter
+/// through every `setup_mounts` call site) keeps `MountPoint::bind_data`/
+/// `ro_bind_data` as simple to call as `MountPoint::ro`/`rw`; safe here
+/// because a sandbox launch happens once per process and never reuses
+/// these numbers across an unrelated exec.
+fn next_bind_data_fd() -> RawFd {
+    NEXT_BIND_DATA_FD.fetch_add(1, Ordering::Relaxed)
 }
 
 impl MountPoint {
@@ -119,6 +177,68 @@ impl MountPoint {
         }
     }
 
+    /// Mount a fresh bwrap-managed /dev (with its own devpts instance) at
+    /// `/dev`, in place of `dev_bind`'s bind mount of the host's
+    pub fn dev() -> Self {
+        Self {
+            source: PathBuf::new(),
+            target: PathBuf::from("/dev"),
+            mode: MountMode::Dev,
+        }
+    }
+
+    /// Add a read-only overlay lower layer; pair with one or more other
+    /// `overlay_src` calls followed by a single `overlay` mount point
+    pub fn overlay_src<P: AsRef<Path>>(lower: P) -> Self {
+        Self {
+            source: lower.as_ref().to_path_buf(),
+            target: PathBuf::new(),
+            mode: MountMode::OverlaySrc,
+        }
+    }
+
+    /// Mount the overlay stacking every preceding `overlay_src` lower layer
+    /// under writable directory `upper` (with scratch directory `workdir`,
+    /// required by overlayfs and must live on the same filesystem as
+    /// `upper`) at `target`
+    pub fn overlay<P: AsRef<Path>>(upper: P, workdir: P, target: P) -> Self {
+        Self {
+            source: upper.as_ref().to_path_buf(),
+            target: target.as_ref().to_path_buf(),
+            mode: MountMode::Overlay {
+                workdir: workdir.as_ref().to_path_buf(),
+            },
+        }
+    }
+
+    /// Create a throwaway copy-on-write overlay over whatever is already
+    /// mounted at `target`
+    pub fn tmp_overlay<P: AsRef<Path>>(target: P) -> Self {
+        Self {
+            source: PathBuf::new(),
+            target: target.as_ref().to_path_buf(),
+            mode: MountMode::TmpOverlay,
+        }
+    }
+
+    /// Materialize `contents` as a writable file at `target`, straight from memory
+    pub fn bind_data<P: AsRef<Path>>(target: P, contents: Vec<u8>) -> Self {
+        Self {
+            source: PathBuf::new(),
+            target: target.as_ref().to_path_buf(),
+            mode: MountMode::BindData { ro: false, contents, target_fd: next_bind_data_fd() },
+        }
+    }
+
+    /// Materialize `contents` as a read-only file at `target`, straight from memory
+    pub fn ro_bind_data<P: AsRef<Path>>(target: P, contents: Vec<u8>) -> Self {
+        Self {
+            source: PathBuf::new(),
+            target: target.as_ref().to_path_buf(),
+            mode: MountMode::BindData { ro: true, contents, target_fd: next_bind_data_fd() },
+        }
+    }
+
     /// Convert this mount point to bwrap command arguments
     pub fn to_args(&self) -> Vec<OsString> {
         match &self.mode {
@@ -166,6 +286,27 @@ impl MountPoint {
                     self.target.clone().into(),
                 ]
             }
+            MountMode::Dev => {
+                vec!["--dev".into(), self.target.clone().into()]
+            }
+            MountMode::OverlaySrc => {
+                vec!["--overlay-src".into(), self.source.clone().into()]
+            }
+            MountMode::Overlay { workdir } => {
+                vec![
+                    "--overlay".into(),
+                    self.source.clone().into(),
+                    workdir.clone().into(),
+                    self.target.clone().into(),
+                ]
+            }
+            MountMode::TmpOverlay => {
+                vec!["--tmp-overlay".into(), self.target.clone().into()]
+            }
+            MountMode::BindData { ro, target_fd, .. } => {
+                let flag = if *ro { "--ro-bind-data" } else { "--bind-data" };
+                vec![flag.into(), target_fd.to_string().into(), self.target.clone().into()]
+            }
         }
     }
 }
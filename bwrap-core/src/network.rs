@@ -52,6 +52,9 @@ pub async fn determine_network_mode(
             Some(policy_name),
             learning_output,
             learning_mode.map(|s| s.to_string()),
+            common.proxy_config.clone(),
+            crate::prompt::options_from_args(common),
+            common.max_config_size,
         )
         .await?;
 
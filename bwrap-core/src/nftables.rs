@@ -0,0 +1,145 @@
+//! Compile a resolved `NetworkPolicy` into an nftables ruleset, expressed as
+//! libnftables JSON (the format `nft -j -f ruleset.json` loads), so a
+//! caller with a way to load it (e.g. into the sandboxed process's network
+//! namespace before it starts) can give the same policy kernel-level
+//! enforcement for a tool that reaches the network without going through
+//! the userspace proxy socket.
+//!
+//! This module only compiles the ruleset — nothing in `crate::sandbox`'s
+//! launch path loads it yet (today's `NetworkMode::Filtered` fully
+//! `--unshare-net`s and routes everything through the proxy's UDS socket
+//! instead, so there's no network namespace for this to protect there).
+//! Call `Policy::to_nftables_json` and apply the result yourself for a
+//! setup that shares or otherwise provisions a real network namespace.
+//!
+//! Only IPv4/IPv6 CIDR ranges can be expressed this way — nftables has no
+//! hostname concept. Hostname-only `hosts`/`hosts_deny` patterns (the
+//! common case; see `bwrap_proxy::filter::ResolvedRanges`) are reported
+//! back as unenforceable instead of silently dropped, so the caller knows
+//! traffic to them still needs the proxy.
+//!
+//! Deny is evaluated before allow in the emitted chain, so an address that
+//! falls in both sets is dropped — the kernel-level equivalent of
+//! `PolicyEngine::allow`'s "deny wins on tie" rule. This is coarser than
+//! the proxy's per-pattern `matches_with_specificity` longest-match logic
+//! (nft set membership has no notion of "more specific prefix"), but since
+//! `deny_groups` exists to carve exceptions out of a broader `allow_groups`
+//! grant, evaluating it first gives the same practical result.
+
+use crate::config::{NetworkConfig, NetworkPolicy};
+use crate::error::{Result, SandboxError};
+use bwrap_proxy::config::DefaultMode;
+use bwrap_proxy::filter::PolicyEngine;
+
+const TABLE_NAME: &str = "bw_policy";
+const CHAIN_NAME: &str = "output";
+
+/// Compile `policy`'s effective allow/deny groups against `network_config`
+/// into an nftables ruleset targeting the `output` hook (the sandboxed
+/// process's egress). Returns the ruleset as libnftables JSON text, plus
+/// the hostname-only patterns it couldn't enforce.
+pub fn compile(policy: &NetworkPolicy, network_config: &NetworkConfig) -> Result<(String, Vec<String>)> {
+    let allow_groups = policy.effective_allow_groups();
+    let deny_groups = policy.deny_groups.clone();
+
+    let resolved = PolicyEngine::resolve_ip_ranges(&allow_groups, &deny_groups, network_config)
+        .map_err(|e| SandboxError::ConfigError(e.to_string()))?;
+
+    let default_verdict = match policy.default {
+        DefaultMode::Allow => "accept",
+        DefaultMode::Deny => "drop",
+    };
+
+    let mut items = vec![table_item(), chain_item(default_verdict)];
+
+    // Deny first, so it wins ties against an overlapping allow entry.
+    items.extend(set_rule_item("ip", "daddr", &to_cidr_strings(&resolved.deny_ipv4), "drop"));
+    items.extend(set_rule_item("ip6", "daddr", &to_cidr_strings(&resolved.deny_ipv6), "drop"));
+    items.extend(set_rule_item("ip", "daddr", &to_cidr_strings(&resolved.allow_ipv4), "accept"));
+    items.extend(set_rule_item("ip6", "daddr", &to_cidr_strings(&resolved.allow_ipv6), "accept"));
+
+    let json = format!("{{\"nftables\":[{}]}}", items.join(","));
+    Ok((json, resolved.unenforceable_hosts))
+}
+
+fn to_cidr_strings<T: std::fmt::Display>(nets: &[T]) -> Vec<String> {
+    nets.iter().map(|n| n.to_string()).collect()
+}
+
+fn table_item() -> String {
+    format!(r#"{{"table":{{"family":"inet","name":"{TABLE_NAME}"}}}}"#)
+}
+
+fn chain_item(default_verdict: &str) -> String {
+    format!(
+        r#"{{"chain":{{"family":"inet","table":"{TABLE_NAME}","name":"{CHAIN_NAME}","type":"filter","hook":"output","prio":0,"policy":"{default_verdict}"}}}}"#
+    )
+}
+
+/// A rule matching `daddr`/`saddr`-style `field` of protocol `family`
+/// ("ip" or "ip6") against the set of `cidrs`, terminating with `verdict`.
+/// `None` if `cidrs` is empty — an empty set match would never fire, so
+/// it's left out of the ruleset entirely rather than emitted as a no-op.
+fn set_rule_item(family: &str, field: &str, cidrs: &[String], verdict: &str) -> Option<String> {
+    if cidrs.is_empty() {
+        return None;
+    }
+
+    let set = cidrs.iter().map(|c| format!("\"{c}\"")).collect::<Vec<_>>().join(",");
+    Some(format!(
+        r#"{{"rule":{{"family":"inet","table":"{TABLE_NAME}","chain":"{CHAIN_NAME}","expr":[{{"match":{{"op":"==","left":{{"payload":{{"protocol":"{family}","field":"{field}"}}}},"right":{{"set":[{set}]}}}}}},{{"{verdict}":null}}]}}}}"#
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bwrap_proxy::config::HostGroup;
+    use indexmap::IndexMap;
+
+    fn network_config_with(group_name: &str, group: HostGroup) -> NetworkConfig {
+        let mut groups = IndexMap::new();
+        groups.insert(group_name.to_string(), group);
+        NetworkConfig {
+            groups,
+            ..NetworkConfig::default()
+        }
+    }
+
+    #[test]
+    fn compiles_cidr_ranges_and_reports_unenforceable_hostnames() {
+        let group = HostGroup {
+            hosts: vec!["*.example.com".to_string()],
+            ipv4_ranges: vec!["10.0.0.0/8".to_string()],
+            ..HostGroup::default()
+        };
+        let network_config = network_config_with("corp", group);
+
+        let policy = NetworkPolicy {
+            default: DefaultMode::Deny,
+            allow_groups: vec!["corp".to_string()],
+            ..NetworkPolicy::default()
+        };
+
+        let (json, unenforceable) = compile(&policy, &network_config).unwrap();
+
+        assert!(json.contains("10.0.0.0/8"));
+        assert!(json.contains("\"policy\":\"drop\""));
+        assert_eq!(unenforceable, vec!["*.example.com".to_string()]);
+    }
+
+    #[test]
+    fn empty_policy_emits_only_table_and_chain() {
+        let policy = NetworkPolicy {
+            default: DefaultMode::Allow,
+            ..NetworkPolicy::default()
+        };
+        let network_config = NetworkConfig::default();
+
+        let (json, unenforceable) = compile(&policy, &network_config).unwrap();
+
+        assert!(json.contains("\"policy\":\"accept\""));
+        assert!(!json.contains("\"rule\""));
+        assert!(unenforceable.is_empty());
+    }
+}
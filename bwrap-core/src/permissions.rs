@@ -0,0 +1,193 @@
+//! Deno-inspired granular, path-scoped permission rules
+//!
+//! `--allow-ro`/`--allow-rw` (see `args::CommonArgs`) are flat lists with no
+//! way to carve an exception out of something broader, and `--full-home-access`
+//! is an all-or-nothing switch. This adds ordered `PermissionRule` lists in
+//! the same spirit as Deno's `--allow-read=<path>`/`--deny-read=<path>`
+//! flags: unlike the gitignore-style negation `crate::config::expand_path_rules`
+//! uses for `FilesystemSpec`'s path lists (where a later rule can re-include
+//! what an earlier one excluded), a deny rule here always wins over any
+//! allow rule it overlaps, regardless of declaration order — carving
+//! `~/.ssh` back out from under `--full-home-access` has to work the same
+//! whether `--deny-write` was passed before or after it.
+//!
+//! `SandboxBuilder` is the only consumer: its mount setup asks
+//! `PermissionSet::allowed_paths` for the concrete paths to bind-mount, and
+//! `SandboxBuilder::new` asks `PermissionSet::allows_run` to gate the single
+//! top-level executable it launches. Confining what *that* process goes on to exec inside the
+//! sandbox is a job for seccomp, not this module — see the syscall-filtering
+//! work this is expected to grow into.
+
+use std::path::{Path, PathBuf};
+
+/// Filesystem access mode a `PermissionRule` covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    Read,
+    Write,
+}
+
+/// One allow or deny rule over a glob path pattern
+#[derive(Debug, Clone)]
+pub struct PermissionRule {
+    pub pattern: String,
+    pub mode: AccessMode,
+    pub allow: bool,
+}
+
+/// Ordered allow/deny rules for filesystem access, plus an `--allow-run`
+/// executable allowlist.
+#[derive(Debug, Clone, Default)]
+pub struct PermissionSet {
+    rules: Vec<PermissionRule>,
+    /// Executable file names allowed to run as the sandbox's top-level
+    /// process. An empty list means unrestricted, preserving existing
+    /// behavior for callers that never set `--allow-run`.
+    allow_run: Vec<String>,
+}
+
+impl PermissionSet {
+    /// Build from `CommonArgs`' granular flags. `full_home_access` expands
+    /// to a single write-allow rule over all of `home` so
+    /// `--full-home-access` is just a special case of this same rule list,
+    /// rather than a separate code path — see `SandboxBuilder::setup_mounts`.
+    pub fn from_args(
+        allow_read: &[String],
+        allow_write: &[String],
+        deny_read: &[String],
+        deny_write: &[String],
+        allow_run: &[String],
+        full_home_access: bool,
+        home: &Path,
+    ) -> Self {
+        let mut rules = Vec::new();
+        if full_home_access {
+            rules.push(PermissionRule {
+                pattern: format!("{}/**", home.display()),
+                mode: AccessMode::Write,
+                allow: true,
+            });
+        }
+        rules.extend(allow_read.iter().map(|p| PermissionRule {
+            pattern: p.clone(),
+            mode: AccessMode::Read,
+            allow: true,
+        }));
+        rules.extend(allow_write.iter().map(|p| PermissionRule {
+            pattern: p.clone(),
+            mode: AccessMode::Write,
+            allow: true,
+        }));
+        rules.extend(deny_read.iter().map(|p| PermissionRule {
+            pattern: p.clone(),
+            mode: AccessMode::Read,
+            allow: false,
+        }));
+        rules.extend(deny_write.iter().map(|p| PermissionRule {
+            pattern: p.clone(),
+            mode: AccessMode::Write,
+            allow: false,
+        }));
+
+        Self {
+            rules,
+            allow_run: allow_run.to_vec(),
+        }
+    }
+
+    /// Concrete, existing paths granted for `mode`: every allow rule's glob
+    /// pattern expanded, minus anything a deny rule for the same mode also
+    /// matches.
+    pub fn allowed_paths(&self, mode: AccessMode) -> Vec<PathBuf> {
+        let denied = self.denied_paths(mode);
+
+        self.rules
+            .iter()
+            .filter(|r| r.allow && r.mode == mode)
+            .flat_map(|r| glob_expand(&r.pattern))
+            .filter(|path| !denied.iter().any(|d| path.starts_with(d) || path == d))
+            .collect()
+    }
+
+    /// Concrete, existing paths any deny rule matching `mode` covers.
+    pub fn denied_paths(&self, mode: AccessMode) -> Vec<PathBuf> {
+        self.rules
+            .iter()
+            .filter(|r| !r.allow && r.mode == mode)
+            .flat_map(|r| glob_expand(&r.pattern))
+            .collect()
+    }
+
+    /// Whether `exe_name` (a bare file name, not a path) may run as the
+    /// sandbox's top-level process. An empty `--allow-run` list means
+    /// unrestricted.
+    pub fn allows_run(&self, exe_name: &str) -> bool {
+        self.allow_run.is_empty() || self.allow_run.iter().any(|e| e == exe_name)
+    }
+}
+
+fn glob_expand(pattern: &str) -> Vec<PathBuf> {
+    let trimmed = pattern.trim_end_matches('/');
+
+    if !trimmed.contains(['*', '?', '[']) {
+        let p = PathBuf::from(trimmed);
+        return if p.exists() { vec![p] } else { vec![] };
+    }
+
+    match glob::glob(trimmed) {
+        Ok(paths) => paths.filter_map(std::result::Result::ok).collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bwrap-permissions-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_deny_wins_regardless_of_rule_order() {
+        let dir = tmp_dir("deny-wins");
+        fs::create_dir_all(dir.join("secrets")).unwrap();
+
+        let set = PermissionSet::from_args(
+            &[format!("{}/**", dir.display())],
+            &[],
+            &[format!("{}/secrets", dir.display())],
+            &[],
+            &[],
+            false,
+            Path::new("/nonexistent-home"),
+        );
+
+        let allowed = set.allowed_paths(AccessMode::Read);
+        assert!(!allowed.iter().any(|p| p == &dir.join("secrets")));
+    }
+
+    #[test]
+    fn test_full_home_access_expands_to_write_allow_rule() {
+        let home = tmp_dir("home");
+        let set = PermissionSet::from_args(&[], &[], &[], &[], &[], true, &home);
+        assert!(set.allowed_paths(AccessMode::Write).iter().any(|p| p.starts_with(&home)));
+    }
+
+    #[test]
+    fn test_allow_run_empty_means_unrestricted() {
+        let set = PermissionSet::default();
+        assert!(set.allows_run("anything"));
+    }
+
+    #[test]
+    fn test_allow_run_restricts_to_listed_names() {
+        let set = PermissionSet::from_args(&[], &[], &[], &[], &["git".to_string()], false, Path::new("/"));
+        assert!(set.allows_run("git"));
+        assert!(!set.allows_run("curl"));
+    }
+}
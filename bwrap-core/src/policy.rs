@@ -1,7 +1,7 @@
 //! Policy resolution and setup
 
 use crate::args::CommonArgs;
-use crate::config::{Config, FilesystemSpec, NetworkMode, Policy};
+use crate::config::{Config, FilesystemSpec, NetworkMode, Policy, SeccompSpec};
 use crate::proxy::create_proxy_task;
 use anyhow::Result;
 
@@ -15,6 +15,8 @@ pub struct PolicySetup {
     pub filesystem_spec: FilesystemSpec,
     /// Network mode configured from the policy
     pub network_mode: NetworkMode,
+    /// Seccomp profile resolved from the policy (see `crate::seccomp`)
+    pub seccomp_spec: SeccompSpec,
 }
 
 /// Set up policy configuration for a tool
@@ -49,6 +51,16 @@ pub async fn setup_policy(
         FilesystemSpec::default()
     };
 
+    // Resolve seccomp profile based on the policy, falling back to the
+    // shipped default denylist if unset or unresolvable (the filter is
+    // always on, never silently absent; see `SeccompSpec::default`)
+    let seccomp_spec = if let Some(profile_name) = &policy.seccomp {
+        crate::config::resolve_seccomp_config(config, profile_name)
+            .unwrap_or_else(|_| SeccompSpec::default())
+    } else {
+        SeccompSpec::default()
+    };
+
     // Determine network mode based on CLI flags and policy network settings
     let network_mode = if common.no_network {
         NetworkMode::Disabled
@@ -68,6 +80,9 @@ pub async fn setup_policy(
                     Some(policy_name_str),
                     None,
                     None,
+                    common.proxy_config.clone(),
+                    crate::prompt::options_from_args(common),
+                    common.max_config_size,
                 )
                 .await
                 .map_err(|e| {
@@ -91,5 +106,6 @@ pub async fn setup_policy(
         policy_name: policy_name_str.to_string(),
         filesystem_spec,
         network_mode,
+        seccomp_spec,
     })
 }
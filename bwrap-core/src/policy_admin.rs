@@ -0,0 +1,254 @@
+//! `policy`/`group` management subcommands
+//!
+//! `CommonArgs` only ever reads policy files (`--list-policies`,
+//! `--list-groups`); this gives bw-* binaries a `new`/`add`/`rm`/`ls`
+//! surface for both named policies and the reusable host groups they
+//! reference, following the same shape Tauri's permission/capability ACL
+//! subcommands use. Edits target the same TOML file `--policy`/
+//! `--proxy-config` reads from.
+//!
+//! Like `prompt::persist_allowed_host`, writes here go through a full
+//! deserialize-mutate-reserialize round trip via `Config`/`toml` rather
+//! than an in-place text editor, so existing comments and formatting in
+//! the file are not preserved.
+
+use crate::capabilities::BwrapCapabilities;
+use crate::config::{Config, ConfigLoader, HostGroup, Policy};
+use crate::error::{Result, SandboxError};
+use clap::Subcommand;
+use std::path::{Path, PathBuf};
+
+/// `policy`/`group` management subcommands, flattened alongside a tool's
+/// own launch args. `None` (the default clap gives an unmatched
+/// invocation) means "launch the sandbox as usual".
+#[derive(Subcommand, Debug)]
+pub enum AdminCommand {
+    /// Manage named network policies
+    Policy {
+        #[command(subcommand)]
+        action: PolicyAction,
+    },
+    /// Manage reusable host groups that policies reference
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    /// Print launcher, relay protocol, and detected bwrap version/capability info
+    Version,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PolicyAction {
+    /// Create a new, empty policy
+    New { name: String },
+    /// Allow a host group (or a bare domain, wrapped in an implicit group) in a policy
+    Add { name: String, target: String },
+    /// Remove a previously-added group or domain from a policy
+    Rm { name: String, target: String },
+    /// List configured policies
+    Ls,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GroupAction {
+    /// Create a new, empty host group
+    New { name: String },
+    /// Add a host (domain or IP) to a group
+    Add { name: String, host: String },
+    /// Remove a host from a group
+    Rm { name: String, host: String },
+    /// List configured host groups
+    Ls,
+}
+
+/// Name of the implicit per-policy group `policy add <name> <domain>` puts
+/// a bare domain (as opposed to an existing group name) into.
+fn implicit_group_name(policy_name: &str) -> String {
+    format!("{policy_name}-hosts")
+}
+
+/// Run an `AdminCommand` against the config file at `config_path` (falling
+/// back to `ConfigLoader::default_config_path()` if unset), printing
+/// results to stdout. A fresh config is created on first write if the
+/// file doesn't exist yet.
+pub fn run(command: AdminCommand, config_path: Option<&Path>) -> Result<()> {
+    let config_path = config_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(ConfigLoader::default_config_path);
+
+    match command {
+        AdminCommand::Policy { action } => run_policy_action(action, &config_path),
+        AdminCommand::Group { action } => run_group_action(action, &config_path),
+        AdminCommand::Version => run_version(),
+    }
+}
+
+/// Print the launcher's own version, the relay wire protocol version it was
+/// built against, and what the installed `bwrap` reports/supports — so a
+/// user can diagnose "this mount mode isn't supported by your bubblewrap"
+/// without reading strace output.
+fn run_version() -> Result<()> {
+    let launcher_name = std::env::args()
+        .next()
+        .and_then(|argv0| Path::new(&argv0).file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "launcher".to_string());
+    println!("{launcher_name} version: {}", env!("CARGO_PKG_VERSION"));
+    println!("Relay wire protocol version (compiled in): {}", bwrap_proxy::RELAY_PROTOCOL_VERSION);
+
+    let caps = BwrapCapabilities::probe(Path::new("bwrap"));
+    match &caps.version {
+        Some(version) => println!("Detected bwrap: {version}"),
+        None => println!("Detected bwrap: not found (is `bwrap` on your PATH?)"),
+    }
+
+    println!("Mount modes usable on this host:");
+    for (name, usable) in caps.mount_mode_support() {
+        println!("  {:<10} {}", name, if usable { "yes" } else { "no (bwrap too old or not found)" });
+    }
+
+    Ok(())
+}
+
+fn load(config_path: &Path) -> Result<Config> {
+    if config_path.exists() {
+        ConfigLoader::load_from_file(config_path)
+    } else {
+        Ok(Config::default())
+    }
+}
+
+fn save(config: &Config, config_path: &PathBuf) -> Result<()> {
+    let serialized = toml::to_string_pretty(config)
+        .map_err(|e| SandboxError::ConfigError(format!("Failed to serialize config: {e}")))?;
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(config_path, serialized)?;
+    Ok(())
+}
+
+fn run_policy_action(action: PolicyAction, config_path: &PathBuf) -> Result<()> {
+    match action {
+        PolicyAction::New { name } => {
+            let mut config = load(config_path)?;
+            if config.policy.policies.contains_key(&name) {
+                return Err(SandboxError::ConfigError(format!("Policy '{name}' already exists")));
+            }
+            config.policy.policies.insert(name.clone(), Policy::default());
+            save(&config, config_path)?;
+            println!("Created policy '{name}'");
+        }
+        PolicyAction::Add { name, target } => {
+            let mut config = load(config_path)?;
+
+            let group_name = if config.network.groups.contains_key(&target) {
+                target.clone()
+            } else {
+                let group_name = implicit_group_name(&name);
+                let group = config
+                    .network
+                    .groups
+                    .entry(group_name.clone())
+                    .or_insert_with(|| HostGroup {
+                        description: format!("Hosts added directly to policy '{name}'"),
+                        hosts: Vec::new(),
+                        hosts_deny: Vec::new(),
+                        ipv4_ranges: Vec::new(),
+                        ipv6_ranges: Vec::new(),
+                        groups: Vec::new(),
+                    });
+                if !group.hosts.iter().any(|h| h == &target) {
+                    group.hosts.push(target.clone());
+                }
+                group_name
+            };
+
+            let policy = config.policy.policies.get_mut(&name).ok_or_else(|| {
+                SandboxError::ConfigError(format!("Policy '{name}' not found; run `policy new {name}` first"))
+            })?;
+            if !policy.network.allow_groups.iter().any(|g| g == &group_name) {
+                policy.network.allow_groups.push(group_name.clone());
+            }
+
+            save(&config, config_path)?;
+            println!("Added '{target}' to policy '{name}' (group '{group_name}')");
+        }
+        PolicyAction::Rm { name, target } => {
+            let mut config = load(config_path)?;
+            let policy = config
+                .policy
+                .policies
+                .get_mut(&name)
+                .ok_or_else(|| SandboxError::ConfigError(format!("Policy '{name}' not found")))?;
+
+            let removed_direct_group = {
+                let before = policy.network.allow_groups.len();
+                policy.network.allow_groups.retain(|g| g != &target);
+                before != policy.network.allow_groups.len()
+            };
+
+            if !removed_direct_group {
+                // `target` wasn't a group this policy allows directly; it may
+                // be a domain previously added via `policy add`, which lives
+                // in this policy's implicit host group instead.
+                let implicit = implicit_group_name(&name);
+                if let Some(group) = config.network.groups.get_mut(&implicit) {
+                    group.hosts.retain(|h| h != &target);
+                }
+            }
+
+            save(&config, config_path)?;
+            println!("Removed '{target}' from policy '{name}'");
+        }
+        PolicyAction::Ls => {
+            let config = load(config_path)?;
+            for (name, policy) in &config.policy.policies {
+                println!("{} - {}", name, policy.description.as_deref().unwrap_or("(no description)"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_group_action(action: GroupAction, config_path: &PathBuf) -> Result<()> {
+    match action {
+        GroupAction::New { name } => {
+            let mut config = load(config_path)?;
+            if config.network.groups.contains_key(&name) {
+                return Err(SandboxError::ConfigError(format!("Group '{name}' already exists")));
+            }
+            config.network.groups.insert(name.clone(), HostGroup::default());
+            save(&config, config_path)?;
+            println!("Created group '{name}'");
+        }
+        GroupAction::Add { name, host } => {
+            let mut config = load(config_path)?;
+            let group = config.network.groups.get_mut(&name).ok_or_else(|| {
+                SandboxError::ConfigError(format!("Group '{name}' not found; run `group new {name}` first"))
+            })?;
+            if !group.hosts.iter().any(|h| h == &host) {
+                group.hosts.push(host.clone());
+            }
+            save(&config, config_path)?;
+            println!("Added '{host}' to group '{name}'");
+        }
+        GroupAction::Rm { name, host } => {
+            let mut config = load(config_path)?;
+            let group = config
+                .network
+                .groups
+                .get_mut(&name)
+                .ok_or_else(|| SandboxError::ConfigError(format!("Group '{name}' not found")))?;
+            group.hosts.retain(|h| h != &host);
+            save(&config, config_path)?;
+            println!("Removed '{host}' from group '{name}'");
+        }
+        GroupAction::Ls => {
+            let config = load(config_path)?;
+            for (name, group) in &config.network.groups {
+                println!("{} - {}", name, group.description);
+            }
+        }
+    }
+    Ok(())
+}
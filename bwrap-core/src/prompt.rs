@@ -0,0 +1,288 @@
+//! Interactive allow/deny prompt control socket
+//!
+//! Paired with `bwrap_proxy::proxy::prompt`: when `--policy-prompt` is set,
+//! `create_proxy_task` starts a `PromptServer` on the host side (outside
+//! the sandbox) that the proxy connects to whenever it encounters a CONNECT
+//! to a host the active policy denies. The proxy holds that connection
+//! open while this server asks the user directly on `/dev/tty` rather than
+//! stdin/stdout, since by the time a prompt fires the sandboxed child
+//! usually holds the parent's inherited stdin. Concurrent prompts are
+//! serialized through `prompt_lock` so two connections can't interleave
+//! their questions on the terminal. Five answers are offered: allow once,
+//! allow for the rest of this session (kept in-memory by the proxy's
+//! `SessionAllowlist`, never written here), allow always (persist an allow
+//! rule), deny once, and deny always (persist a deny rule); persisted
+//! rules are picked up by any running `ConfigWatcher` for the same policy
+//! without needing a restart.
+//!
+//! Filesystem access isn't wired into this mechanism: `MountPoint`s are
+//! all resolved before `bwrap` execs the guest, so there's no point at
+//! which an out-of-mount path open could be intercepted and turned into a
+//! prompt the way a CONNECT can be paused mid-flight. Offering the same
+//! "allow and bind read-only" UX for `MountMode` would need a
+//! `SECCOMP_RET_USER_NOTIF` filter and a supervisor reading notifications
+//! off its fd (see `crate::seccomp`, whose classic-BPF programs only allow
+//! or fail a syscall with EPERM and use no user notification), not
+//! just a reuse of this control socket.
+
+use crate::args::CommonArgs;
+use crate::config::{Config, ConfigLoader, HostGroup, Policy};
+use crate::error::{Result, SandboxError};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader as StdBufReader, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Mutex;
+
+/// CLI-level request to enable policy prompting: where to persist "allow
+/// and remember" decisions, and how long to wait for an answer.
+pub struct PolicyPromptOptions {
+    /// Config file to append persisted allow decisions to (defaults to the
+    /// same file `--proxy-config`/`ConfigLoader` would resolve to)
+    pub config_path: Option<PathBuf>,
+    /// How long to wait for a terminal answer before falling back to deny
+    pub timeout_secs: u64,
+}
+
+/// Build `PolicyPromptOptions` from `--policy-prompt`/`--policy-prompt-timeout-secs`,
+/// or `None` if prompting isn't enabled.
+pub fn options_from_args(common: &CommonArgs) -> Option<PolicyPromptOptions> {
+    common.policy_prompt.then(|| PolicyPromptOptions {
+        config_path: Some(common.proxy_config.clone().unwrap_or_else(ConfigLoader::default_config_path)),
+        timeout_secs: common.policy_prompt_timeout_secs,
+    })
+}
+
+/// A running control socket server. Its `Drop` unlinks the socket; callers
+/// that want it to outlive the current function (as with `ConfigWatcher`)
+/// should `std::mem::forget` it.
+pub struct PromptServer {
+    socket_path: PathBuf,
+}
+
+impl PromptServer {
+    /// Bind `socket_path` and start handling prompt requests in the
+    /// background. "Allow and persist" answers are appended to the
+    /// `prompted` host group in `config_path`'s `policy_name` policy.
+    pub async fn start(socket_path: PathBuf, config_path: Option<PathBuf>, policy_name: String) -> Result<Self> {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path)?;
+
+        let prompt_lock = Arc::new(Mutex::new(()));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!("Policy prompt socket accept failed: {}", e);
+                        continue;
+                    }
+                };
+
+                let prompt_lock = prompt_lock.clone();
+                let config_path = config_path.clone();
+                let policy_name = policy_name.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, &prompt_lock, config_path, &policy_name).await {
+                        tracing::debug!("Policy prompt connection error: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { socket_path })
+    }
+}
+
+impl Drop for PromptServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    prompt_lock: &Mutex<()>,
+    config_path: Option<PathBuf>,
+    policy_name: &str,
+) -> Result<()> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+
+    let host = line.trim().strip_prefix("PROMPT ").unwrap_or(line.trim()).to_string();
+
+    // Serialize terminal prompts: hold the lock for the whole ask-and-wait
+    // so two concurrent connections can't interleave their questions.
+    let guard = prompt_lock.lock().await;
+    let decision = tokio::task::spawn_blocking({
+        let host = host.clone();
+        move || ask_terminal(&host)
+    })
+    .await
+    .unwrap_or(PromptAnswer::DenyOnce);
+    drop(guard);
+
+    match decision {
+        PromptAnswer::AllowPersist => {
+            if let Some(ref config_path) = config_path {
+                if let Err(e) = persist_allowed_host(config_path, policy_name, &host) {
+                    tracing::warn!("Failed to persist allowed host {}: {}", host, e);
+                }
+            } else {
+                tracing::warn!("No config path to persist allowed host {} into", host);
+            }
+        }
+        PromptAnswer::DenyPersist => {
+            if let Some(ref config_path) = config_path {
+                if let Err(e) = persist_denied_host(config_path, policy_name, &host) {
+                    tracing::warn!("Failed to persist denied host {}: {}", host, e);
+                }
+            } else {
+                tracing::warn!("No config path to persist denied host {} into", host);
+            }
+        }
+        // "Allow for this session" is tracked in-memory by the proxy's
+        // `SessionAllowlist`, not here — nothing to persist.
+        PromptAnswer::AllowOnce | PromptAnswer::AllowSession | PromptAnswer::DenyOnce => {}
+    }
+
+    let reply = match decision {
+        PromptAnswer::AllowOnce => "ALLOW_ONCE\n",
+        PromptAnswer::AllowSession => "ALLOW_SESSION\n",
+        PromptAnswer::AllowPersist => "ALLOW_PERSIST\n",
+        PromptAnswer::DenyOnce => "DENY\n",
+        PromptAnswer::DenyPersist => "DENY_PERSIST\n",
+    };
+    write_half.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+enum PromptAnswer {
+    AllowOnce,
+    AllowSession,
+    AllowPersist,
+    DenyOnce,
+    DenyPersist,
+}
+
+/// Ask on `/dev/tty` rather than stdin/stdout: the sandboxed child holds
+/// the inherited stdin by the time a prompt can fire, so reading from it
+/// here would race the child for input instead of reaching the user.
+fn ask_terminal(host: &str) -> PromptAnswer {
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty");
+    let Ok(mut tty) = tty else {
+        tracing::warn!("No controlling terminal available for policy prompt; denying {}", host);
+        return PromptAnswer::DenyOnce;
+    };
+
+    let _ = write!(
+        tty,
+        "\nbwrap: allow connection to {host}? \
+         [y]es-once / [s]ession / [a]lways (persist) / [N]o-once / [d]eny-always: "
+    );
+    let _ = tty.flush();
+
+    let mut reader = StdBufReader::new(tty);
+    let mut answer = String::new();
+    if reader.read_line(&mut answer).is_err() {
+        return PromptAnswer::DenyOnce;
+    }
+
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => PromptAnswer::AllowOnce,
+        "s" | "session" => PromptAnswer::AllowSession,
+        "a" | "always" => PromptAnswer::AllowPersist,
+        "d" | "deny-always" => PromptAnswer::DenyPersist,
+        _ => PromptAnswer::DenyOnce,
+    }
+}
+
+/// Append `host` to the `prompted` host group referenced by `policy_name`'s
+/// `allow_groups` in `config_path`, creating both if they don't already
+/// exist, then write the file back.
+fn persist_allowed_host(config_path: &Path, policy_name: &str, host: &str) -> Result<()> {
+    const PROMPTED_GROUP: &str = "prompted";
+
+    persist_prompted_host(
+        config_path,
+        policy_name,
+        host,
+        PROMPTED_GROUP,
+        "Hosts allowed via interactive policy prompts",
+        |policy| &mut policy.network.allow_groups,
+    )
+}
+
+/// Append `host` to the `prompt_denied` host group referenced by
+/// `policy_name`'s `deny_groups` in `config_path`, creating both if they
+/// don't already exist, then write the file back.
+fn persist_denied_host(config_path: &Path, policy_name: &str, host: &str) -> Result<()> {
+    const PROMPT_DENIED_GROUP: &str = "prompt_denied";
+
+    persist_prompted_host(
+        config_path,
+        policy_name,
+        host,
+        PROMPT_DENIED_GROUP,
+        "Hosts denied via interactive policy prompts",
+        |policy| &mut policy.network.deny_groups,
+    )
+}
+
+/// Shared plumbing for `persist_allowed_host`/`persist_denied_host`: load
+/// (or default) `config_path`, add `host` to the `group_name` host group
+/// (creating it if needed), wire that group into `policy_name`'s group
+/// list via `group_list`, and write the file back.
+fn persist_prompted_host(
+    config_path: &Path,
+    policy_name: &str,
+    host: &str,
+    group_name: &str,
+    group_description: &str,
+    group_list: impl FnOnce(&mut Policy) -> &mut Vec<String>,
+) -> Result<()> {
+    let mut config = if config_path.exists() {
+        ConfigLoader::load_from_file(config_path)?
+    } else {
+        Config::default()
+    };
+
+    let policy = config
+        .policy
+        .policies
+        .entry(policy_name.to_string())
+        .or_insert_with(Policy::default);
+    let groups = group_list(policy);
+    if !groups.iter().any(|g| g == group_name) {
+        groups.push(group_name.to_string());
+    }
+
+    let group = config
+        .network
+        .groups
+        .entry(group_name.to_string())
+        .or_insert_with(|| HostGroup {
+            description: group_description.to_string(),
+            hosts: Vec::new(),
+            hosts_deny: Vec::new(),
+            ipv4_ranges: Vec::new(),
+            ipv6_ranges: Vec::new(),
+            groups: Vec::new(),
+        });
+    if !group.hosts.iter().any(|h| h == host) {
+        group.hosts.push(host.to_string());
+    }
+
+    let serialized = toml::to_string_pretty(&config)
+        .map_err(|e| SandboxError::ConfigError(format!("Failed to serialize config: {e}")))?;
+
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(config_path, serialized)?;
+    Ok(())
+}
@@ -1,28 +1,113 @@
 //! Proxy server initialization and management
 
 use anyhow::{Context, Result};
-use bwrap_proxy::{PolicyEngine, ProxyServer, ProxyServerConfig};
-use crate::config::{Config, LearningRecorder, resolve_policy};
+use bwrap_proxy::{HostMatcher, ProxyServer, ProxyServerConfig, UpstreamRouter};
+use crate::config::{Config, ConfigWatcher, LearningRecorder, UpstreamProxyConfig, resolve_policy};
+use crate::prompt::{PolicyPromptOptions, PromptServer};
+use crate::proxy_manager;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::SystemTime;
 
-/// Create and spawn the proxy server as an async task
+/// Compile `CommonConfig::proxy.upstream` into a ready-to-query
+/// `UpstreamRouter`, parsing each configured URL eagerly so a typo is
+/// reported at startup rather than on the first connection that would have
+/// used it.
+fn build_upstream_router(config: &UpstreamProxyConfig) -> Result<UpstreamRouter> {
+    match config {
+        UpstreamProxyConfig::None => Ok(UpstreamRouter::direct()),
+        UpstreamProxyConfig::Global { url } => {
+            let url = url::Url::parse(url).context(format!("Invalid upstream proxy URL: {url}"))?;
+            Ok(UpstreamRouter::global(url))
+        }
+        UpstreamProxyConfig::ByDomain { rules, fallback } => {
+            let rules = rules
+                .iter()
+                .map(|rule| {
+                    let url = url::Url::parse(&rule.url)
+                        .context(format!("Invalid upstream proxy URL for '{}': {}", rule.pattern, rule.url))?;
+                    let mut matcher = HostMatcher::new();
+                    matcher.add_pattern(&rule.pattern);
+                    Ok((matcher, url))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let fallback = fallback
+                .as_ref()
+                .map(|url| url::Url::parse(url).context(format!("Invalid fallback upstream proxy URL: {url}")))
+                .transpose()?;
+            Ok(UpstreamRouter::by_domain(rules, fallback))
+        }
+    }
+}
+
+/// Create (or attach to) a running proxy and return its socket path
+///
+/// Plain policy enforcement with no learning is the common, repeated case
+/// this exists to speed up, so it's delegated to `proxy_manager::ensure_daemon`:
+/// repeated invocations that resolve to the same policy attach to one
+/// already-running daemon instead of paying proxy startup cost every time.
+/// Learning sessions (`--learn`/`--learn-deny`) are comparatively rare,
+/// one-off data-collection runs rather than a hot path, and the daemon
+/// model has no good answer for "whose learning output wins" if two
+/// differently-configured learning sessions shared one daemon — those keep
+/// spinning up their own private, ephemeral proxy below.
+pub async fn create_proxy_task(
+    config: &Config,
+    policy_name: Option<&str>,
+    learning_output: Option<&PathBuf>,
+    learning_mode: Option<String>,
+    explicit_config: Option<PathBuf>,
+    policy_prompt: Option<PolicyPromptOptions>,
+    max_config_size: u64,
+) -> Result<(PathBuf, Option<String>)> {
+    if learning_output.is_none() && learning_mode.is_none() && policy_prompt.is_none() {
+        if let Some(policy_name) = policy_name {
+            if let Ok(policy) = resolve_policy(config, policy_name) {
+                if matches!(policy.network.network, bwrap_proxy::config::NetworkMode::Proxy) {
+                    let socket_path = proxy_manager::ensure_daemon(config, policy_name, &policy)
+                        .await
+                        .context(format!("Failed to start or attach to proxy daemon for policy: {}", policy_name))?;
+                    return Ok((socket_path, None));
+                }
+            }
+        }
+    }
+
+    create_ephemeral_proxy_task(
+        config,
+        policy_name,
+        learning_output,
+        learning_mode,
+        explicit_config,
+        policy_prompt,
+        max_config_size,
+    )
+    .await
+}
+
+/// Spawn a private, one-shot proxy as an in-process tokio task on a
+/// throwaway `/tmp` socket, for learning sessions (and any policy that
+/// doesn't resolve to `ensure_daemon`'s Proxy-mode path)
 ///
 /// This function:
 /// 1. Generates a unique socket path in /tmp
-/// 2. Creates a PolicyEngine for the specified policy (using already-loaded config)
+/// 2. Starts a `ConfigWatcher` for the specified policy (the already-loaded config only
+///    decides whether the policy needs filtering; the watcher loads and hot-reloads
+///    `explicit_config` plus the usual user/project config files itself)
 /// 3. Creates a LearningRecorder if learning_output is specified
 /// 4. Spawns the proxy server as a tokio task (runs until parent exits)
 /// 5. Waits for the proxy to be ready (listening on the socket)
 /// 6. Returns the socket path and learning mode (if active)
 ///
 /// Note: The proxy will save learning data on shutdown via a cleanup function
-pub async fn create_proxy_task(
+async fn create_ephemeral_proxy_task(
     config: &Config,
     policy_name: Option<&str>,
     learning_output: Option<&PathBuf>,
     learning_mode: Option<String>,
+    explicit_config: Option<PathBuf>,
+    policy_prompt: Option<PolicyPromptOptions>,
+    max_config_size: u64,
 ) -> Result<(PathBuf, Option<String>)> {
     // Generate a unique socket path in /tmp
     let session_id = SystemTime::now()
@@ -31,22 +116,24 @@ pub async fn create_proxy_task(
         .as_nanos();
     let socket_path = PathBuf::from(format!("/tmp/bw-proxy-{}.sock", session_id));
 
-    // Create PolicyEngine if a policy name is specified
+    // Create the policy engine (via a ConfigWatcher, so config edits made while
+    // the sandbox is running take effect without a restart) if a policy name
+    // is specified and it requires filtering.
     let policy_engine = if let Some(policy_name) = policy_name {
         let resolved_policy = resolve_policy(&config, policy_name)
             .context(format!("Failed to load policy: {}", policy_name))?;
 
-        // Only create PolicyEngine if the policy requires filtering (Proxy mode with deny rules)
+        // Only watch/filter if the policy requires filtering (Proxy mode)
         if matches!(resolved_policy.network.network, bwrap_proxy::config::NetworkMode::Proxy) {
-            Some(Arc::new(
-                PolicyEngine::from_network_policy(
-                    resolved_policy.network.effective_allow_groups(),
-                    resolved_policy.network.deny_groups.clone(),
-                    resolved_policy.network.default.clone(),
-                    &config.network,
-                )
-                .context(format!("Failed to initialize policy engine for: {}", policy_name))?,
-            ))
+            let watcher = ConfigWatcher::start_with_max_size(explicit_config, policy_name.to_string(), max_config_size)
+                .await
+                .context(format!("Failed to initialize policy watcher for: {}", policy_name))?;
+            let shared = watcher.shared();
+            // Leak the watcher for the lifetime of the process: the proxy task
+            // spawned below outlives this function, and needs the underlying
+            // notify watcher to keep running for as long as it does.
+            std::mem::forget(watcher);
+            Some(shared)
         } else {
             // For Open or Disabled network modes, no filtering engine needed
             None
@@ -79,6 +166,29 @@ pub async fn create_proxy_task(
         None
     };
 
+    // If policy prompting is enabled, start the control socket the proxy
+    // will ask over before blocking a denied host, and leak it for the
+    // lifetime of the process for the same reason the watcher above is
+    // leaked: it needs to keep running for as long as the proxy task does.
+    let policy_prompt = if let Some(opts) = policy_prompt {
+        let prompt_socket_path = PathBuf::from(format!("/tmp/bw-prompt-{}.sock", session_id));
+        let server = PromptServer::start(
+            prompt_socket_path.clone(),
+            opts.config_path,
+            policy_name.unwrap_or("default").to_string(),
+        )
+        .await
+        .context("Failed to start policy prompt control socket")?;
+        std::mem::forget(server);
+
+        Some(bwrap_proxy::PolicyPrompt {
+            socket_path: prompt_socket_path,
+            timeout: std::time::Duration::from_secs(opts.timeout_secs),
+        })
+    } else {
+        None
+    };
+
     // Create proxy server
     let learning_recorder_trait: Option<Arc<dyn bwrap_proxy::filter::LearningRecorderTrait>> =
         learning_recorder.as_ref().map(|lr| lr.clone() as Arc<dyn bwrap_proxy::filter::LearningRecorderTrait>);
@@ -89,7 +199,20 @@ pub async fn create_proxy_task(
         policy_engine,
         learning_recorder: learning_recorder_trait,
         learning_output: learning_output.cloned(),
-        learning_mode: learning_mode.clone(),
+        learning_save_stats: false,
+        learning_max_age: None,
+        persistent: false,
+        protocol: bwrap_proxy::WireProtocol::Text,
+        verify_sni: false,
+        sni_fallback: bwrap_proxy::SniFallback::default(),
+        upstream_router: build_upstream_router(&config.common.proxy.upstream)
+            .context("Failed to compile upstream proxy configuration")?,
+        graceful_shutdown: false,
+        drain_timeout: std::time::Duration::from_secs(30),
+        resolver: None,
+        rate_limit: None,
+        policy_prompt,
+        session_allowlist: bwrap_proxy::SessionAllowlist::default(),
     };
 
     let proxy = ProxyServer::new(proxy_config);
@@ -0,0 +1,315 @@
+//! Persistent, reusable proxy daemon lifecycle management
+//!
+//! `create_proxy_task` used to spin up a private proxy per sandbox
+//! invocation on a throwaway `/tmp/bw-proxy-<timestamp>.sock`, busy-waiting
+//! on `Path::exists()` for it to come up. Following odproxy's service
+//! spawn/stop model, this module instead keys a proxy daemon by a hash of
+//! its resolved policy + network config, registers its socket under
+//! `$XDG_RUNTIME_DIR/bwrap/<hash>.sock`, and lets any sandbox launch that
+//! resolves to the same policy attach to an already-running daemon instead
+//! of spawning its own. A daemon is just a detached `bwrap-proxy` binary
+//! invocation (the same binary `bwrap-proxy/src/main.rs` builds), started
+//! with `--persistent` so its socket survives past the first connection,
+//! and outlives whichever `bw-claude`/`bw-gemini` invocation started it.
+//!
+//! Health is checked by connecting to the socket, not by polling
+//! `exists()`: a crashed daemon can leave a stale socket file behind, and a
+//! freshly-spawned one may not be listening yet even though the file (and
+//! its directory entry) already exist.
+
+use crate::config::{Config, Policy};
+use crate::error::{Result, SandboxError};
+use crate::lockfile::canonical_digest;
+use bwrap_proxy::config::NetworkConfig;
+use serde::Serialize;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tokio::net::UnixStream;
+
+/// How long to keep retrying the connect health-check after spawning a
+/// fresh daemon before giving up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(2);
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Observed state of a named proxy daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonStatus {
+    /// Something is listening on the daemon's socket
+    Running,
+    /// No daemon is registered, or it isn't responding
+    NotRunning,
+}
+
+#[derive(Serialize)]
+struct PolicyFingerprint<'a> {
+    policy_name: &'a str,
+    network: &'a NetworkConfig,
+    policy: &'a Policy,
+}
+
+/// Hash the resolved policy + network config that determine a proxy
+/// daemon's behavior, so invocations that resolve to the same effective
+/// policy share a daemon, and an edit that changes the policy gets a fresh
+/// hash (and therefore a fresh daemon) rather than reusing a stale one.
+pub fn policy_hash(config: &Config, policy_name: &str, policy: &Policy) -> Result<String> {
+    canonical_digest(&PolicyFingerprint {
+        policy_name,
+        network: &config.network,
+        policy,
+    })
+}
+
+/// Directory proxy daemon sockets, pidfiles, and metadata are registered
+/// in: `$XDG_RUNTIME_DIR/bwrap`, falling back to a per-user directory under
+/// the system temp dir when `XDG_RUNTIME_DIR` isn't set
+pub fn runtime_dir() -> PathBuf {
+    if let Ok(xdg_runtime) = env::var("XDG_RUNTIME_DIR") {
+        PathBuf::from(xdg_runtime).join("bwrap")
+    } else {
+        let user = env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+        env::temp_dir().join(format!("bwrap-{user}"))
+    }
+}
+
+fn socket_path(hash: &str) -> PathBuf {
+    runtime_dir().join(format!("{hash}.sock"))
+}
+
+fn pid_path(hash: &str) -> PathBuf {
+    runtime_dir().join(format!("{hash}.pid"))
+}
+
+/// Sidecar recording which policy name a registered daemon was started
+/// for, so a later invocation can tell whether that daemon's policy config
+/// has since changed (see `reap_orphaned`)
+fn meta_path(hash: &str) -> PathBuf {
+    runtime_dir().join(format!("{hash}.meta"))
+}
+
+/// Generated `--config` handed to the spawned daemon, embedding its fully
+/// resolved policy (see `resolved_daemon_config`) rather than relying on
+/// the daemon to find and resolve one itself
+fn daemon_config_path(hash: &str) -> PathBuf {
+    runtime_dir().join(format!("{hash}.proxy-config.toml"))
+}
+
+/// Health-check a daemon by connecting to its socket
+pub async fn status(hash: &str) -> DaemonStatus {
+    match UnixStream::connect(socket_path(hash)).await {
+        Ok(_) => DaemonStatus::Running,
+        Err(_) => DaemonStatus::NotRunning,
+    }
+}
+
+/// Stop the daemon registered under `hash`, if any: sends SIGTERM to its
+/// recorded pid and removes its registration files
+pub fn stop(hash: &str) -> Result<()> {
+    if let Ok(pid) = std::fs::read_to_string(pid_path(hash)) {
+        let _ = Command::new("kill").arg("-TERM").arg(pid.trim()).status();
+    }
+    let _ = std::fs::remove_file(pid_path(hash));
+    let _ = std::fs::remove_file(meta_path(hash));
+    let _ = std::fs::remove_file(socket_path(hash));
+    let _ = std::fs::remove_file(daemon_config_path(hash));
+    Ok(())
+}
+
+/// Stop and remove registration for any daemon whose recorded policy name
+/// no longer resolves to the same hash under the current `config` — i.e.
+/// its policy config changed since it was started, so it's serving a grant
+/// set nothing asks for anymore
+fn reap_orphaned(config: &Config) -> Result<()> {
+    let dir = runtime_dir();
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(());
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("meta") {
+            continue;
+        }
+        let Some(hash) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Ok(policy_name) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let policy_name = policy_name.trim();
+
+        let still_current = crate::resolve_policy(config, policy_name)
+            .ok()
+            .and_then(|policy| policy_hash(config, policy_name, &policy).ok())
+            .is_some_and(|current_hash| current_hash == hash);
+
+        if !still_current {
+            tracing::info!(
+                "Reaping proxy daemon for '{policy_name}' ({hash}): policy config has changed"
+            );
+            stop(hash)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensure a daemon for `policy_name` is running and return its socket path.
+///
+/// Reuses an already-running, healthy daemon registered under this
+/// policy's hash if one exists; otherwise reaps any daemons whose policy
+/// config has drifted since they were started, and spawns a fresh detached
+/// `bwrap-proxy --persistent` process.
+pub async fn ensure_daemon(config: &Config, policy_name: &str, policy: &Policy) -> Result<PathBuf> {
+    let dir = runtime_dir();
+    std::fs::create_dir_all(&dir).map_err(SandboxError::Io)?;
+
+    reap_orphaned(config)?;
+
+    let hash = policy_hash(config, policy_name, policy)?;
+    let socket = socket_path(&hash);
+
+    if status(&hash).await == DaemonStatus::Running {
+        tracing::debug!("Attaching to existing proxy daemon for '{policy_name}' ({hash})");
+        return Ok(socket);
+    }
+
+    spawn_daemon(&hash, &socket, config, policy_name, policy)?;
+    std::fs::write(meta_path(&hash), policy_name).map_err(SandboxError::Io)?;
+
+    wait_until_running(&hash).await?;
+
+    Ok(socket)
+}
+
+/// `bwrap-proxy`'s own `--config` only ever speaks its crate-local
+/// `network.policies` namespace — a flat `{name: Policy}` map it looks up
+/// directly by name — which is NOT the same namespace as bwrap-core's
+/// top-level `config.policy.policies` that `policy_name` actually resolved
+/// against here (see `resolve_policy`). Passing `policy_name` straight
+/// through via `--mode restrictive:<policy_name>` and relying on the
+/// daemon to re-resolve it from whatever `--config`/project config it
+/// happens to find would only work by coincidence (or not at all, for a
+/// policy like "claude" that bw-proxy's own built-ins don't define). So
+/// this embeds the already-resolved policy, converted into bw-proxy's
+/// schema, into a generated config file the daemon is always handed —
+/// the daemon's job is just to enforce it, not resolve it.
+fn resolved_daemon_config(config: &Config, policy_name: &str, policy: &Policy) -> bwrap_proxy::config::Config {
+    let mut network = config.network.clone();
+    network.policies = std::iter::once((
+        policy_name.to_string(),
+        bwrap_proxy::config::Policy {
+            description: policy.description.clone().unwrap_or_default(),
+            default: policy.network.default.clone(),
+            allow_groups: policy.network.effective_allow_groups(),
+            deny_groups: policy.network.deny_groups.clone(),
+        },
+    ))
+    .collect();
+
+    bwrap_proxy::config::Config { network }
+}
+
+fn spawn_daemon(hash: &str, socket: &Path, config: &Config, policy_name: &str, policy: &Policy) -> Result<()> {
+    let bwrap_proxy_path = find_bwrap_proxy_binary()?;
+
+    let daemon_config = resolved_daemon_config(config, policy_name, policy);
+    let config_path = daemon_config_path(hash);
+    let serialized = toml::to_string_pretty(&daemon_config)
+        .map_err(|e| SandboxError::ConfigError(format!("Failed to serialize daemon config: {e}")))?;
+    std::fs::write(&config_path, serialized).map_err(SandboxError::Io)?;
+
+    let mut cmd = Command::new(bwrap_proxy_path);
+    cmd.arg("--socket")
+        .arg(socket)
+        .arg("--mode")
+        .arg(format!("restrictive:{policy_name}"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--persistent")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let child = cmd.spawn().map_err(SandboxError::Io)?;
+    std::fs::write(pid_path(hash), child.id().to_string()).map_err(SandboxError::Io)?;
+
+    // Not waited on: the child is reparented to init once this process
+    // exits and keeps running as the daemon.
+    Ok(())
+}
+
+async fn wait_until_running(hash: &str) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if status(hash).await == DaemonStatus::Running {
+            return Ok(());
+        }
+        tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+    }
+
+    Err(SandboxError::ConfigError(format!(
+        "Timed out waiting for proxy daemon '{hash}' to start"
+    )))
+}
+
+fn find_bwrap_proxy_binary() -> Result<PathBuf> {
+    if let Ok(exe_path) = env::current_exe() {
+        if let Some(parent) = exe_path.parent() {
+            let candidate = parent.join("bwrap-proxy");
+            if candidate.exists() {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    if let Ok(path_env) = env::var("PATH") {
+        for dir in path_env.split(':') {
+            let candidate = PathBuf::from(dir).join("bwrap-proxy");
+            if candidate.exists() {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    Err(SandboxError::CliNotFound(PathBuf::from("bwrap-proxy")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_policy_hash_stable_for_same_input() {
+        let config = Config::default();
+        let policy = Policy::default();
+        let a = policy_hash(&config, "default", &policy).unwrap();
+        let b = policy_hash(&config, "default", &policy).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_policy_hash_differs_by_policy_name() {
+        let config = Config::default();
+        let policy = Policy::default();
+        let a = policy_hash(&config, "default", &policy).unwrap();
+        let b = policy_hash(&config, "other", &policy).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_resolved_daemon_config_puts_policy_under_its_own_name() {
+        let config = Config::default();
+        let mut policy = Policy::default();
+        policy.network.allow_groups = vec!["github".to_string()];
+        policy.network.deny_groups = vec!["tracking".to_string()];
+
+        let daemon_config = resolved_daemon_config(&config, "claude", &policy);
+
+        let resolved = daemon_config.network.policies.get("claude").unwrap();
+        assert_eq!(resolved.allow_groups, vec!["github".to_string()]);
+        assert_eq!(resolved.deny_groups, vec!["tracking".to_string()]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Tool registry for the generic `bw` multi-call dispatcher
+//!
+//! Each entry describes how to find and launch one CLI tool, replacing a
+//! dedicated `bw-<tool>` binary's hardcoded `get_<tool>_path`/`ToolConfig`
+//! construction with a data-driven TOML registry: a new tool means adding
+//! an entry here, not compiling a new binary. See `bw/src/main.rs` for how
+//! entries are resolved from `argv[0]`/a subcommand and dispatched.
+
+use crate::error::{Result, SandboxError};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Full tool registry, keyed by tool name (matched against `argv[0]`'s
+/// `bw-` prefix or an explicit `bw <name>` subcommand)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolRegistry {
+    #[serde(default)]
+    pub tools: IndexMap<String, ToolRegistryEntry>,
+}
+
+/// One tool's discovery rule and launch defaults
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolRegistryEntry {
+    /// Explicit path to the tool's CLI; checked before any discovery rule
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Candidate paths relative to `$HOME`, checked in order (e.g. `".local/bin/gemini"`)
+    #[serde(default)]
+    pub home_candidates: Vec<String>,
+    /// Fall back to a `which <name>` lookup on `$PATH` if nothing above matched
+    #[serde(default = "default_true")]
+    pub which_fallback: bool,
+    /// Default arguments always passed ahead of the user's own CLI args
+    #[serde(default)]
+    pub default_args: Vec<String>,
+    /// Help text shown for this tool's specific options
+    #[serde(default)]
+    pub help_text: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ToolRegistry {
+    /// Load a registry file (the tool table is the whole document, i.e.
+    /// `[tools.claude]`, `[tools.gemini]`, ...)
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|source| SandboxError::ConfigLoad {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Built-in entries for the tools `bw-claude`/`bw-gemini` used to
+    /// hardcode, so `bw claude`/`bw gemini` (and the symlinked
+    /// `bw-claude`/`bw-gemini` names) keep working with no registry file
+    /// present.
+    pub fn builtin() -> Self {
+        let mut tools = IndexMap::new();
+        tools.insert(
+            "claude".to_string(),
+            ToolRegistryEntry {
+                path: None,
+                home_candidates: vec![".claude/local/claude".to_string()],
+                which_fallback: false,
+                default_args: vec!["--dangerously-skip-permissions".to_string()],
+                help_text: "Claude-specific options:\n  By default, --dangerously-skip-permissions is passed to Claude.\n  Remove it from this tool's `default_args` in the registry to disable that behavior."
+                    .to_string(),
+            },
+        );
+        tools.insert(
+            "gemini".to_string(),
+            ToolRegistryEntry {
+                path: None,
+                home_candidates: vec![".local/bin/gemini".to_string()],
+                which_fallback: true,
+                default_args: vec![],
+                help_text: "Gemini arguments are passed through unchanged.\n\nFor authentication, you may need to pass environment variables into the sandbox.\nUse the --pass-env argument for each variable you need."
+                    .to_string(),
+            },
+        );
+        Self { tools }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ToolRegistryEntry> {
+        self.tools.get(name)
+    }
+}
+
+impl ToolRegistryEntry {
+    /// Resolve this entry to a concrete CLI path: explicit `path`, then
+    /// each `home_candidates` entry under `$HOME` in order, then a `which`
+    /// lookup on `$PATH` if `which_fallback` is set.
+    pub fn resolve_path(&self, tool_name: &str) -> Result<PathBuf> {
+        if let Some(path) = &self.path {
+            if path.exists() {
+                return Ok(path.clone());
+            }
+        }
+
+        if let Ok(home) = std::env::var("HOME") {
+            for candidate in &self.home_candidates {
+                let p = PathBuf::from(&home).join(candidate);
+                if p.exists() {
+                    return Ok(p);
+                }
+            }
+        }
+
+        if self.which_fallback {
+            if let Ok(output) = std::process::Command::new("which").arg(tool_name).output() {
+                if output.status.success() {
+                    let path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+                    if path.exists() {
+                        return Ok(path);
+                    }
+                }
+            }
+        }
+
+        Err(SandboxError::CliNotFound(PathBuf::from(tool_name)))
+    }
+}
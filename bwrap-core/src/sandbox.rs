@@ -1,14 +1,17 @@
 //! Sandbox builder and execution
 
 use crate::config::{
-    HomeAccessMode, NetworkMode, SandboxConfig, FilesystemSpec,
+    CommitMode, HomeAccessMode, NetworkMode, SandboxConfig, FilesystemSpec, SecurityPolicy, UserMode,
+    expand_path_rules,
 };
 use crate::env::EnvironmentBuilder;
 use crate::error::{Result, SandboxError};
-use crate::mount::MountPoint;
+use crate::mount::{MountMode, MountPoint};
+use crate::permissions::AccessMode;
 
 use std::env;
 use std::fs;
+use std::os::unix::fs::FileTypeExt;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, ExitStatus};
 
@@ -33,11 +36,22 @@ pub struct SandboxBuilder {
     env_builder: EnvironmentBuilder,
     tmp_export_dir: Option<PathBuf>,
     filesystem_spec: FilesystemSpec,
+    overlay_upper_dir: Option<PathBuf>,
 }
 
 impl SandboxBuilder {
     /// Create a new sandbox builder with a filesystem spec
-    pub fn new(config: SandboxConfig, filesystem_spec: FilesystemSpec) -> Result<Self> {
+    ///
+    /// `security_policy`, if present, gates `config.pass_through_env`,
+    /// `config.additional_rw_paths`, and full home access against an
+    /// admin-defined allowlist for `config.tool_name` (see
+    /// `crate::config::SecurityPolicy`), independent of whatever flags the
+    /// caller passed on the command line.
+    pub fn new(
+        config: SandboxConfig,
+        filesystem_spec: FilesystemSpec,
+        security_policy: Option<&SecurityPolicy>,
+    ) -> Result<Self> {
         // Validate configuration
         if !config.shell && !config.tool_config.cli_path.exists() {
             return Err(SandboxError::CliNotFound(
@@ -49,12 +63,37 @@ impl SandboxBuilder {
             return Err(SandboxError::DirNotFound(config.target_dir.clone()));
         }
 
+        if !config.shell {
+            let exe_name = config
+                .tool_config
+                .cli_path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .unwrap_or(&config.tool_config.name);
+            if !config.permissions.allows_run(exe_name) {
+                return Err(SandboxError::ExecutableNotAllowed {
+                    tool: config.tool_name.clone(),
+                    exe: exe_name.to_string(),
+                });
+            }
+        }
+
+        if let Some(policy) = security_policy {
+            policy.check(
+                &config.tool_name,
+                &config.pass_through_env,
+                &config.additional_rw_paths,
+                config.home_access == HomeAccessMode::Full,
+            )?;
+        }
+
         Ok(Self {
             config,
             mounts: Vec::new(),
             env_builder: EnvironmentBuilder::new(),
             tmp_export_dir: None,
             filesystem_spec,
+            overlay_upper_dir: None,
         })
     }
 
@@ -75,6 +114,8 @@ impl SandboxBuilder {
         Ok(Sandbox {
             command,
             tmp_export_dir: self.tmp_export_dir,
+            overlay_upper_dir: self.overlay_upper_dir,
+            target_dir: self.config.target_dir.clone(),
         })
     }
 
@@ -91,6 +132,21 @@ impl SandboxBuilder {
         Ok(export_dir)
     }
 
+    /// Create the overlay's writable upper layer and scratch workdir under
+    /// `tmp_export_dir` (so they're cleaned up the same way on `Drop`), for
+    /// `CommitMode::Overlay`
+    fn create_overlay_dirs(&self) -> Result<(PathBuf, PathBuf)> {
+        let base = self
+            .tmp_export_dir
+            .as_ref()
+            .expect("tmp_export_dir is created before setup_mounts runs");
+        let upper_dir = base.join("overlay-upper");
+        let work_dir = base.join("overlay-work");
+        fs::create_dir_all(&upper_dir).map_err(SandboxError::TmpDirCreation)?;
+        fs::create_dir_all(&work_dir).map_err(SandboxError::TmpDirCreation)?;
+        Ok((upper_dir, work_dir))
+    }
+
     fn setup_mounts(&mut self) -> Result<()> {
         let home = env::var("HOME").map_err(|_| SandboxError::EnvVarNotFound("HOME".to_string()))?;
         let home_path = PathBuf::from(&home);
@@ -107,17 +163,24 @@ impl SandboxBuilder {
         match self.config.home_access {
             HomeAccessMode::Full => {
                 self.mounts.push(MountPoint::rw(&home_path, &home_path));
+                self.apply_full_home_denials(&home_path);
             }
             HomeAccessMode::Safe => {
                 self.mount_ro_home_dirs(&home_path)?;
                 self.mount_rw_home_dirs(&home_path)?;
                 self.mount_home_files(&home_path)?;
+                self.mount_tmp_overlay_home_dirs(&home_path)?;
             }
         }
 
         // Mount additional paths from config (both modes can use this)
         self.mount_config_paths()?;
 
+        // Deno-style granular --allow-read/--allow-write rules (see
+        // `crate::permissions`), layered on top of the flat --allow-ro/
+        // --allow-rw lists below rather than replacing them
+        self.mount_granular_permissions();
+
         // System binaries and libraries (read-only)
         for path in ["/usr", "/lib", "/lib64"] {
             if Path::new(path).exists() {
@@ -132,16 +195,35 @@ impl SandboxBuilder {
         // Note: Tool-specific state directories and dot files should be configured
         // via the filesystem config (safe_home_dirs), not hardcoded here
 
-        // Project directory (read-write by default)
-        self.mounts
-            .push(MountPoint::rw(&self.config.target_dir, &self.config.target_dir));
+        // Project directory: direct rw bind by default, or a discardable
+        // overlay upper layer under `CommitMode::Overlay` (see `CommitMode`)
+        match self.config.commit_mode {
+            CommitMode::Direct => {
+                self.mounts
+                    .push(MountPoint::rw(&self.config.target_dir, &self.config.target_dir));
+            }
+            CommitMode::Overlay => {
+                let (upper_dir, work_dir) = self.create_overlay_dirs()?;
+                self.mounts.push(MountPoint::overlay_src(&self.config.target_dir));
+                self.mounts
+                    .push(MountPoint::overlay(&upper_dir, &work_dir, &self.config.target_dir));
+                self.overlay_upper_dir = Some(upper_dir);
+            }
+        }
 
         // Mount bw-relay binary for command execution
         self.mount_bw_relay()?;
 
-        // Process and device access (handled with special modes)
+        // Process and device access (handled with special modes). Shell
+        // mode always gets a real devpts instance (interactive tools like
+        // pagers/editors/tmux need to allocate their own PTYs); otherwise
+        // it's opt-in via `config.pty`.
         self.mounts.push(MountPoint::proc());
-        self.mounts.push(MountPoint::dev_bind());
+        if self.config.shell || self.config.pty {
+            self.mounts.push(MountPoint::dev());
+        } else {
+            self.mounts.push(MountPoint::dev_bind());
+        }
 
         // Additional mount paths (support relative paths)
         for path in &self.config.additional_ro_paths {
@@ -277,71 +359,117 @@ impl SandboxBuilder {
     }
 
     fn mount_ro_home_dirs(&mut self, home: &Path) -> Result<()> {
-        for dir_name in &self.filesystem_spec.ro_home_dirs {
-            let dir_path = home.join(dir_name);
-            if dir_path.exists() {
-                // Use ro_try to skip if mount fails (e.g., permission issues)
-                self.mounts.push(MountPoint::ro_try(&dir_path, &dir_path));
-            }
+        for dir_path in expand_path_rules(&self.filesystem_spec.ro_home_dirs, home) {
+            // Use ro_try to skip if mount fails (e.g., permission issues)
+            self.mounts.push(MountPoint::ro_try(&dir_path, &dir_path));
         }
         Ok(())
     }
 
     fn mount_rw_home_dirs(&mut self, home: &Path) -> Result<()> {
-        for dir_name in &self.filesystem_spec.rw_home_dirs {
-            let dir_path = home.join(dir_name);
-            if dir_path.exists() {
-                // Use rw mount for read-write home directories
-                self.mounts.push(MountPoint::rw(&dir_path, &dir_path));
-            }
+        for dir_path in expand_path_rules(&self.filesystem_spec.rw_home_dirs, home) {
+            // Use rw mount for read-write home directories
+            self.mounts.push(MountPoint::rw(&dir_path, &dir_path));
+        }
+        Ok(())
+    }
+
+    /// Mount a throwaway copy-on-write overlay (see `MountMode::TmpOverlay`)
+    /// over each configured home directory a tool needs to scribble in
+    /// (e.g. a `~/.cache`) without those writes persisting or ever touching
+    /// the host, unlike `mount_rw_home_dirs`'s direct bind.
+    fn mount_tmp_overlay_home_dirs(&mut self, home: &Path) -> Result<()> {
+        for dir_path in expand_path_rules(&self.filesystem_spec.tmp_overlay_home_dirs, home) {
+            self.mounts.push(MountPoint::tmp_overlay(&dir_path));
         }
         Ok(())
     }
 
     fn mount_home_files(&mut self, home: &Path) -> Result<()> {
         // Mount read-only files in home directory
-        for file_name in &self.filesystem_spec.ro_home_files {
-            let file_path = home.join(file_name);
-            if file_path.exists() {
-                self.mounts.push(MountPoint::ro_try(&file_path, &file_path));
-            }
+        for file_path in expand_path_rules(&self.filesystem_spec.ro_home_files, home) {
+            self.mounts.push(MountPoint::ro_try(&file_path, &file_path));
         }
 
         // Mount read-write files in home directory
-        for file_name in &self.filesystem_spec.rw_home_files {
-            let file_path = home.join(file_name);
-            if file_path.exists() {
-                self.mounts.push(MountPoint::rw(&file_path, &file_path));
-            }
+        for file_path in expand_path_rules(&self.filesystem_spec.rw_home_files, home) {
+            self.mounts.push(MountPoint::rw(&file_path, &file_path));
         }
         Ok(())
     }
 
-    fn mount_config_paths(&mut self) -> Result<()> {
-        // Mount read-only paths from config
-        for path_str in &self.filesystem_spec.ro_paths {
-            let path = PathBuf::from(path_str);
-            if path.exists() {
-                self.mounts.push(MountPoint::ro_try(&path, &path));
+    /// Carve `--deny-read`/`--deny-write` exceptions back out of the
+    /// whole-home rw bind `HomeAccessMode::Full` just pushed. Mount order
+    /// decides the winner (see `mount_minimal_etc`'s `/etc` remount), so
+    /// these are appended immediately after that bind: a read-denied path
+    /// gets hidden behind an empty tmpfs, a write-denied path is
+    /// remounted read-only.
+    fn apply_full_home_denials(&mut self, home: &Path) {
+        let read_denied = self.config.permissions.denied_paths(AccessMode::Read);
+        for path in &read_denied {
+            if path.starts_with(home) {
+                self.mounts.push(MountPoint::tmpfs(path));
             }
         }
 
-        // Mount read-write paths from config
-        for path_str in &self.filesystem_spec.rw_paths {
-            let path = PathBuf::from(path_str);
-            if path.exists() {
+        for path in self.config.permissions.denied_paths(AccessMode::Write) {
+            if path.starts_with(home) && !read_denied.contains(&path) {
+                self.mounts.push(MountPoint::remount_ro(&path));
+            }
+        }
+    }
+
+    /// Mount the concrete paths resolved from `--allow-read`/`--allow-write`
+    /// (see `crate::permissions`). Paths already covered by a whole-home rw
+    /// bind (`HomeAccessMode::Full`) are skipped rather than re-bound
+    /// individually.
+    fn mount_granular_permissions(&mut self) {
+        let home = env::var("HOME").ok().map(PathBuf::from);
+        let covered_by_full_home = |path: &Path| {
+            self.config.home_access == HomeAccessMode::Full
+                && home.as_ref().is_some_and(|h| path.starts_with(h))
+        };
+
+        for path in self.config.permissions.allowed_paths(AccessMode::Read) {
+            if !covered_by_full_home(&path) {
+                self.mounts.push(MountPoint::ro(&path, &path));
+            }
+        }
+
+        for path in self.config.permissions.allowed_paths(AccessMode::Write) {
+            if !covered_by_full_home(&path) {
                 self.mounts.push(MountPoint::rw(&path, &path));
             }
         }
+    }
+
+    fn mount_config_paths(&mut self) -> Result<()> {
+        // Mount read-only paths from config (globs resolve relative to the
+        // target dir unless the pattern itself is absolute)
+        for path in expand_path_rules(&self.filesystem_spec.ro_paths, &self.config.target_dir) {
+            self.mounts.push(MountPoint::ro_try(&path, &path));
+        }
+
+        // Mount read-write paths from config
+        for path in expand_path_rules(&self.filesystem_spec.rw_paths, &self.config.target_dir) {
+            self.mounts.push(MountPoint::rw(&path, &path));
+        }
         Ok(())
     }
 
     fn setup_environment(&mut self) -> Result<()> {
         let home = env::var("HOME").map_err(|_| SandboxError::EnvVarNotFound("HOME".to_string()))?;
-        let user = env::var("USER").unwrap_or_else(|_| "user".to_string());
         let path_env = env::var("PATH").unwrap_or_else(|_| "/usr/bin:/bin:/usr/sbin:/sbin".to_string());
         let term_env = env::var("TERM").unwrap_or_else(|_| "xterm".to_string());
 
+        // Under `UserMode::Mapped { uid: 0, .. }` the guest's own uid is 0,
+        // so report it as "root" rather than leaking the caller's real host
+        // username for an identity the guest no longer has.
+        let user = match self.config.user_mode {
+            crate::config::UserMode::Mapped { uid: 0, .. } => "root".to_string(),
+            _ => env::var("USER").unwrap_or_else(|_| "user".to_string()),
+        };
+
         self.env_builder
             .set("HOME", home)
             .set("PWD", self.config.target_dir.display().to_string())
@@ -367,16 +495,39 @@ impl SandboxBuilder {
             .arg("--unshare-pid")
             .arg("--unshare-ipc");
 
+        // User namespace: remap the guest's uid/gid if configured, and pin
+        // it shut afterward so the guest can't create a further-nested
+        // namespace of its own (see `UserMode`)
+        if let UserMode::Mapped { uid, gid } = self.config.user_mode {
+            cmd.arg("--unshare-user")
+                .arg("--uid")
+                .arg(uid.to_string())
+                .arg("--gid")
+                .arg(gid.to_string())
+                .arg("--disable-userns");
+        }
+
         // Network namespace
         match self.config.network_mode {
-            NetworkMode::Enabled => 
+            NetworkMode::Enabled =>
                 cmd.arg("--share-net"),
-            NetworkMode::Disabled | NetworkMode::Filtered { .. } => 
+            NetworkMode::Disabled | NetworkMode::Filtered { .. } =>
                 cmd.arg("--unshare-net"),
         };
 
-        // Add all mounts
+        // Syscall filtering: compile the resolved profile to classic-BPF and
+        // hand it to bwrap via a memfd (see `crate::seccomp`). Fails closed.
+        crate::seccomp::install(&mut cmd, &self.config.seccomp)?;
+
+        // Add all mounts. `BindData` mounts additionally need their contents
+        // handed to bwrap across `exec`, the same memfd + `pre_exec` `dup2`
+        // mechanism `crate::seccomp` uses for the syscall filter program.
         for mount in &self.mounts {
+            if let MountMode::BindData { contents, target_fd, .. } = &mount.mode {
+                let memfd = crate::memfd::write_to_memfd("bw-bind-data", contents)
+                    .map_err(SandboxError::BindDataSetup)?;
+                crate::memfd::pre_exec_dup2(&mut cmd, memfd, *target_fd);
+            }
             cmd.args(mount.to_args());
         }
 
@@ -437,6 +588,12 @@ impl SandboxBuilder {
                     HomeAccessMode::Full => "full (unsafe)",
                 }
             );
+            match self.config.user_mode {
+                UserMode::Host => tracing::info!("User namespace: host (real uid/gid)"),
+                UserMode::Mapped { uid, gid } => {
+                    tracing::info!("User namespace: mapped to uid={} gid={}", uid, gid)
+                }
+            }
             if self.config.shell {
                 tracing::info!("Mode: Interactive shell");
             }
@@ -451,6 +608,8 @@ impl SandboxBuilder {
 pub struct Sandbox {
     command: Command,
     tmp_export_dir: Option<PathBuf>,
+    overlay_upper_dir: Option<PathBuf>,
+    target_dir: PathBuf,
 }
 
 impl Sandbox {
@@ -467,6 +626,55 @@ impl Sandbox {
             .spawn()
             .map_err(SandboxError::BwrapExecution)
     }
+
+    /// The overlay's writable upper layer, if this sandbox ran with
+    /// `CommitMode::Overlay`; `None` under `CommitMode::Direct`, where edits
+    /// already landed on `target_dir` directly. The directory (and anything
+    /// in it) is removed when `Sandbox` drops unless copied out first via
+    /// this path or [`Sandbox::commit_overlay`].
+    pub fn overlay_upper_dir(&self) -> Option<&Path> {
+        self.overlay_upper_dir.as_deref()
+    }
+
+    /// Copy every change recorded in the overlay's upper layer back onto
+    /// `target_dir`, a no-op under `CommitMode::Direct`. This only adds and
+    /// overwrites files; overlayfs represents a deletion inside the upper
+    /// layer as a whiteout character-device marker, which is skipped here
+    /// rather than translated into a removal on `target_dir` — deletions
+    /// made inside the sandbox are not propagated by this method.
+    pub fn commit_overlay(&self) -> Result<()> {
+        let Some(upper_dir) = &self.overlay_upper_dir else {
+            return Ok(());
+        };
+        copy_overlay_upper(upper_dir, &self.target_dir)
+    }
+}
+
+/// Recursively copy `upper` (an overlayfs upper layer) onto `dest`, skipping
+/// whiteout markers (character-device files overlayfs uses to record
+/// deletions) since there is no equivalent "remove this path" operation to
+/// apply to `dest` from a marker alone.
+fn copy_overlay_upper(upper: &Path, dest: &Path) -> Result<()> {
+    for entry in fs::read_dir(upper).map_err(SandboxError::TmpDirCreation)? {
+        let entry = entry.map_err(SandboxError::TmpDirCreation)?;
+        let file_type = entry.file_type().map_err(SandboxError::TmpDirCreation)?;
+        let dest_path = dest.join(entry.file_name());
+
+        if file_type.is_char_device() {
+            continue;
+        } else if file_type.is_symlink() {
+            let link_target = fs::read_link(entry.path()).map_err(SandboxError::TmpDirCreation)?;
+            let _ = fs::remove_file(&dest_path);
+            std::os::unix::fs::symlink(link_target, &dest_path)
+                .map_err(SandboxError::TmpDirCreation)?;
+        } else if file_type.is_dir() {
+            fs::create_dir_all(&dest_path).map_err(SandboxError::TmpDirCreation)?;
+            copy_overlay_upper(&entry.path(), &dest_path)?;
+        } else {
+            fs::copy(entry.path(), &dest_path).map_err(SandboxError::TmpDirCreation)?;
+        }
+    }
+    Ok(())
 }
 
 impl Drop for Sandbox {
@@ -1,4 +1,9 @@
 //! Configuration file loading and merging
+//!
+//! `Config` here only covers the `[network]` table of the shared bw-claude
+//! config file (see `bwrap_core::config::schema::Config`) — bw-proxy is
+//! handed the same file via `--config` but only ever needs its network
+//! groups and policies, not the filesystem/seccomp/tool sections.
 
 use super::schema::Config;
 use crate::error::{ProxyError, Result};
@@ -52,7 +57,7 @@ impl ConfigLoader {
 
     /// Merge user config on top of built-in config
     /// User config takes precedence: groups and policies are extended,
-    /// tool-specific settings override built-in
+    /// a user entry with the same name overriding the built-in one
     pub fn merge_configs(builtin: Config, user: Config) -> Config {
         let mut merged = builtin;
 
@@ -66,16 +71,11 @@ impl ConfigLoader {
             merged.network.policies.insert(name, policy);
         }
 
-        // Override common config with user settings
-        merged.common = user.common;
-
-        // Override tool configs if user specified them
-        if user.claude.is_some() {
-            merged.claude = user.claude;
-        }
-        if user.gemini.is_some() {
-            merged.gemini = user.gemini;
+        // Merge remote feeds: user feeds override/extend built-in
+        for (name, feed) in user.network.feeds {
+            merged.network.feeds.insert(name, feed);
         }
+        merged.network.block_doh = user.network.block_doh;
 
         merged
     }
@@ -134,47 +134,57 @@ impl ConfigLoader {
     }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            common: Default::default(),
-            network: Default::default(),
-            claude: None,
-            gemini: None,
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::schema::Policy;
 
     #[test]
     fn test_default_config() {
         let config = Config::default();
-        assert_eq!(config.common.config_version, "1.0");
-        assert!(!config.common.verbose);
+        assert!(config.network.groups.is_empty());
+        assert!(config.network.policies.is_empty());
     }
 
     #[test]
     fn test_parse_toml() {
         let toml_str = r#"
-[common]
-config_version = "1.0"
-verbose = true
-
-[common.proxy]
-default_mode = "open"
-
-[network]
-
-[[network.host_groups]]
-name = "test"
+[network.groups.test]
 description = "Test group"
-domains = ["*.example.com"]
+hosts = ["*.example.com"]
+
+[network.policies.default]
+description = "Deny everything not explicitly allowed"
+default = "deny"
+allow_groups = ["test"]
 "#;
 
-        let result: std::result::Result<Config, _> = toml::from_str(toml_str);
-        assert!(result.is_ok());
+        let config: Config = toml::from_str(toml_str).expect("valid config");
+        assert!(config.network.groups.contains_key("test"));
+        assert!(config.network.policies.contains_key("default"));
+    }
+
+    #[test]
+    fn test_merge_configs_prefers_user_policy_over_builtin() {
+        let mut builtin = Config::default();
+        builtin.network.policies.insert(
+            "default".to_string(),
+            Policy {
+                description: "builtin".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let mut user = Config::default();
+        user.network.policies.insert(
+            "default".to_string(),
+            Policy {
+                description: "user".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let merged = ConfigLoader::merge_configs(builtin, user);
+        assert_eq!(merged.network.policies["default"].description, "user");
     }
 }
@@ -3,7 +3,10 @@
 //! This module only handles network-specific configuration types.
 //! The full application configuration system is in bwrap-core.
 
+pub mod loader;
 pub mod schema;
+pub mod stringlist;
 pub mod validator;
 
-pub use schema::{HostGroup, NetworkConfig, NetworkMode, DefaultMode};
+pub use loader::ConfigLoader;
+pub use schema::{Config, FeedConfig, FeedMode, HostGroup, NetworkConfig, NetworkMode, Policy, DefaultMode};
@@ -5,26 +5,138 @@
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
-/// Network configuration with host groups
+/// Top-level config file bw-proxy accepts via `--config`. This is the same
+/// file bw-claude/bw-gemini load through `bwrap_core::config::schema::Config`
+/// — bw-proxy only cares about its `[network]` table.
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default)]
+    pub network: NetworkConfig,
+}
+
+/// Network configuration with host groups
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NetworkConfig {
     #[serde(default)]
     pub groups: IndexMap<String, HostGroup>,
+    /// Named policies selectable via `--mode restrictive:<name>`, resolved
+    /// against `groups` by `PolicyEngine::from_network_policy`
+    #[serde(default)]
+    pub policies: IndexMap<String, Policy>,
+    /// Remote allow/deny list feeds, refreshed on an interval and merged
+    /// into synthetic groups (see `crate::filter::feed`)
+    #[serde(default)]
+    pub feeds: IndexMap<String, FeedConfig>,
+    /// Deny known public DNS-over-HTTPS providers and special-case the
+    /// Mozilla DoH canary domain so clients can't bypass name-based
+    /// filtering by switching to encrypted DNS (see `crate::filter::antidoh`)
+    #[serde(default = "default_true")]
+    pub block_doh: bool,
 }
 
-/// A named group of hosts and IP ranges
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            groups: IndexMap::new(),
+            policies: IndexMap::new(),
+            feeds: IndexMap::new(),
+            block_doh: true,
+        }
+    }
+}
+
+/// A named policy selectable via `--mode restrictive:<name>`: the allow/deny
+/// host groups and default behavior to resolve into a `PolicyEngine`
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct HostGroup {
+pub struct Policy {
     #[serde(default)]
     pub description: String,
-    /// Hosts to allow/include
+    /// Default behavior when no allow/deny rule matches
+    #[serde(default = "default_policy_mode")]
+    pub default: DefaultMode,
+    /// Groups to allow. Same `StringList` coercion as `HostGroup::hosts`.
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
+    pub allow_groups: Vec<String>,
+    /// Groups to deny, taking precedence over `allow_groups` on a
+    /// more-specific match (see `PolicyEngine`'s more-specific-wins logic).
+    /// Same `StringList` coercion as `HostGroup::hosts`.
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
+    pub deny_groups: Vec<String>,
+}
+
+fn default_policy_mode() -> DefaultMode {
+    DefaultMode::Deny
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            description: String::new(),
+            default: default_policy_mode(),
+            allow_groups: vec![],
+            deny_groups: vec![],
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A remote allow/deny list, fetched over HTTP and refreshed on an interval
+///
+/// The fetched body is parsed as hostname-per-line or CIDR-per-line text;
+/// blank lines and `#` comments are ignored. See `crate::filter::feed`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FeedConfig {
+    /// URL to fetch the list from
+    pub url: String,
+    /// Whether fetched entries feed the allow or the deny matcher
+    pub mode: FeedMode,
+    /// How often to refetch the list, in seconds
+    #[serde(default = "default_feed_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+}
+
+fn default_feed_refresh_interval_secs() -> u64 {
+    3600
+}
+
+/// Which matcher a feed's entries should be merged into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedMode {
+    Allow,
+    Deny,
+}
+
+/// A named group of hosts and IP ranges
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct HostGroup {
     #[serde(default)]
+    pub description: String,
+    /// Hosts to allow/include. Accepts either a TOML array or a single
+    /// whitespace-separated string (see `super::stringlist`). Each entry is
+    /// either a plain literal/glob hostname, or a `cfg()`-style match
+    /// expression like `any(suffix = "github.com", port = "443")` (see
+    /// `crate::filter::expr`).
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
     pub hosts: Vec<String>,
-    /// Hosts to explicitly deny (override allow rules)
-    #[serde(default)]
+    /// Hosts to explicitly deny (override allow rules). Same `StringList`
+    /// coercion and match-expression support as `hosts`.
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
     pub hosts_deny: Vec<String>,
-    /// References to other groups (for composition)
-    #[serde(default)]
+    /// IPv4 CIDR ranges to allow/include (e.g. learned addresses, aggregated
+    /// into minimal covering prefixes by `LearningRecorder`). Same
+    /// `StringList` coercion as `hosts`.
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
+    pub ipv4_ranges: Vec<String>,
+    /// IPv6 CIDR ranges to allow/include. Same `StringList` coercion as `hosts`.
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
+    pub ipv6_ranges: Vec<String>,
+    /// References to other groups (for composition). Same `StringList`
+    /// coercion as `hosts`.
+    #[serde(default, deserialize_with = "super::stringlist::deserialize_string_list")]
     pub groups: Vec<String>,
 }
 
@@ -0,0 +1,63 @@
+//! `Vec<String>` fields that also accept a single whitespace-separated string
+//!
+//! Modeled on Cargo's `StringList` config coercion: a hand-written TOML file
+//! shouldn't have to remember whether `hosts = "a.com b.com"` or
+//! `hosts = ["a.com", "b.com"]` is the "right" form for a given field, and an
+//! environment-variable override (see `bwrap_core::config::env_overrides`)
+//! without a comma produces a single string rather than an array. `deserialize_string_list` accepts
+//! either: a TOML array deserializes normally, and a bare string is split on
+//! whitespace into one entry per word.
+
+use serde::{Deserialize, Deserializer};
+
+/// Use as `#[serde(default, deserialize_with = "deserialize_string_list")]`
+/// on a `Vec<String>` field that should also accept a single
+/// whitespace-separated string in place of an array.
+pub fn deserialize_string_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        List(Vec<String>),
+        String(String),
+    }
+
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::List(list) => list,
+        StringOrList::String(s) => s.split_whitespace().map(str::to_string).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        #[serde(default, deserialize_with = "deserialize_string_list")]
+        hosts: Vec<String>,
+    }
+
+    #[test]
+    fn test_accepts_toml_array() {
+        let w: Wrapper = toml::from_str(r#"hosts = ["a.com", "b.com"]"#).unwrap();
+        assert_eq!(w.hosts, vec!["a.com".to_string(), "b.com".to_string()]);
+    }
+
+    #[test]
+    fn test_accepts_whitespace_separated_string() {
+        let w: Wrapper = toml::from_str(r#"hosts = "a.com b.com  c.com""#).unwrap();
+        assert_eq!(
+            w.hosts,
+            vec!["a.com".to_string(), "b.com".to_string(), "c.com".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_missing_field_defaults_empty() {
+        let w: Wrapper = toml::from_str("").unwrap();
+        assert!(w.hosts.is_empty());
+    }
+}
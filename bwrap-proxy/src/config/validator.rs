@@ -78,26 +78,46 @@ impl ConfigValidator {
         Ok(())
     }
 
-    /// Validate wildcard patterns
+    /// Validate wildcard patterns and `cfg()`-style match expressions
     fn validate_patterns(network: &NetworkConfig) -> Result<()> {
         for (group_name, group) in &network.groups {
-            for pattern in &group.hosts {
-                // Basic validation: no double wildcards
-                if pattern.contains("**") {
-                    return Err(ValidationError::InvalidPattern {
-                        pattern: format!("{} in group {}", pattern, group_name),
-                    }
-                    .into());
-                }
+            for pattern in group.hosts.iter().chain(group.hosts_deny.iter()) {
+                Self::validate_pattern(pattern, group_name)?;
+            }
+        }
 
-                // Check for invalid characters
-                if pattern.contains('\0') || pattern.contains('\n') {
-                    return Err(ValidationError::InvalidPattern {
-                        pattern: format!("{} in group {}", pattern, group_name),
-                    }
-                    .into());
+        Ok(())
+    }
+
+    /// Validate a single `hosts`/`hosts_deny` entry: an expression-form entry
+    /// (see `crate::filter::expr::is_expression`) is parsed in full, so a
+    /// syntax error or unknown predicate key is caught at load time rather
+    /// than silently never matching; a plain literal/glob entry keeps the
+    /// existing lightweight checks.
+    fn validate_pattern(pattern: &str, group_name: &str) -> Result<()> {
+        if crate::filter::expr::is_expression(pattern) {
+            return crate::filter::expr::Expr::parse(pattern).map(|_| ()).map_err(|e| {
+                ValidationError::InvalidPattern {
+                    pattern: format!("{} in group {}: {}", pattern, group_name, e),
                 }
+                .into()
+            });
+        }
+
+        // Basic validation: no double wildcards
+        if pattern.contains("**") {
+            return Err(ValidationError::InvalidPattern {
+                pattern: format!("{} in group {}", pattern, group_name),
+            }
+            .into());
+        }
+
+        // Check for invalid characters
+        if pattern.contains('\0') || pattern.contains('\n') {
+            return Err(ValidationError::InvalidPattern {
+                pattern: format!("{} in group {}", pattern, group_name),
             }
+            .into());
         }
 
         Ok(())
@@ -119,6 +139,8 @@ mod tests {
                 description: "A".to_string(),
                 hosts: vec![],
                 hosts_deny: vec![],
+                ipv4_ranges: vec![],
+                ipv6_ranges: vec![],
                 groups: vec!["b".to_string()],
             },
         );
@@ -129,6 +151,8 @@ mod tests {
                 description: "B".to_string(),
                 hosts: vec![],
                 hosts_deny: vec![],
+                ipv4_ranges: vec![],
+                ipv6_ranges: vec![],
                 groups: vec![],
             },
         );
@@ -146,6 +170,8 @@ mod tests {
                 description: "A".to_string(),
                 hosts: vec![],
                 hosts_deny: vec![],
+                ipv4_ranges: vec![],
+                ipv6_ranges: vec![],
                 groups: vec!["b".to_string()],
             },
         );
@@ -156,6 +182,8 @@ mod tests {
                 description: "B".to_string(),
                 hosts: vec![],
                 hosts_deny: vec![],
+                ipv4_ranges: vec![],
+                ipv6_ranges: vec![],
                 groups: vec!["a".to_string()], // Cycle!
             },
         );
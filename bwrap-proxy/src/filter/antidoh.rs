@@ -0,0 +1,123 @@
+//! Built-in anti-DNS-over-HTTPS policy layer
+//!
+//! A sandboxed agent can bypass this proxy's name-based filtering entirely
+//! by speaking DNS-over-HTTPS to a hardcoded resolver: the proxy would only
+//! ever see the IP of the DoH endpoint, not the names being looked up. This
+//! module ships a maintained group of known public DoH provider
+//! hostnames/IPs that's merged into the deny matcher by default (see
+//! `NetworkConfig::block_doh`), and special-cases the Mozilla DoH "canary"
+//! domain so a client probing for it is told filtering is active rather
+//! than being allowed to discover otherwise.
+
+use crate::config::schema::{HostGroup, NetworkConfig};
+
+/// Synthetic group name the built-in anti-DoH rules are merged into, for
+/// appending to a policy's `deny_groups` (see `crate::filter::feed` for the
+/// equivalent pattern used by remote feeds).
+pub const ANTI_DOH_GROUP_NAME: &str = "__builtin:anti-doh";
+
+/// Mozilla's DoH canary domain: browsers query this hostname before
+/// enabling encrypted DNS and expect it to fail to resolve (or be
+/// deliberately blocked) as a signal that the network enforces its own DNS
+/// policy. Denying it here tells probing clients the same thing.
+pub const DOH_CANARY_DOMAIN: &str = "use-application-dns.net";
+
+/// Hostnames of known public DoH providers
+const KNOWN_DOH_HOSTS: &[&str] = &[
+    "cloudflare-dns.com",
+    "mozilla.cloudflare-dns.com",
+    "dns.google",
+    "dns.google.com",
+    "doh.opendns.com",
+    "doh.familyshield.opendns.com",
+    "dns.quad9.net",
+    "doh.quad9.net",
+    "doh.cleanbrowsing.org",
+    "doh.dns.sb",
+    "dns.adguard.com",
+    "dns-family.adguard.com",
+    "doh.libredns.gr",
+    "dns.nextdns.io",
+];
+
+/// IPv4 addresses of known public DoH providers (CIDR notation, /32 each)
+const KNOWN_DOH_IPV4: &[&str] = &[
+    "1.1.1.1/32",
+    "1.0.0.1/32",
+    "8.8.8.8/32",
+    "8.8.4.4/32",
+    "9.9.9.9/32",
+    "149.112.112.112/32",
+    "208.67.222.222/32",
+    "208.67.220.220/32",
+    "94.140.14.14/32",
+    "94.140.15.15/32",
+];
+
+/// The built-in group of known DoH provider hostnames/IPs plus the DoH
+/// canary domain, all in `hosts_deny`/`ipv4_ranges` so it can be appended to
+/// a policy's `deny_groups` like any other group.
+pub fn builtin_anti_doh_group() -> HostGroup {
+    let mut hosts_deny: Vec<String> = KNOWN_DOH_HOSTS.iter().map(|s| s.to_string()).collect();
+    hosts_deny.push(DOH_CANARY_DOMAIN.to_string());
+
+    HostGroup {
+        description: "Built-in: known public DoH providers + DoH canary domain".to_string(),
+        hosts: Vec::new(),
+        hosts_deny,
+        ipv4_ranges: KNOWN_DOH_IPV4.iter().map(|s| s.to_string()).collect(),
+        ipv6_ranges: Vec::new(),
+        groups: Vec::new(),
+    }
+}
+
+/// Return an augmented copy of `network_config` with the built-in anti-DoH
+/// group inserted under `ANTI_DOH_GROUP_NAME`, if `block_doh` is enabled.
+pub fn augment_with_anti_doh(network_config: &NetworkConfig) -> NetworkConfig {
+    let mut augmented = network_config.clone();
+    if network_config.block_doh {
+        augmented.groups.insert(ANTI_DOH_GROUP_NAME.to_string(), builtin_anti_doh_group());
+    }
+    augmented
+}
+
+/// The built-in anti-DoH group's name, for appending to a policy's
+/// `deny_groups`, if `block_doh` is enabled.
+pub fn anti_doh_group_name(network_config: &NetworkConfig) -> Option<String> {
+    network_config.block_doh.then(|| ANTI_DOH_GROUP_NAME.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_group_denies_canary_domain() {
+        let group = builtin_anti_doh_group();
+        assert!(group.hosts_deny.contains(&DOH_CANARY_DOMAIN.to_string()));
+    }
+
+    #[test]
+    fn test_builtin_group_denies_known_providers() {
+        let group = builtin_anti_doh_group();
+        assert!(group.hosts_deny.contains(&"dns.google".to_string()));
+        assert!(group.ipv4_ranges.contains(&"1.1.1.1/32".to_string()));
+    }
+
+    #[test]
+    fn test_augment_enabled_by_default() {
+        let config = NetworkConfig::default();
+        let augmented = augment_with_anti_doh(&config);
+        assert!(augmented.groups.contains_key(ANTI_DOH_GROUP_NAME));
+        assert_eq!(anti_doh_group_name(&config), Some(ANTI_DOH_GROUP_NAME.to_string()));
+    }
+
+    #[test]
+    fn test_augment_disabled() {
+        let mut config = NetworkConfig::default();
+        config.block_doh = false;
+        let augmented = augment_with_anti_doh(&config);
+        assert!(!augmented.groups.contains_key(ANTI_DOH_GROUP_NAME));
+        assert_eq!(anti_doh_group_name(&config), None);
+    }
+}
@@ -0,0 +1,146 @@
+//! CIDR aggregation of individual addresses into minimal covering prefixes
+//!
+//! Used by `LearningRecorder` to collapse a session's observed IPv4/IPv6
+//! addresses (recorded as full-length prefixes) into a compact set of CIDR
+//! ranges instead of emitting one entry per address.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Default fraction of a candidate prefix's address span that must be made
+/// up of real recorded addresses before it is emitted as a single summary
+/// prefix rather than split into its two (recursively aggregated) halves.
+pub const DEFAULT_DENSITY_THRESHOLD: f64 = 0.5;
+
+/// Aggregate a set of IPv4 addresses into minimal covering CIDR prefixes.
+pub fn aggregate_ipv4(addrs: &[Ipv4Addr], density_threshold: f64) -> Vec<String> {
+    let bits: Vec<u128> = addrs.iter().map(|a| u32::from(*a) as u128).collect();
+    aggregate_bits(bits, 32, density_threshold)
+        .into_iter()
+        .map(|(value, prefix_len)| format!("{}/{}", Ipv4Addr::from(value as u32), prefix_len))
+        .collect()
+}
+
+/// Aggregate a set of IPv6 addresses into minimal covering CIDR prefixes.
+pub fn aggregate_ipv6(addrs: &[Ipv6Addr], density_threshold: f64) -> Vec<String> {
+    let bits: Vec<u128> = addrs.iter().map(|a| u128::from(*a)).collect();
+    aggregate_bits(bits, 128, density_threshold)
+        .into_iter()
+        .map(|(value, prefix_len)| format!("{}/{}", Ipv6Addr::from(value), prefix_len))
+        .collect()
+}
+
+/// Sort and dedupe `addrs` (each a full `width`-bit address) and aggregate
+/// them starting from the widest possible prefix (`/0`).
+fn aggregate_bits(mut addrs: Vec<u128>, width: u8, density_threshold: f64) -> Vec<(u128, u8)> {
+    addrs.sort_unstable();
+    addrs.dedup();
+    aggregate_prefix(&addrs, 0, width, density_threshold)
+}
+
+/// Recursively aggregate `addrs` (already sorted, all sharing the implicit
+/// `prefix_len`-bit prefix of their first element) into `(prefix_value,
+/// prefix_len)` pairs.
+///
+/// At each candidate prefix we compare the fraction of its address span that
+/// is made up of real recorded addresses against `density_threshold`. Once
+/// that fraction is met the whole span is emitted as a single prefix;
+/// otherwise it is split in half on the next bit and each half is aggregated
+/// independently. A higher threshold yields tighter, more literal output; the
+/// default of 0.5 favors compact summaries over exact address enumeration, so
+/// e.g. a lone address is rounded up to cover its neighboring `/31` (or
+/// `/127`) rather than staying a standalone `/32`.
+fn aggregate_prefix(addrs: &[u128], prefix_len: u8, width: u8, density_threshold: f64) -> Vec<(u128, u8)> {
+    if addrs.is_empty() {
+        return Vec::new();
+    }
+
+    let span_bits = width - prefix_len;
+    let span: u128 = if span_bits >= 128 { u128::MAX } else { 1u128 << span_bits };
+    let prefix_value = addrs[0] & !(span.wrapping_sub(1));
+    let density = addrs.len() as f64 / span as f64;
+
+    if span_bits == 0 || density >= density_threshold {
+        return vec![(prefix_value, prefix_len)];
+    }
+
+    let split_bit = span_bits - 1;
+    let split_point = addrs.partition_point(|addr| (addr >> split_bit) & 1 == 0);
+    let (low, high) = addrs.split_at(split_point);
+
+    let mut result = aggregate_prefix(low, prefix_len + 1, width, density_threshold);
+    result.extend(aggregate_prefix(high, prefix_len + 1, width, density_threshold));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_address_rounds_up_to_pair_at_default_threshold() {
+        // A lone address meets the default 0.5 density against its /31
+        // neighbor (1 real address / 2-address span), so it gets rounded up.
+        let addrs = vec!["192.168.1.1".parse().unwrap()];
+        let result = aggregate_ipv4(&addrs, DEFAULT_DENSITY_THRESHOLD);
+        assert_eq!(result, vec!["192.168.1.0/31".to_string()]);
+    }
+
+    #[test]
+    fn test_single_address_stays_full_length_above_half_density() {
+        let addrs = vec!["10.0.0.1".parse().unwrap()];
+        let result = aggregate_ipv4(&addrs, 0.9);
+        assert_eq!(result, vec!["10.0.0.1/32".to_string()]);
+    }
+
+    #[test]
+    fn test_full_subnet_merges_at_default_threshold() {
+        // 4 contiguous addresses meet 0.5 density even against the wider
+        // /29 span (4/8 = 0.5), so they summarize as broadly as possible.
+        let addrs: Vec<Ipv4Addr> = (0..4u8).map(|i| format!("10.0.0.{i}").parse().unwrap()).collect();
+        let result = aggregate_ipv4(&addrs, DEFAULT_DENSITY_THRESHOLD);
+        assert_eq!(result, vec!["10.0.0.0/29".to_string()]);
+    }
+
+    #[test]
+    fn test_full_subnet_stays_tight_above_full_density() {
+        // No set of real addresses can exceed density 1.0, so a threshold
+        // above 1.0 disables merging entirely.
+        let addrs: Vec<Ipv4Addr> = (0..4u8).map(|i| format!("10.0.0.{i}").parse().unwrap()).collect();
+        let result = aggregate_ipv4(&addrs, 1.1);
+        assert_eq!(
+            result,
+            vec![
+                "10.0.0.0/32".to_string(),
+                "10.0.0.1/32".to_string(),
+                "10.0.0.2/32".to_string(),
+                "10.0.0.3/32".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raising_threshold_narrows_aggregation() {
+        // 3 of the 4 addresses in 10.0.0.0/30 are present: density 0.75
+        // merges at the default threshold but not once it exceeds 0.75.
+        let addrs: Vec<Ipv4Addr> = vec![
+            "10.0.0.0".parse().unwrap(),
+            "10.0.0.1".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+        ];
+        let merged = aggregate_ipv4(&addrs, DEFAULT_DENSITY_THRESHOLD);
+        assert_eq!(merged, vec!["10.0.0.0/30".to_string()]);
+
+        let not_merged = aggregate_ipv4(&addrs, 0.9);
+        assert_eq!(
+            not_merged,
+            vec!["10.0.0.0/31".to_string(), "10.0.0.2/32".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_ipv6_single_address() {
+        let addrs = vec!["2001:db8::1".parse().unwrap()];
+        let result = aggregate_ipv6(&addrs, DEFAULT_DENSITY_THRESHOLD);
+        assert_eq!(result, vec!["2001:db8::/127".to_string()]);
+    }
+}
@@ -0,0 +1,305 @@
+//! A small `cfg()`-style boolean expression language for `HostGroup` entries
+//!
+//! Plain entries in `HostGroup::hosts`/`hosts_deny` stay exactly what they've
+//! always been: a literal hostname or a glob, matched by `HostMatcher`. This
+//! module adds a second, opt-in form — recognized by [`is_expression`] when an
+//! entry contains `(` or `=` — modeled on cargo-platform's `cfg()` grammar, so
+//! a single entry can combine several conditions:
+//!
+//! ```text
+//! any(suffix = "github.com", host = "api.openai.com")
+//! all(not(suffix = "internal.corp"), port = "443")
+//! ```
+//!
+//! Supported predicate keys are `host` (exact match or glob, same syntax as a
+//! plain `hosts` entry), `suffix` (domain-suffix match), and `port` (exact
+//! numeric match). `ConfigValidator::validate_patterns` parses every
+//! expression-form entry at load time; `PolicyEngine` evaluates the resulting
+//! [`Expr`] tree against each connection's `(host, port)` via [`Expr::eval`].
+
+use std::fmt;
+use wildmatch::WildMatch;
+
+/// True if `entry` should be parsed as an [`Expr`] rather than treated as a
+/// plain literal/glob host pattern. Every valid expression is either a
+/// combinator call (`any(...)`, `all(...)`, `not(...)`) or a bare predicate
+/// (`key = "value"`), so the presence of `(` or `=` is enough to tell the two
+/// forms apart — neither character is legal in a bare hostname or glob.
+pub fn is_expression(entry: &str) -> bool {
+    entry.contains('(') || entry.contains('=')
+}
+
+/// A parsed host-matching expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A single `key = "value"` condition
+    Predicate { key: String, value: String },
+    /// True iff every child is true
+    All(Vec<Expr>),
+    /// True iff at least one child is true
+    Any(Vec<Expr>),
+    /// True iff the child is false
+    Not(Box<Expr>),
+}
+
+/// Why an expression-form `hosts`/`hosts_deny` entry failed to parse
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprParseError(String);
+
+impl fmt::Display for ExprParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ExprParseError {}
+
+impl Expr {
+    /// Parse an expression-form entry, e.g. `any(suffix = "a.com", port = 443)`
+    pub fn parse(input: &str) -> Result<Self, ExprParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let expr = parser.parse_expr()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(ExprParseError(format!("unexpected trailing input in '{input}'")));
+        }
+        Ok(expr)
+    }
+
+    /// Evaluate this expression against a connection's destination
+    pub fn eval(&self, host: &str, port: Option<u16>) -> bool {
+        match self {
+            Expr::Predicate { key, value } => match key.as_str() {
+                "host" => WildMatch::new(value).matches(host),
+                "suffix" => host == value || host.ends_with(&format!(".{value}")),
+                "port" => port.is_some_and(|p| value.parse::<u16>().is_ok_and(|v| v == p)),
+                // Unknown keys never reach here: `validate_patterns` rejects
+                // them at load time, so this arm is unreachable in practice.
+                _ => false,
+            },
+            Expr::All(children) => children.iter().all(|c| c.eval(host, port)),
+            Expr::Any(children) => children.iter().any(|c| c.eval(host, port)),
+            Expr::Not(child) => !child.eval(host, port),
+        }
+    }
+
+    /// How specific this expression is, for comparison against a plain
+    /// host/IP match on the same "most specific wins" scale `PolicyEngine`
+    /// already uses (see `HostMatcher::matches_with_specificity`). A single
+    /// predicate counts as one concrete condition, the same weight as one
+    /// concrete hostname label; `all()` sums its children since satisfying
+    /// every one is strictly more specific than satisfying any single one;
+    /// `any()` takes the least specific child, since that's the weakest
+    /// condition actually required to match; `not()` keeps its child's
+    /// weight, since negating a condition doesn't change how narrowly it
+    /// targets a host.
+    pub fn specificity(&self) -> usize {
+        match self {
+            Expr::Predicate { .. } => 1,
+            Expr::All(children) => children.iter().map(Expr::specificity).sum(),
+            Expr::Any(children) => children.iter().map(Expr::specificity).min().unwrap_or(0),
+            Expr::Not(child) => child.specificity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    Comma,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ExprParseError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Eq);
+                i += 1;
+            }
+            '"' => {
+                let start = i + 1;
+                let mut j = start;
+                while j < chars.len() && chars[j] != '"' {
+                    j += 1;
+                }
+                if j >= chars.len() {
+                    return Err(ExprParseError(format!("unterminated string in '{input}'")));
+                }
+                tokens.push(Token::Str(chars[start..j].iter().collect()));
+                i = j + 1;
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '*' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.' || chars[i] == '-' || chars[i] == '*')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            other => return Err(ExprParseError(format!("unexpected character '{other}' in '{input}'"))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), ExprParseError> {
+        match self.bump() {
+            Some(ref tok) if tok == expected => Ok(()),
+            other => Err(ExprParseError(format!("expected {expected:?}, found {other:?}"))),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ExprParseError> {
+        let name = match self.bump() {
+            Some(Token::Ident(name)) => name,
+            other => return Err(ExprParseError(format!("expected an identifier, found {other:?}"))),
+        };
+
+        match self.peek() {
+            Some(Token::Eq) => {
+                self.bump();
+                let value = match self.bump() {
+                    Some(Token::Str(s)) => s,
+                    other => return Err(ExprParseError(format!("expected a quoted string, found {other:?}"))),
+                };
+                validate_key(&name)?;
+                Ok(Expr::Predicate { key: name, value })
+            }
+            Some(Token::LParen) => {
+                self.bump();
+                let children = self.parse_expr_list()?;
+                self.expect(&Token::RParen)?;
+                match name.as_str() {
+                    "all" => Ok(Expr::All(children)),
+                    "any" => Ok(Expr::Any(children)),
+                    "not" => {
+                        let mut children = children;
+                        if children.len() != 1 {
+                            return Err(ExprParseError("not() takes exactly one argument".to_string()));
+                        }
+                        Ok(Expr::Not(Box::new(children.remove(0))))
+                    }
+                    other => Err(ExprParseError(format!("unknown combinator '{other}'"))),
+                }
+            }
+            other => Err(ExprParseError(format!("expected '=' or '(' after '{name}', found {other:?}"))),
+        }
+    }
+
+    fn parse_expr_list(&mut self) -> Result<Vec<Expr>, ExprParseError> {
+        let mut children = vec![self.parse_expr()?];
+        while matches!(self.peek(), Some(Token::Comma)) {
+            self.bump();
+            children.push(self.parse_expr()?);
+        }
+        Ok(children)
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), ExprParseError> {
+    match key {
+        "host" | "suffix" | "port" => Ok(()),
+        other => Err(ExprParseError(format!("unknown predicate key '{other}'"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_expression_detects_combinators_and_predicates() {
+        assert!(is_expression("any(suffix = \"github.com\")"));
+        assert!(is_expression("port = \"443\""));
+        assert!(!is_expression("*.example.com"));
+        assert!(!is_expression("example.com"));
+    }
+
+    #[test]
+    fn test_parses_single_predicate() {
+        let expr = Expr::parse("suffix = \"github.com\"").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Predicate {
+                key: "suffix".to_string(),
+                value: "github.com".to_string(),
+            }
+        );
+        assert!(expr.eval("api.github.com", None));
+        assert!(!expr.eval("github.com.evil.net", None));
+    }
+
+    #[test]
+    fn test_parses_any_and_all() {
+        let any = Expr::parse("any(suffix = \"github.com\", host = \"api.openai.com\")").unwrap();
+        assert!(any.eval("raw.github.com", None));
+        assert!(any.eval("api.openai.com", None));
+        assert!(!any.eval("evil.com", None));
+
+        let all = Expr::parse("all(not(suffix = \"internal.corp\"), port = \"443\")").unwrap();
+        assert!(all.eval("example.com", Some(443)));
+        assert!(!all.eval("example.com", Some(8080)));
+        assert!(!all.eval("foo.internal.corp", Some(443)));
+    }
+
+    #[test]
+    fn test_rejects_unknown_key() {
+        assert!(Expr::parse("protocol = \"tcp\"").is_err());
+    }
+
+    #[test]
+    fn test_rejects_syntax_errors() {
+        assert!(Expr::parse("any(suffix = \"github.com\"").is_err());
+        assert!(Expr::parse("suffix \"github.com\"").is_err());
+        assert!(Expr::parse("not(host = \"a\", host = \"b\")").is_err());
+    }
+
+    #[test]
+    fn test_specificity_orders_more_constrained_expressions_higher() {
+        let narrow = Expr::parse("all(suffix = \"github.com\", port = \"443\")").unwrap();
+        let wide = Expr::parse("any(suffix = \"github.com\", port = \"443\")").unwrap();
+        assert!(narrow.specificity() > wide.specificity());
+    }
+}
@@ -0,0 +1,124 @@
+//! Remote allow/deny list feeds
+//!
+//! Lets a policy point at one or more externally-hosted blocklists/allowlists
+//! — plain hostname-per-line or CIDR-per-line text, one entry per line, `#`
+//! comments and blank lines ignored — and periodically refreshes them into
+//! synthetic `HostGroup`s that a policy can reference exactly like any
+//! statically configured group.
+
+use crate::config::schema::{FeedMode, HostGroup, NetworkConfig};
+use crate::error::{ProxyError, Result};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Synthetic group name a feed's entries are merged into.
+pub fn feed_group_name(feed_name: &str) -> String {
+    format!("__feed:{feed_name}")
+}
+
+/// Fetch a feed over HTTP and parse it as hostname-per-line / CIDR-per-line.
+pub async fn fetch_feed(url: &str) -> Result<Vec<String>> {
+    let body = reqwest::get(url)
+        .await
+        .map_err(|e| ProxyError::Network(format!("Failed to fetch feed {url}: {e}")))?
+        .text()
+        .await
+        .map_err(|e| ProxyError::Network(format!("Failed to read feed {url}: {e}")))?;
+
+    Ok(body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Fetch every configured feed and return an augmented copy of `network_config`
+/// with one synthetic `HostGroup` per feed inserted into `groups`.
+///
+/// Entries that already appear as a literal pattern in the feed's target
+/// direction (allow or deny) for some statically configured group are
+/// skipped, so a feed doesn't duplicate a rule an admin already wrote by
+/// hand. A feed that fails to fetch is logged and simply omitted from the
+/// result; the caller keeps whatever engine it already had running.
+pub async fn augment_with_feeds(network_config: &NetworkConfig) -> NetworkConfig {
+    let mut augmented = network_config.clone();
+    if network_config.feeds.is_empty() {
+        return augmented;
+    }
+
+    let existing_allow: HashSet<&str> = network_config
+        .groups
+        .values()
+        .flat_map(|g| g.hosts.iter().map(String::as_str))
+        .collect();
+    let existing_deny: HashSet<&str> = network_config
+        .groups
+        .values()
+        .flat_map(|g| g.hosts_deny.iter().map(String::as_str))
+        .collect();
+
+    for (name, feed) in &network_config.feeds {
+        let entries = match fetch_feed(&feed.url).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("Failed to refresh feed '{name}' ({}): {e}", feed.url);
+                continue;
+            }
+        };
+
+        let existing = match feed.mode {
+            FeedMode::Allow => &existing_allow,
+            FeedMode::Deny => &existing_deny,
+        };
+        let deduped: Vec<String> = entries
+            .into_iter()
+            .filter(|entry| !existing.contains(entry.as_str()))
+            .collect();
+
+        let mut group = HostGroup {
+            description: format!(
+                "Remote {} feed: {}",
+                match feed.mode {
+                    FeedMode::Allow => "allow",
+                    FeedMode::Deny => "deny",
+                },
+                feed.url
+            ),
+            hosts: Vec::new(),
+            hosts_deny: Vec::new(),
+            ipv4_ranges: Vec::new(),
+            ipv6_ranges: Vec::new(),
+            groups: Vec::new(),
+        };
+        match feed.mode {
+            FeedMode::Allow => group.hosts = deduped,
+            FeedMode::Deny => group.hosts_deny = deduped,
+        }
+
+        augmented.groups.insert(feed_group_name(name), group);
+    }
+
+    augmented
+}
+
+/// Names of the synthetic groups `augment_with_feeds` would insert for
+/// feeds configured with the given mode, for appending to a policy's
+/// `allow_groups` / `deny_groups` so the expanded matchers pick them up.
+pub fn feed_group_names(network_config: &NetworkConfig, mode: FeedMode) -> Vec<String> {
+    network_config
+        .feeds
+        .iter()
+        .filter(|(_, feed)| feed.mode == mode)
+        .map(|(name, _)| feed_group_name(name))
+        .collect()
+}
+
+/// Shortest refresh interval among all configured feeds, if any are configured.
+pub fn shortest_refresh_interval(network_config: &NetworkConfig) -> Option<Duration> {
+    network_config
+        .feeds
+        .values()
+        .map(|feed| Duration::from_secs(feed.refresh_interval_secs))
+        .min()
+}
@@ -1,23 +1,53 @@
+use super::cidr::{self, DEFAULT_DENSITY_THRESHOLD};
 use crate::config::schema::HostGroup;
 use crate::error::{ProxyError, Result};
-use chrono::Utc;
-use std::collections::HashSet;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+/// Access metadata tracked per recorded host/IP entry: how many times it was
+/// seen and the first/last time it was seen.
+#[derive(Debug, Clone)]
+struct EntryMeta {
+    hit_count: u64,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+impl EntryMeta {
+    fn new() -> Self {
+        let now = Utc::now();
+        Self {
+            hit_count: 1,
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+    fn touch(&mut self) {
+        self.hit_count += 1;
+        self.last_seen = Utc::now();
+    }
+}
+
 /// Records accessed hosts and IPs during learning mode
 /// Can track both allowed access (--learn) and denied access (--learn-deny)
 #[derive(Clone)]
 pub struct LearningRecorder {
-    // Allowed access recording
-    hosts: Arc<Mutex<HashSet<String>>>,
-    ipv4_ranges: Arc<Mutex<HashSet<String>>>,
-    ipv6_ranges: Arc<Mutex<HashSet<String>>>,
+    // Allowed access recording, keyed by host/address with per-entry access metadata
+    hosts: Arc<Mutex<HashMap<String, EntryMeta>>>,
+    ipv4_ranges: Arc<Mutex<HashMap<String, EntryMeta>>>,
+    ipv6_ranges: Arc<Mutex<HashMap<String, EntryMeta>>>,
     // Denied access recording (for --learn-deny mode)
-    denied_hosts: Arc<Mutex<HashSet<String>>>,
+    denied_hosts: Arc<Mutex<HashMap<String, EntryMeta>>>,
     session_name: String,
+    // If true, `to_host_group` emits recorded addresses verbatim as /32 or
+    // /128 entries instead of collapsing them into covering CIDR prefixes.
+    raw_addresses: bool,
+    density_threshold: f64,
 }
 
 impl LearningRecorder {
@@ -27,48 +57,79 @@ impl LearningRecorder {
         let session_name = format!("learned_session_{}", timestamp);
 
         Self {
-            hosts: Arc::new(Mutex::new(HashSet::new())),
-            ipv4_ranges: Arc::new(Mutex::new(HashSet::new())),
-            ipv6_ranges: Arc::new(Mutex::new(HashSet::new())),
-            denied_hosts: Arc::new(Mutex::new(HashSet::new())),
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+            ipv4_ranges: Arc::new(Mutex::new(HashMap::new())),
+            ipv6_ranges: Arc::new(Mutex::new(HashMap::new())),
+            denied_hosts: Arc::new(Mutex::new(HashMap::new())),
             session_name,
+            raw_addresses: false,
+            density_threshold: DEFAULT_DENSITY_THRESHOLD,
         }
     }
 
     /// Create a recorder with a custom session name
     pub fn with_session_name(name: impl Into<String>) -> Self {
         Self {
-            hosts: Arc::new(Mutex::new(HashSet::new())),
-            ipv4_ranges: Arc::new(Mutex::new(HashSet::new())),
-            ipv6_ranges: Arc::new(Mutex::new(HashSet::new())),
-            denied_hosts: Arc::new(Mutex::new(HashSet::new())),
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+            ipv4_ranges: Arc::new(Mutex::new(HashMap::new())),
+            ipv6_ranges: Arc::new(Mutex::new(HashMap::new())),
+            denied_hosts: Arc::new(Mutex::new(HashMap::new())),
             session_name: name.into(),
+            raw_addresses: false,
+            density_threshold: DEFAULT_DENSITY_THRESHOLD,
         }
     }
 
-    /// Record a host access (skips if already in existing learned file)
+    /// Emit recorded addresses verbatim (one /32 or /128 entry each) instead
+    /// of collapsing them into covering CIDR prefixes, trading compactness
+    /// for exact fidelity to what was actually observed.
+    pub fn with_raw_addresses(mut self) -> Self {
+        self.raw_addresses = true;
+        self
+    }
+
+    /// Set the density threshold used when collapsing recorded addresses
+    /// into CIDR prefixes (see `crate::filter::cidr`). Has no effect in raw
+    /// address mode.
+    pub fn with_density_threshold(mut self, density_threshold: f64) -> Self {
+        self.density_threshold = density_threshold;
+        self
+    }
+
+    /// Record a host access, bumping its hit count and last-seen time if
+    /// already recorded
     pub fn record_host(&self, host: &str) {
         if let Ok(mut hosts) = self.hosts.lock() {
-            hosts.insert(host.to_string());
+            Self::touch_entry(&mut hosts, host);
         }
     }
 
-    /// Record an IP access (skips if already in existing learned file)
+    /// Record an IP access, bumping its hit count and last-seen time if
+    /// already recorded
     pub fn record_ip(&self, ip: IpAddr) {
         match ip {
             IpAddr::V4(addr) => {
                 if let Ok(mut ipv4s) = self.ipv4_ranges.lock() {
-                    ipv4s.insert(addr.to_string());
+                    Self::touch_entry(&mut ipv4s, &addr.to_string());
                 }
             }
             IpAddr::V6(addr) => {
                 if let Ok(mut ipv6s) = self.ipv6_ranges.lock() {
-                    ipv6s.insert(addr.to_string());
+                    Self::touch_entry(&mut ipv6s, &addr.to_string());
                 }
             }
         }
     }
 
+    /// Record a fresh sighting of `key` in `entries`, bumping its hit count
+    /// and last-seen time if it's already there
+    fn touch_entry(entries: &mut HashMap<String, EntryMeta>, key: &str) {
+        entries
+            .entry(key.to_string())
+            .and_modify(EntryMeta::touch)
+            .or_insert_with(EntryMeta::new);
+    }
+
     /// Record a connection (both host and IP if available)
     /// Automatically skips entries already in the learned file
     pub fn record(&self, host: &str, ip: Option<IpAddr>) {
@@ -81,7 +142,7 @@ impl LearningRecorder {
     /// Record a denied host access (for --learn-deny mode)
     pub fn record_denied_host(&self, host: &str) {
         if let Ok(mut hosts) = self.denied_hosts.lock() {
-            hosts.insert(host.to_string());
+            Self::touch_entry(&mut hosts, host);
         }
     }
 
@@ -147,19 +208,29 @@ impl LearningRecorder {
     }
 
     /// Get a snapshot of recorded data as a HostGroup
+    ///
+    /// Unless recording in raw-address mode, `ipv4_ranges`/`ipv6_ranges` are
+    /// collapsed into minimal covering CIDR prefixes (see
+    /// `crate::filter::cidr`) before being returned.
     pub fn to_host_group(&self) -> HostGroup {
         let hosts = self.hosts.lock()
-            .map(|h| h.iter().cloned().collect::<Vec<_>>())
+            .map(|h| h.keys().cloned().collect::<Vec<_>>())
             .unwrap_or_default();
 
         let ipv4_ranges = self.ipv4_ranges.lock()
-            .map(|h| h.iter().cloned().collect::<Vec<_>>())
+            .map(|h| h.keys().cloned().collect::<Vec<_>>())
             .unwrap_or_default();
 
         let ipv6_ranges = self.ipv6_ranges.lock()
-            .map(|h| h.iter().cloned().collect::<Vec<_>>())
+            .map(|h| h.keys().cloned().collect::<Vec<_>>())
             .unwrap_or_default();
 
+        let (ipv4_ranges, ipv6_ranges) = if self.raw_addresses {
+            (ipv4_ranges, ipv6_ranges)
+        } else {
+            (self.aggregate_ipv4(&ipv4_ranges), self.aggregate_ipv6(&ipv6_ranges))
+        };
+
         HostGroup {
             description: self.session_name.clone(),
             hosts,
@@ -170,10 +241,24 @@ impl LearningRecorder {
         }
     }
 
+    /// Parse recorded IPv4 address strings and collapse them into minimal
+    /// covering CIDR prefixes at `self.density_threshold`.
+    fn aggregate_ipv4(&self, raw: &[String]) -> Vec<String> {
+        let addrs: Vec<Ipv4Addr> = raw.iter().filter_map(|s| s.parse().ok()).collect();
+        cidr::aggregate_ipv4(&addrs, self.density_threshold)
+    }
+
+    /// Parse recorded IPv6 address strings and collapse them into minimal
+    /// covering CIDR prefixes at `self.density_threshold`.
+    fn aggregate_ipv6(&self, raw: &[String]) -> Vec<String> {
+        let addrs: Vec<Ipv6Addr> = raw.iter().filter_map(|s| s.parse().ok()).collect();
+        cidr::aggregate_ipv6(&addrs, self.density_threshold)
+    }
+
     /// Get denied hosts as a HostGroup (for --learn-deny mode)
     pub fn to_denied_host_group(&self) -> HostGroup {
         let denied_hosts = self.denied_hosts.lock()
-            .map(|h| h.iter().cloned().collect::<Vec<_>>())
+            .map(|h| h.keys().cloned().collect::<Vec<_>>())
             .unwrap_or_default();
 
         HostGroup {
@@ -287,6 +372,83 @@ impl LearningRecorder {
             ipv6_count: self.ipv6_ranges.lock().map(|h| h.len()).unwrap_or(0),
         }
     }
+
+    /// Drop any recorded host/IP/denied-host entries not seen within `max_age`
+    ///
+    /// Run this before promoting a learning session into a real allow group
+    /// to trim one-off connections that happened once and were never seen
+    /// again, instead of baking them into the generated policy.
+    pub fn prune(&self, max_age: Duration) {
+        let cutoff = Utc::now() - max_age;
+        for entries in [&self.hosts, &self.ipv4_ranges, &self.ipv6_ranges, &self.denied_hosts] {
+            if let Ok(mut entries) = entries.lock() {
+                entries.retain(|_, meta| meta.last_seen >= cutoff);
+            }
+        }
+    }
+
+    /// Snapshot `entries`, sorted by descending hit count (most-contacted
+    /// first, ties broken alphabetically for stable output)
+    fn sorted_entries(entries: &Arc<Mutex<HashMap<String, EntryMeta>>>) -> Vec<(String, EntryMeta)> {
+        let mut entries: Vec<(String, EntryMeta)> = entries.lock()
+            .map(|e| e.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        entries.sort_by(|a, b| b.1.hit_count.cmp(&a.1.hit_count).then_with(|| a.0.cmp(&b.0)));
+        entries
+    }
+
+    /// Save recorded data to a TOML file like `save_to_file`, but with raw
+    /// (unaggregated) addresses sorted by descending hit count and annotated
+    /// with a comment recording how many times each entry was seen and its
+    /// first/last-seen timestamps.
+    ///
+    /// Intended as a review pass before promoting a learning session into a
+    /// real allow group: the stats make it easy to spot and trim noisy
+    /// one-off connections that `save_to_file`'s compact output would hide.
+    pub fn save_with_stats(&self, path: &Path) -> Result<()> {
+        let mut hosts = Self::sorted_entries(&self.hosts);
+        let mut ipv4_ranges = Self::sorted_entries(&self.ipv4_ranges);
+        let mut ipv6_ranges = Self::sorted_entries(&self.ipv6_ranges);
+
+        // Load existing domains from the file and filter them out
+        if path.exists() {
+            let existing = Self::load_existing_domains(path)?;
+
+            hosts.retain(|(v, _)| !existing.contains(v));
+            ipv4_ranges.retain(|(v, _)| !existing.contains(v));
+            ipv6_ranges.retain(|(v, _)| !existing.contains(v));
+        }
+
+        // Check if we have any NEW data to save after deduplication
+        if hosts.is_empty() && ipv4_ranges.is_empty() && ipv6_ranges.is_empty() {
+            return Ok(()); // Nothing new to save
+        }
+
+        // Read existing config or create minimal structure
+        let mut config_content = if path.exists() {
+            fs::read_to_string(path).map_err(ProxyError::from)?
+        } else {
+            // Create minimal config structure
+            String::from("[common]\nlog_level = \"info\"\n\n[network]\n\n")
+        };
+
+        // Generate the new group section (with only new entries)
+        let group_section = format!(
+            "[network.groups.{}]\n{}{}{}\n",
+            self.session_name,
+            format_toml_array_with_stats("hosts", &hosts),
+            format_toml_array_with_stats("ipv4_ranges", &ipv4_ranges),
+            format_toml_array_with_stats("ipv6_ranges", &ipv6_ranges),
+        );
+
+        // Append the new group
+        config_content.push_str(&group_section);
+
+        // Write back to file
+        fs::write(path, config_content).map_err(ProxyError::from)?;
+
+        Ok(())
+    }
 }
 
 impl Default for LearningRecorder {
@@ -323,6 +485,28 @@ fn format_toml_array(key: &str, values: &[String]) -> String {
     result
 }
 
+/// Like `format_toml_array`, but precedes each entry with a comment line
+/// recording its hit count and first/last-seen timestamps
+fn format_toml_array_with_stats(key: &str, entries: &[(String, EntryMeta)]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+
+    let mut result = format!("{} = [\n", key);
+    for (value, meta) in entries {
+        result.push_str(&format!(
+            "  # seen {} time{}, first {}, last {}\n",
+            meta.hit_count,
+            if meta.hit_count == 1 { "" } else { "s" },
+            meta.first_seen.to_rfc3339(),
+            meta.last_seen.to_rfc3339(),
+        ));
+        result.push_str(&format!("  \"{}\",\n", value));
+    }
+    result.push_str("]\n");
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -356,6 +540,28 @@ mod tests {
         assert_eq!(group.ipv6_ranges.len(), 1);
     }
 
+    #[test]
+    fn test_record_ip_aggregates_by_default() {
+        let recorder = LearningRecorder::new();
+        for i in 0..4u8 {
+            recorder.record_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, i)));
+        }
+
+        let group = recorder.to_host_group();
+        assert_eq!(group.ipv4_ranges, vec!["10.0.0.0/29".to_string()]);
+    }
+
+    #[test]
+    fn test_record_ip_raw_addresses_mode() {
+        let recorder = LearningRecorder::new().with_raw_addresses();
+        for i in 0..4u8 {
+            recorder.record_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, i)));
+        }
+
+        let group = recorder.to_host_group();
+        assert_eq!(group.ipv4_ranges.len(), 4);
+    }
+
     #[test]
     fn test_record_combined() {
         let recorder = LearningRecorder::new();
@@ -384,8 +590,59 @@ mod tests {
     }
 
     #[test]
-    fn test_save_to_file() {
+    fn test_repeated_access_bumps_hit_count() {
+        let recorder = LearningRecorder::with_session_name("test_session");
+        recorder.record_host("example.com");
+        recorder.record_host("example.com");
+        recorder.record_host("example.com");
+
+        let entries = LearningRecorder::sorted_entries(&recorder.hosts);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, "example.com");
+        assert_eq!(entries[0].1.hit_count, 3);
+    }
+
+    #[test]
+    fn test_prune_drops_stale_entries() {
         let recorder = LearningRecorder::with_session_name("test_session");
+        recorder.record_host("stale.example.com");
+        recorder.record_host("fresh.example.com");
+
+        // Backdate the stale entry's last-seen time directly, since we can't
+        // fast-forward the clock in a test
+        {
+            let mut hosts = recorder.hosts.lock().unwrap();
+            hosts.get_mut("stale.example.com").unwrap().last_seen = Utc::now() - Duration::days(2);
+        }
+
+        recorder.prune(Duration::days(1));
+
+        let group = recorder.to_host_group();
+        assert_eq!(group.hosts, vec!["fresh.example.com".to_string()]);
+    }
+
+    #[test]
+    fn test_save_with_stats_sorts_by_hit_count_and_annotates_entries() {
+        let recorder = LearningRecorder::with_session_name("test_session");
+        recorder.record_host("rare.example.com");
+        recorder.record_host("popular.example.com");
+        recorder.record_host("popular.example.com");
+        recorder.record_host("popular.example.com");
+
+        let temp_file = NamedTempFile::new().unwrap();
+        recorder.save_with_stats(temp_file.path()).unwrap();
+
+        let content = fs::read_to_string(temp_file.path()).unwrap();
+        let popular_pos = content.find("popular.example.com").unwrap();
+        let rare_pos = content.find("rare.example.com").unwrap();
+        assert!(popular_pos < rare_pos, "more frequently seen host should sort first");
+        assert!(content.contains("seen 3 times"));
+        assert!(content.contains("seen 1 time,"));
+    }
+
+    #[test]
+    fn test_save_to_file() {
+        let recorder = LearningRecorder::with_session_name("test_session").with_raw_addresses();
         recorder.record_host("example.com");
         recorder.record_host("api.example.com");
         recorder.record_ip(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
@@ -1,13 +1,22 @@
 //! Host and IP matching logic
 
 use ipnet::{Ipv4Net, Ipv6Net};
+use std::collections::HashMap;
 use std::net::IpAddr;
 use wildmatch::WildMatch;
 
 /// Matcher for hosts and IP addresses
 #[derive(Debug, Clone)]
 pub struct HostMatcher {
+    /// Patterns a label-based trie can't express — a wildcard anywhere but
+    /// the pattern's leading label (e.g. `test.*.org`), or a `*` fused into
+    /// a literal label (e.g. `api*.example.com`) — walked linearly the same
+    /// way every pattern used to be
     patterns: Vec<WildMatch>,
+    /// Literal and leading-wildcard-only patterns (e.g. `example.com`,
+    /// `*.example.com`), indexed by `trie` for O(host depth) lookup instead
+    /// of a linear scan over every pattern; see `DomainTrie`
+    trie: DomainTrie,
     ipv4_ranges: Vec<Ipv4Net>,
     ipv6_ranges: Vec<Ipv6Net>,
 }
@@ -17,14 +26,22 @@ impl HostMatcher {
     pub fn new() -> Self {
         Self {
             patterns: Vec::new(),
+            trie: DomainTrie::new(),
             ipv4_ranges: Vec::new(),
             ipv6_ranges: Vec::new(),
         }
     }
 
-    /// Add a wildcard pattern for host matching
+    /// Add a wildcard pattern for host matching. Patterns the trie can
+    /// represent (a literal domain, or one with `*` as its whole leading
+    /// label) go there; anything else falls back to linear `WildMatch`
+    /// scanning.
     pub fn add_pattern(&mut self, pattern: &str) {
-        self.patterns.push(WildMatch::new(pattern));
+        if is_trie_eligible(pattern) {
+            self.trie.insert(pattern);
+        } else {
+            self.patterns.push(WildMatch::new(pattern));
+        }
     }
 
     /// Add an IPv4 CIDR range
@@ -39,7 +56,7 @@ impl HostMatcher {
 
     /// Check if a hostname matches any pattern
     pub fn matches_host(&self, host: &str) -> bool {
-        self.patterns.iter().any(|p| p.matches(host))
+        self.trie.matches_with_specificity(host).is_some() || self.patterns.iter().any(|p| p.matches(host))
     }
 
     /// Check if an IP address matches any range
@@ -50,6 +67,28 @@ impl HostMatcher {
         }
     }
 
+    /// Like `matches_ip`, but return the most specific (longest-prefix)
+    /// matching CIDR's prefix length as its specificity, so an IP match is
+    /// comparable on the same scale as `matches_with_specificity`'s
+    /// hostname label counts (see `PolicyEngine::allow`, which picks a
+    /// winner across both pattern kinds together).
+    pub fn matches_ip_with_specificity(&self, ip: IpAddr) -> Option<usize> {
+        match ip {
+            IpAddr::V4(ipv4) => self
+                .ipv4_ranges
+                .iter()
+                .filter(|net| net.contains(&ipv4))
+                .map(|net| net.prefix_len() as usize)
+                .max(),
+            IpAddr::V6(ipv6) => self
+                .ipv6_ranges
+                .iter()
+                .filter(|net| net.contains(&ipv6))
+                .map(|net| net.prefix_len() as usize)
+                .max(),
+        }
+    }
+
     /// Check if either hostname or IP matches
     pub fn matches(&self, host: &str, ip: Option<IpAddr>) -> bool {
         if self.matches_host(host) {
@@ -65,24 +104,41 @@ impl HostMatcher {
 
     /// Check if matcher has any patterns or ranges
     pub fn is_empty(&self) -> bool {
-        self.patterns.is_empty() && self.ipv4_ranges.is_empty() && self.ipv6_ranges.is_empty()
+        self.patterns.is_empty() && self.trie.is_empty() && self.ipv4_ranges.is_empty() && self.ipv6_ranges.is_empty()
+    }
+
+    /// The IPv4 CIDR ranges added so far (e.g. for compiling into a
+    /// kernel-level ruleset — see `crate::filter::policy::ResolvedRanges`)
+    pub fn ipv4_ranges(&self) -> &[Ipv4Net] {
+        &self.ipv4_ranges
     }
 
-    /// Check if host matches with specificity calculation
-    /// Returns Some(specificity) if matched, None if no match
-    /// Specificity = count of non-wildcard domain elements in the matched hostname
+    /// The IPv6 CIDR ranges added so far
+    pub fn ipv6_ranges(&self) -> &[Ipv6Net] {
+        &self.ipv6_ranges
+    }
+
+    /// Check if host matches with specificity calculation.
+    /// Returns Some(specificity) if matched, None if no match.
+    ///
+    /// For trie-backed patterns, specificity is the number of concrete
+    /// (non-wildcard) labels on the winning pattern's matching path, so a
+    /// narrower pattern like `*.api.example.com` outranks a broader one
+    /// like `*.example.com` when both match the same host. Patterns that
+    /// fall back to linear `WildMatch` scanning keep the coarser original
+    /// heuristic (the matched hostname's own label count), since they're
+    /// the rare interior-wildcard case rather than the common path.
     pub fn matches_with_specificity(&self, host: &str) -> Option<usize> {
-        let mut max_specificity = None;
+        let mut best = self.trie.matches_with_specificity(host);
 
-        // Check host patterns
         for pattern in &self.patterns {
             if pattern.matches(host) {
                 let spec = calculate_hostname_specificity(host);
-                max_specificity = Some(max_specificity.unwrap_or(0).max(spec));
+                best = Some(best.map_or(spec, |b| b.max(spec)));
             }
         }
 
-        max_specificity
+        best
     }
 }
 
@@ -99,6 +155,116 @@ impl Default for HostMatcher {
     }
 }
 
+/// True if a label-based trie can represent `pattern` exactly: every label
+/// is either a literal (no `*` in it) or the whole pattern's leading label
+/// is a bare `*`. Anything else — an interior wildcard like `test.*.org`,
+/// or a `*` fused into a literal like `api*.example.com` — can't be
+/// expressed as a path through the trie and must fall back to `WildMatch`.
+fn is_trie_eligible(pattern: &str) -> bool {
+    pattern.split('.').enumerate().all(|(i, label)| {
+        if label == "*" {
+            i == 0
+        } else {
+            !label.contains('*')
+        }
+    })
+}
+
+/// A reverse-DNS-label trie over literal and leading-wildcard domain
+/// patterns (see `is_trie_eligible`), giving `matches_with_specificity`
+/// O(host depth) lookup instead of scanning every pattern linearly — the
+/// bottleneck this exists to fix, since learning-mode allow-lists can grow
+/// into the thousands of domains.
+///
+/// Patterns are inserted by splitting on `.` and walking the labels in
+/// reverse (so `*.example.com` walks `com` → `example` → `*`), mirroring
+/// how DNS names actually nest. A concrete label is an exact-match edge to
+/// a child node; a leading `*` is stored as that node's `wildcard` slot.
+/// Because a trie-eligible pattern only ever has `*` as its final
+/// (left-most, most specific) label, reaching a `wildcard` slot during
+/// lookup always means "the rest of the query's labels, however many are
+/// left, are covered from here" — the same dot-spanning behavior
+/// `WildMatch`'s `*` has, not just a single extra label.
+#[derive(Debug, Clone, Default)]
+struct DomainTrie {
+    root: TrieNode,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TrieNode {
+    children: HashMap<String, TrieNode>,
+    /// Specificity (concrete labels consumed to reach here) of a pattern
+    /// ending in a `*` at this point, if one was inserted
+    wildcard_specificity: Option<usize>,
+    /// Specificity of a pattern that ends exactly at this node (no trailing
+    /// wildcard)
+    terminal_specificity: Option<usize>,
+}
+
+impl DomainTrie {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.root.children.is_empty()
+            && self.root.wildcard_specificity.is_none()
+            && self.root.terminal_specificity.is_none()
+    }
+
+    fn insert(&mut self, pattern: &str) {
+        let labels: Vec<&str> = pattern.split('.').rev().collect();
+        Self::insert_labels(&mut self.root, &labels, 0);
+    }
+
+    fn insert_labels(node: &mut TrieNode, labels: &[&str], concrete_so_far: usize) {
+        match labels.split_first() {
+            None => {
+                node.terminal_specificity =
+                    Some(node.terminal_specificity.map_or(concrete_so_far, |s| s.max(concrete_so_far)));
+            }
+            Some((&"*", _rest)) => {
+                // `is_trie_eligible` only lets a `*` through as a pattern's
+                // final (left-most) label, so there's never anything past it
+                node.wildcard_specificity =
+                    Some(node.wildcard_specificity.map_or(concrete_so_far, |s| s.max(concrete_so_far)));
+            }
+            Some((label, rest)) => {
+                Self::insert_labels(
+                    node.children.entry((*label).to_string()).or_default(),
+                    rest,
+                    concrete_so_far + 1,
+                );
+            }
+        }
+    }
+
+    /// The most specific matching pattern's specificity, if any
+    fn matches_with_specificity(&self, host: &str) -> Option<usize> {
+        let labels: Vec<&str> = host.split('.').rev().collect();
+        Self::lookup(&self.root, &labels)
+    }
+
+    fn lookup(node: &TrieNode, labels: &[&str]) -> Option<usize> {
+        match labels.split_first() {
+            None => node.terminal_specificity,
+            Some((label, rest)) => {
+                let via_child = node.children.get(*label).and_then(|child| Self::lookup(child, rest));
+                merge_best(via_child, node.wildcard_specificity)
+            }
+        }
+    }
+}
+
+fn merge_best(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(x), Some(y)) => Some(x.max(y)),
+        (Some(x), None) => Some(x),
+        (None, Some(y)) => Some(y),
+        (None, None) => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,9 +316,12 @@ mod tests {
         matcher.add_pattern("*.example.com");
         matcher.add_pattern("*.api.example.com");
 
-        // More specific pattern should win
-        assert_eq!(matcher.matches_with_specificity("test.api.example.com"), Some(4));
-        assert_eq!(matcher.matches_with_specificity("test.example.com"), Some(3));
+        // Both patterns match "test.api.example.com"; the narrower one
+        // (3 concrete labels: api, example, com) outranks the broader one
+        // (2: example, com).
+        assert_eq!(matcher.matches_with_specificity("test.api.example.com"), Some(3));
+        // Only the broader pattern matches this host.
+        assert_eq!(matcher.matches_with_specificity("test.example.com"), Some(2));
 
         // No match
         assert_eq!(matcher.matches_with_specificity("other.org"), None);
@@ -165,4 +334,61 @@ mod tests {
         assert_eq!(calculate_hostname_specificity("localhost"), 1);
         assert_eq!(calculate_hostname_specificity("example.com"), 2);
     }
+
+    #[test]
+    fn test_ip_specificity_prefers_longest_prefix() {
+        let mut matcher = HostMatcher::new();
+        matcher.add_ipv4_range("10.0.0.0/8".parse().unwrap());
+        matcher.add_ipv4_range("10.1.2.0/24".parse().unwrap());
+
+        // Both ranges cover this address; the /24 is more specific than the /8.
+        assert_eq!(
+            matcher.matches_ip_with_specificity(IpAddr::V4("10.1.2.3".parse::<Ipv4Addr>().unwrap())),
+            Some(24)
+        );
+        // Only the /8 covers this one.
+        assert_eq!(
+            matcher.matches_ip_with_specificity(IpAddr::V4("10.5.0.1".parse::<Ipv4Addr>().unwrap())),
+            Some(8)
+        );
+        // Neither covers this one.
+        assert_eq!(
+            matcher.matches_ip_with_specificity(IpAddr::V4("192.168.1.1".parse::<Ipv4Addr>().unwrap())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_interior_wildcard_falls_back_to_linear_matching() {
+        assert!(!is_trie_eligible("test.*.org"));
+        assert!(!is_trie_eligible("api*.example.com"));
+        assert!(is_trie_eligible("*.example.com"));
+        assert!(is_trie_eligible("example.com"));
+        assert!(is_trie_eligible("*"));
+    }
+
+    #[test]
+    fn test_trie_backed_matching_agrees_with_wildmatch() {
+        let patterns = ["example.com", "*.example.com", "*.a.b.example.com", "*"];
+
+        let mut matcher = HostMatcher::new();
+        for pattern in patterns {
+            matcher.add_pattern(pattern);
+        }
+        let linear: Vec<WildMatch> = patterns.iter().map(|p| WildMatch::new(p)).collect();
+
+        for host in [
+            "example.com",
+            "foo.example.com",
+            "x.a.b.example.com",
+            "a.b.example.com",
+            "other.org",
+            "x.y.example.com",
+            "localhost",
+        ] {
+            let trie_says = matcher.matches_host(host);
+            let linear_says = linear.iter().any(|p| p.matches(host));
+            assert_eq!(trie_says, linear_says, "mismatch for host {host}");
+        }
+    }
 }
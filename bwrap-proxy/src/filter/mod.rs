@@ -2,8 +2,20 @@
 
 pub mod matcher;
 pub mod policy;
+pub mod expr;
+pub mod learning;
 pub mod learning_recorder_trait;
+pub mod resolver;
+pub mod feed;
+pub mod cidr;
+pub mod antidoh;
 
 pub use matcher::HostMatcher;
-pub use policy::PolicyEngine;
+pub use policy::{PolicyEngine, ResolvedRanges};
+pub use expr::{Expr, is_expression};
+pub use learning::{LearningRecorder, LearningStats};
 pub use learning_recorder_trait::LearningRecorderTrait;
+pub use resolver::{DnsResolver, ResolutionContext};
+pub use feed::{augment_with_feeds, feed_group_name, feed_group_names, shortest_refresh_interval};
+pub use cidr::{aggregate_ipv4, aggregate_ipv6, DEFAULT_DENSITY_THRESHOLD};
+pub use antidoh::{augment_with_anti_doh, anti_doh_group_name, DOH_CANARY_DOMAIN};
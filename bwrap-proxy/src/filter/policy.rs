@@ -1,19 +1,57 @@
 //! Policy engine for evaluating network access
 
+use super::expr::{is_expression, Expr};
 use super::matcher::HostMatcher;
+use super::resolver::DnsResolver;
 use crate::config::schema::{DefaultMode, HostGroup, NetworkConfig};
 use crate::error::{ProxyError, Result};
 use indexmap::IndexMap;
+use ipnet::{Ipv4Net, Ipv6Net};
 use std::collections::HashSet;
 use std::net::IpAddr;
+use std::sync::Arc;
 
 /// Policy engine that evaluates whether connections should be allowed
 /// Uses "more specific wins" logic when both allow and deny rules match
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct PolicyEngine {
     allow_matcher: HostMatcher,
     deny_matcher: HostMatcher,
+    /// Expression-form `hosts` entries (see `Expr`/`is_expression`), kept
+    /// separate from `allow_matcher` since they need `port` as well as
+    /// `host` to evaluate
+    allow_exprs: Vec<Expr>,
+    /// Expression-form `hosts_deny` entries
+    deny_exprs: Vec<Expr>,
     default: crate::config::schema::DefaultMode,
+    /// Optional resolver enabling PTR-based re-evaluation of IP-only connections
+    resolver: Option<Arc<DnsResolver>>,
+}
+
+/// IPv4/IPv6 CIDR ranges an allow/deny group set resolves to, plus any
+/// hostname-only patterns that have no static IP — those can only be
+/// enforced by the userspace proxy, not a kernel-level filter. Returned by
+/// `PolicyEngine::resolve_ip_ranges`.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedRanges {
+    pub allow_ipv4: Vec<Ipv4Net>,
+    pub allow_ipv6: Vec<Ipv6Net>,
+    pub deny_ipv4: Vec<Ipv4Net>,
+    pub deny_ipv6: Vec<Ipv6Net>,
+    pub unenforceable_hosts: Vec<String>,
+}
+
+impl std::fmt::Debug for PolicyEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PolicyEngine")
+            .field("allow_matcher", &self.allow_matcher)
+            .field("deny_matcher", &self.deny_matcher)
+            .field("allow_exprs", &self.allow_exprs)
+            .field("deny_exprs", &self.deny_exprs)
+            .field("default", &self.default)
+            .field("resolver", &self.resolver.is_some())
+            .finish()
+    }
 }
 
 impl PolicyEngine {
@@ -26,31 +64,218 @@ impl PolicyEngine {
     ) -> Result<Self> {
         let mut allow_matcher = HostMatcher::new();
         let mut deny_matcher = HostMatcher::new();
+        let mut allow_exprs = Vec::new();
+        let mut deny_exprs = Vec::new();
         let mut processed = HashSet::new();
 
         // Recursively expand all groups referenced by the policy's allow groups
         for group_name in &allow_groups {
-            Self::expand_group(group_name, &network_config.groups, &mut allow_matcher, &mut processed)?;
+            Self::expand_group(
+                group_name,
+                &network_config.groups,
+                &mut allow_matcher,
+                &mut allow_exprs,
+                &mut processed,
+            )?;
         }
 
         // Recursively expand all groups referenced by the policy's deny groups
         processed.clear();
         for group_name in &deny_groups {
-            Self::expand_group_deny(group_name, &network_config.groups, &mut deny_matcher, &mut processed)?;
+            Self::expand_group_deny(
+                group_name,
+                &network_config.groups,
+                &mut deny_matcher,
+                &mut deny_exprs,
+                &mut processed,
+            )?;
         }
 
         Ok(Self {
             allow_matcher,
             deny_matcher,
+            allow_exprs,
+            deny_exprs,
             default,
+            resolver: None,
+        })
+    }
+
+    /// Attach a DNS resolver and forward-resolve every hostname pattern in
+    /// `allow_groups` into an auxiliary IP set on the allow matcher, so that
+    /// an allow-by-name rule also permits the addresses it currently
+    /// resolves to. Also enables PTR re-evaluation in `allow_async`.
+    pub async fn with_resolver(
+        mut self,
+        allow_groups: Vec<String>,
+        network_config: &NetworkConfig,
+        resolver: Arc<DnsResolver>,
+    ) -> Result<Self> {
+        let mut processed = HashSet::new();
+        let mut hosts = Vec::new();
+        for group_name in &allow_groups {
+            Self::collect_group_hosts(group_name, &network_config.groups, &mut hosts, &mut processed)?;
+        }
+
+        for host in hosts {
+            // Only forward-resolve concrete hostnames; glob patterns have no
+            // single resolvable address.
+            if host.contains('*') {
+                continue;
+            }
+            for ip in resolver.forward_lookup(&host).await {
+                match ip {
+                    IpAddr::V4(addr) => {
+                        if let Ok(net) = ipnet::Ipv4Net::new(addr, 32) {
+                            self.allow_matcher.add_ipv4_range(net);
+                        }
+                    }
+                    IpAddr::V6(addr) => {
+                        if let Ok(net) = ipnet::Ipv6Net::new(addr, 128) {
+                            self.allow_matcher.add_ipv6_range(net);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.resolver = Some(resolver);
+        Ok(self)
+    }
+
+    /// Collect the literal host patterns referenced (directly or via nested
+    /// groups) by `group_name`, for forward-resolution purposes.
+    fn collect_group_hosts(
+        group_name: &str,
+        groups: &IndexMap<String, HostGroup>,
+        out: &mut Vec<String>,
+        processed: &mut HashSet<String>,
+    ) -> Result<()> {
+        if processed.contains(group_name) {
+            return Ok(());
+        }
+        processed.insert(group_name.to_string());
+
+        let group = groups.get(group_name).ok_or_else(|| ProxyError::GroupNotFound {
+            group: group_name.to_string(),
+        })?;
+
+        out.extend(group.hosts.iter().cloned());
+
+        for child_name in &group.groups {
+            Self::collect_group_hosts(child_name, groups, out, processed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Collect the literal deny-host patterns referenced (directly or via
+    /// nested groups) by `group_name`
+    fn collect_group_hosts_deny(
+        group_name: &str,
+        groups: &IndexMap<String, HostGroup>,
+        out: &mut Vec<String>,
+        processed: &mut HashSet<String>,
+    ) -> Result<()> {
+        if processed.contains(group_name) {
+            return Ok(());
+        }
+        processed.insert(group_name.to_string());
+
+        let group = groups.get(group_name).ok_or_else(|| ProxyError::GroupNotFound {
+            group: group_name.to_string(),
+        })?;
+
+        out.extend(group.hosts_deny.iter().cloned());
+
+        for child_name in &group.groups {
+            Self::collect_group_hosts_deny(child_name, groups, out, processed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Expand `allow_groups`/`deny_groups` into concrete host patterns
+    /// (groups resolved, not referenced by name) — e.g. to record exactly
+    /// what a policy grants in a reproducibility lockfile.
+    pub fn expand_hostnames(
+        allow_groups: &[String],
+        deny_groups: &[String],
+        network_config: &NetworkConfig,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let mut allow = Vec::new();
+        let mut processed = HashSet::new();
+        for group_name in allow_groups {
+            Self::collect_group_hosts(group_name, &network_config.groups, &mut allow, &mut processed)?;
+        }
+
+        let mut deny = Vec::new();
+        processed.clear();
+        for group_name in deny_groups {
+            Self::collect_group_hosts_deny(group_name, &network_config.groups, &mut deny, &mut processed)?;
+        }
+
+        Ok((allow, deny))
+    }
+
+    /// Expand `allow_groups`/`deny_groups` into the CIDR ranges a
+    /// kernel-level filter can enforce directly, plus the hostname-only
+    /// patterns it can't (see `ResolvedRanges`) — for compiling a policy
+    /// into an nftables ruleset (see `bwrap_core::nftables`).
+    pub fn resolve_ip_ranges(
+        allow_groups: &[String],
+        deny_groups: &[String],
+        network_config: &NetworkConfig,
+    ) -> Result<ResolvedRanges> {
+        let mut allow_matcher = HostMatcher::new();
+        // Expression-form entries have no concrete CIDR to contribute, so
+        // this nftables-oriented expansion discards them (they end up
+        // covered by `unenforceable_hosts` via `expand_hostnames` below).
+        let mut allow_exprs = Vec::new();
+        let mut processed = HashSet::new();
+        for group_name in allow_groups {
+            Self::expand_group(
+                group_name,
+                &network_config.groups,
+                &mut allow_matcher,
+                &mut allow_exprs,
+                &mut processed,
+            )?;
+        }
+
+        let mut deny_matcher = HostMatcher::new();
+        let mut deny_exprs = Vec::new();
+        processed.clear();
+        for group_name in deny_groups {
+            Self::expand_group_deny(
+                group_name,
+                &network_config.groups,
+                &mut deny_matcher,
+                &mut deny_exprs,
+                &mut processed,
+            )?;
+        }
+
+        let (allow_hosts, deny_hosts) = Self::expand_hostnames(allow_groups, deny_groups, network_config)?;
+
+        Ok(ResolvedRanges {
+            allow_ipv4: allow_matcher.ipv4_ranges().to_vec(),
+            allow_ipv6: allow_matcher.ipv6_ranges().to_vec(),
+            deny_ipv4: deny_matcher.ipv4_ranges().to_vec(),
+            deny_ipv6: deny_matcher.ipv6_ranges().to_vec(),
+            unenforceable_hosts: allow_hosts.into_iter().chain(deny_hosts).collect(),
         })
     }
 
-    /// Recursively expand a group and add its hosts/IPs to the matcher (allow patterns)
+    /// Recursively expand a group and add its hosts/IPs to the matcher (allow
+    /// patterns); expression-form entries (see `Expr`/`is_expression`) are
+    /// parsed and collected into `exprs` instead, since they need `port` as
+    /// well as `host` to evaluate
     fn expand_group(
         group_name: &str,
         groups: &IndexMap<String, HostGroup>,
         matcher: &mut HostMatcher,
+        exprs: &mut Vec<Expr>,
         processed: &mut HashSet<String>,
     ) -> Result<()> {
         // Avoid reprocessing groups (handles DAG structure)
@@ -64,24 +289,63 @@ impl PolicyEngine {
             group: group_name.to_string(),
         })?;
 
-        // Add allow host patterns
+        // Add allow host patterns, routing expression-form entries to `exprs`
         for host in &group.hosts {
-            matcher.add_pattern(host);
+            Self::add_host_entry(host, matcher, exprs);
         }
 
+        // Add allow IP ranges (e.g. CIDR prefixes aggregated by LearningRecorder)
+        Self::add_ip_ranges(group, matcher);
+
         // Recursively expand referenced groups
         for child_name in &group.groups {
-            Self::expand_group(child_name, groups, matcher, processed)?;
+            Self::expand_group(child_name, groups, matcher, exprs, processed)?;
         }
 
         Ok(())
     }
 
-    /// Recursively expand a group and add its hosts/IPs to the deny matcher
+    /// Route a single `hosts`/`hosts_deny` entry to the plain matcher or, if
+    /// it's expression-form, to `exprs`. A malformed expression is skipped
+    /// (and logged) rather than propagated as an error here — `validator`
+    /// rejects it at config-load time, so reaching this point means it
+    /// already parsed successfully once; re-parsing failure here would only
+    /// happen for configs that bypassed validation.
+    fn add_host_entry(host: &str, matcher: &mut HostMatcher, exprs: &mut Vec<Expr>) {
+        if is_expression(host) {
+            match Expr::parse(host) {
+                Ok(expr) => exprs.push(expr),
+                Err(e) => tracing::warn!("Skipping invalid host expression '{host}': {e}"),
+            }
+        } else {
+            matcher.add_pattern(host);
+        }
+    }
+
+    /// Parse and add a group's `ipv4_ranges`/`ipv6_ranges` CIDR strings to a matcher,
+    /// skipping (and logging) any entry that fails to parse
+    fn add_ip_ranges(group: &HostGroup, matcher: &mut HostMatcher) {
+        for cidr in &group.ipv4_ranges {
+            match cidr.parse() {
+                Ok(net) => matcher.add_ipv4_range(net),
+                Err(_) => tracing::warn!("Skipping invalid IPv4 CIDR '{cidr}'"),
+            }
+        }
+        for cidr in &group.ipv6_ranges {
+            match cidr.parse() {
+                Ok(net) => matcher.add_ipv6_range(net),
+                Err(_) => tracing::warn!("Skipping invalid IPv6 CIDR '{cidr}'"),
+            }
+        }
+    }
+
+    /// Recursively expand a group and add its hosts/IPs to the deny matcher;
+    /// see `expand_group` for why expression-form entries go to `exprs`
     fn expand_group_deny(
         group_name: &str,
         groups: &IndexMap<String, HostGroup>,
         matcher: &mut HostMatcher,
+        exprs: &mut Vec<Expr>,
         processed: &mut HashSet<String>,
     ) -> Result<()> {
         // Avoid reprocessing groups (handles DAG structure)
@@ -95,62 +359,201 @@ impl PolicyEngine {
             group: group_name.to_string(),
         })?;
 
-        // Add deny host patterns
+        // Add deny host patterns, routing expression-form entries to `exprs`
         for host in &group.hosts_deny {
-            matcher.add_deny_pattern(host);
+            Self::add_host_entry(host, matcher, exprs);
         }
 
+        // Add deny IP ranges (e.g. CIDR prefixes aggregated by LearningRecorder)
+        Self::add_ip_ranges(group, matcher);
+
         // Recursively expand referenced groups (deny patterns)
         for child_name in &group.groups {
-            Self::expand_group_deny(child_name, groups, matcher, processed)?;
+            Self::expand_group_deny(child_name, groups, matcher, exprs, processed)?;
         }
 
         Ok(())
     }
 
-    /// Check if a connection to the given host/IP should be allowed
-    /// Uses "longest match" logic: when both allow and deny rules match,
-    /// the one with highest specificity wins. On a tie, deny wins.
-    pub fn allow(&self, host: &str, ip: Option<IpAddr>) -> bool {
-        // Check hostname specificity for both allow and deny matchers
-        let allow_hostname_spec = self.allow_matcher.matches_with_specificity(host);
-        let deny_hostname_spec = self.deny_matcher.matches_with_specificity(host);
+    /// Check if a connection to the given host/IP/port should be allowed
+    ///
+    /// Uses "longest match wins" logic: the best-matching allow pattern and
+    /// the best-matching deny pattern are each reduced to a single
+    /// specificity score — the most specific of its hostname match (see
+    /// `HostMatcher::matches_with_specificity`), its IP/CIDR match (see
+    /// `HostMatcher::matches_ip_with_specificity`, comparable on the same
+    /// scale via CIDR prefix length), and its match expression (see
+    /// `Expr::specificity`, comparable on the same scale since both count
+    /// concrete conditions) — and whichever side scores higher wins. Deny
+    /// wins a tie, and `default` only applies when neither side matches at
+    /// all.
+    pub fn allow(&self, host: &str, ip: Option<IpAddr>, port: Option<u16>) -> bool {
+        let allow_spec = Self::best_specificity(&self.allow_matcher, &self.allow_exprs, host, ip, port);
+        let deny_spec = Self::best_specificity(&self.deny_matcher, &self.deny_exprs, host, ip, port);
 
-        // Check IP matches (no specificity - either matches or doesn't)
-        let allow_ip_match = ip.map(|a| self.allow_matcher.matches_ip(a)).unwrap_or(false);
-        let deny_ip_match = ip.map(|a| self.deny_matcher.matches_ip(a)).unwrap_or(false);
+        match (allow_spec, deny_spec) {
+            (Some(allow_spec), Some(deny_spec)) => allow_spec > deny_spec,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => self.default == DefaultMode::Allow,
+        }
+    }
 
-        // Apply "longest match wins" logic with deny as tiebreak
-        match (allow_hostname_spec, deny_hostname_spec) {
-            (Some(allow_spec), Some(deny_spec)) => {
-                // Both matched by hostname - more specific wins (deny wins on tie)
-                return allow_spec > deny_spec;
-            }
-            (Some(_), None) => {
-                // Only allow matched by hostname
-                return true;
-            }
-            (None, Some(_)) => {
-                // Only deny matched by hostname
-                return false;
-            }
-            (None, None) => {
-                // No hostname matches, check IP matches
-                match (allow_ip_match, deny_ip_match) {
-                    (true, true) => {
-                        // Both matched by IP, deny wins on tie
-                        return false;
-                    }
-                    (true, false) => return true,
-                    (false, true) => return false,
-                    (false, false) => {
-                        // Neither matched - use default behavior
-                        // DefaultMode::Allow: allow by default (return true)
-                        // DefaultMode::Deny: deny by default (return false)
-                        return self.default == DefaultMode::Allow;
-                    }
-                }
+    /// The most specific of `matcher`'s hostname match, its IP/CIDR match,
+    /// and the best-matching expression in `exprs` against `host`/`ip`/
+    /// `port`, or `None` if nothing matches at all
+    fn best_specificity(
+        matcher: &HostMatcher,
+        exprs: &[Expr],
+        host: &str,
+        ip: Option<IpAddr>,
+        port: Option<u16>,
+    ) -> Option<usize> {
+        let host_spec = matcher.matches_with_specificity(host);
+        let ip_spec = ip.and_then(|addr| matcher.matches_ip_with_specificity(addr));
+        let expr_spec = exprs.iter().filter(|e| e.eval(host, port)).map(Expr::specificity).max();
+
+        [host_spec, ip_spec, expr_spec].into_iter().flatten().max()
+    }
+
+    /// Like `allow`, but when the connection was only identified by IP and
+    /// a resolver is attached, perform a PTR lookup to re-evaluate hostname
+    /// rules against the names that claim the address.
+    ///
+    /// A PTR-derived hostname match is treated as one specificity level
+    /// lower than an ordinary match, so a literal-IP deny rule still wins
+    /// a tie against a name recovered only through reverse DNS.
+    pub async fn allow_async(&self, host: &str, ip: Option<IpAddr>, port: Option<u16>) -> bool {
+        if self.allow(host, ip, port) {
+            return true;
+        }
+
+        let (Some(resolver), Some(addr)) = (&self.resolver, ip) else {
+            return self.allow(host, ip, port);
+        };
+
+        let mut best_allow = Self::best_specificity(&self.allow_matcher, &self.allow_exprs, host, ip, port);
+        let deny_spec = Self::best_specificity(&self.deny_matcher, &self.deny_exprs, host, ip, port);
+
+        for name in resolver.reverse_lookup(addr).await {
+            if let Some(spec) = self.allow_matcher.matches_with_specificity(&name) {
+                // PTR-derived match: discount specificity by one so a
+                // literal-IP or exact-hostname deny still wins on tie.
+                let discounted = spec.saturating_sub(1);
+                best_allow = Some(best_allow.unwrap_or(0).max(discounted));
             }
         }
+
+        match (best_allow, deny_spec) {
+            (Some(a), Some(d)) => a > d,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => self.default == DefaultMode::Allow,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(allow_hosts: &[&str], deny_hosts: &[&str], default: DefaultMode) -> PolicyEngine {
+        let mut allow_matcher = HostMatcher::new();
+        for host in allow_hosts {
+            allow_matcher.add_pattern(host);
+        }
+        let mut deny_matcher = HostMatcher::new();
+        for host in deny_hosts {
+            deny_matcher.add_pattern(host);
+        }
+        PolicyEngine {
+            allow_matcher,
+            deny_matcher,
+            allow_exprs: Vec::new(),
+            deny_exprs: Vec::new(),
+            default,
+            resolver: None,
+        }
+    }
+
+    #[test]
+    fn more_specific_allow_punches_through_a_broader_deny() {
+        let engine = engine(&["safe.example.com"], &["*.example.com"], DefaultMode::Deny);
+        assert!(engine.allow("safe.example.com", None, None));
+        assert!(!engine.allow("other.example.com", None, None));
+    }
+
+    #[test]
+    fn more_specific_deny_carves_an_exception_out_of_a_broader_allow() {
+        let engine = engine(&["*.example.com"], &["evil.example.com"], DefaultMode::Allow);
+        assert!(!engine.allow("evil.example.com", None, None));
+        assert!(engine.allow("other.example.com", None, None));
+    }
+
+    #[test]
+    fn equally_specific_allow_and_deny_ties_to_deny() {
+        let engine = engine(&["example.com"], &["example.com"], DefaultMode::Allow);
+        assert!(!engine.allow("example.com", None, None));
+    }
+
+    #[test]
+    fn neither_side_matches_falls_back_to_default() {
+        let allow_only = engine(&["example.com"], &[], DefaultMode::Allow);
+        assert!(allow_only.allow("unrelated.org", None, None));
+
+        let deny_only = engine(&["example.com"], &[], DefaultMode::Deny);
+        assert!(!deny_only.allow("unrelated.org", None, None));
+    }
+
+    #[test]
+    fn more_specific_ip_range_beats_a_broader_hostname_match() {
+        let mut e = engine(&["*.example.com"], &[], DefaultMode::Deny);
+        e.deny_matcher.add_ipv4_range("10.1.2.0/24".parse().unwrap());
+
+        let ip = Some(IpAddr::V4("10.1.2.3".parse().unwrap()));
+        // "*.example.com" matches the host with specificity 2, but the
+        // deny side's /24 (specificity 24) is more specific and wins.
+        assert!(!e.allow("foo.example.com", ip, None));
+    }
+
+    #[test]
+    fn more_specific_hostname_beats_a_broader_ip_range() {
+        let mut e = engine(&["safe.example.com"], &[], DefaultMode::Allow);
+        // "safe.example.com" scores 3 (three concrete labels); the /2 deny
+        // range scores 2, so the hostname is the more specific match.
+        e.deny_matcher.add_ipv4_range("0.0.0.0/2".parse().unwrap());
+
+        let ip = Some(IpAddr::V4("10.1.2.3".parse().unwrap()));
+        assert!(e.allow("safe.example.com", ip, None));
+    }
+
+    #[test]
+    fn allow_expression_grants_access_a_plain_pattern_would_miss() {
+        let mut e = engine(&[], &[], DefaultMode::Deny);
+        e.allow_exprs.push(Expr::parse("all(suffix = \"example.com\", port = \"443\")").unwrap());
+
+        assert!(e.allow("api.example.com", None, Some(443)));
+        assert!(!e.allow("api.example.com", None, Some(8080)));
+    }
+
+    #[test]
+    fn more_specific_expression_beats_a_broader_plain_deny() {
+        // "*.example.com" matches with specificity 2 (two concrete labels:
+        // "example", "com"); a three-predicate `all()` outscores it.
+        let mut e = engine(&[], &["*.example.com"], DefaultMode::Deny);
+        e.allow_exprs.push(
+            Expr::parse("all(host = \"safe.example.com\", suffix = \"example.com\", port = \"443\")").unwrap(),
+        );
+
+        assert!(e.allow("safe.example.com", None, Some(443)));
+        assert!(!e.allow("safe.example.com", None, Some(80)));
+    }
+
+    #[test]
+    fn equally_specific_expression_and_plain_deny_ties_to_deny() {
+        let mut e = engine(&[], &["*.example.com"], DefaultMode::Deny);
+        e.allow_exprs.push(Expr::parse("all(host = \"safe.example.com\", port = \"443\")").unwrap());
+
+        assert!(!e.allow("safe.example.com", None, Some(443)));
     }
 }
@@ -0,0 +1,136 @@
+//! DNS resolution support for policy matching
+//!
+//! Extends the policy engine so it can reason about addresses as well as
+//! names: a reverse (PTR) lookup lets a raw-IP connection be re-evaluated
+//! against hostname rules, and forward-resolving the hosts in an allow
+//! group lets an allow-by-name rule also cover the addresses it currently
+//! resolves to.
+
+use hickory_resolver::TokioAsyncResolver;
+use lru::LruCache;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default number of entries kept per cache (PTR and forward separately);
+/// bounds memory for a long-lived proxy daemon handling many distinct hosts
+/// instead of growing the cache forever.
+const DEFAULT_CACHE_CAPACITY: usize = 512;
+
+/// A cached resolution result along with the instant it expires
+struct CacheEntry<T> {
+    value: T,
+    expires_at: Instant,
+}
+
+/// DNS resolver with a small TTL-aware, size-bounded LRU cache, used to
+/// bridge IP-only connections back to hostname-based policy rules and to
+/// resolve CONNECT hostnames for IP/CIDR policy matching.
+pub struct DnsResolver {
+    resolver: TokioAsyncResolver,
+    ptr_cache: Mutex<LruCache<IpAddr, CacheEntry<Vec<String>>>>,
+    fwd_cache: Mutex<LruCache<String, CacheEntry<Vec<IpAddr>>>>,
+}
+
+impl DnsResolver {
+    /// Create a resolver using the system's configured nameservers, with the
+    /// default per-cache capacity (see `DEFAULT_CACHE_CAPACITY`)
+    pub fn new() -> Result<Self, hickory_resolver::error::ResolveError> {
+        Self::with_cache_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Create a resolver using the system's configured nameservers, capping
+    /// each of the PTR and forward caches at `capacity` entries
+    pub fn with_cache_capacity(capacity: usize) -> Result<Self, hickory_resolver::error::ResolveError> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(1).unwrap());
+        Ok(Self {
+            resolver,
+            ptr_cache: Mutex::new(LruCache::new(capacity)),
+            fwd_cache: Mutex::new(LruCache::new(capacity)),
+        })
+    }
+
+    /// Reverse-resolve an IP address to the hostnames that claim it (PTR lookup)
+    ///
+    /// Results are cached for the TTL returned by the resolver; a failed
+    /// lookup is treated as "no names" rather than an error, since plenty
+    /// of addresses simply have no PTR record.
+    pub async fn reverse_lookup(&self, ip: IpAddr) -> Vec<String> {
+        if let Some(cached) = self.get_cached(&self.ptr_cache, &ip) {
+            return cached;
+        }
+
+        let (names, ttl) = match self.resolver.reverse_lookup(ip).await {
+            Ok(lookup) => {
+                let ttl = lookup.as_lookup().valid_until();
+                let names = lookup
+                    .iter()
+                    .map(|name| name.to_string().trim_end_matches('.').to_string())
+                    .collect::<Vec<_>>();
+                (names, ttl)
+            }
+            Err(_) => (Vec::new(), Instant::now() + Duration::from_secs(60)),
+        };
+
+        self.put_cached(&self.ptr_cache, ip, names.clone(), ttl);
+        names
+    }
+
+    /// Forward-resolve a hostname to the addresses it currently answers with
+    ///
+    /// Used at policy-load time to build an auxiliary IP set for allow-by-name
+    /// groups, so a raw-IP connection to one of those addresses is also allowed.
+    pub async fn forward_lookup(&self, host: &str) -> Vec<IpAddr> {
+        if let Some(cached) = self.get_cached(&self.fwd_cache, &host.to_string()) {
+            return cached;
+        }
+
+        let (addrs, ttl) = match self.resolver.lookup_ip(host).await {
+            Ok(lookup) => {
+                let ttl = lookup.as_lookup().valid_until();
+                (lookup.iter().collect::<Vec<_>>(), ttl)
+            }
+            Err(_) => (Vec::new(), Instant::now() + Duration::from_secs(60)),
+        };
+
+        self.put_cached(&self.fwd_cache, host.to_string(), addrs.clone(), ttl);
+        addrs
+    }
+
+    fn get_cached<K: std::hash::Hash + Eq + Clone, T: Clone>(
+        &self,
+        cache: &Mutex<LruCache<K, CacheEntry<T>>>,
+        key: &K,
+    ) -> Option<T> {
+        let mut cache = cache.lock().ok()?;
+        let entry = cache.get(key)?;
+        if entry.expires_at > Instant::now() {
+            Some(entry.value.clone())
+        } else {
+            None
+        }
+    }
+
+    fn put_cached<K: std::hash::Hash + Eq, T>(
+        &self,
+        cache: &Mutex<LruCache<K, CacheEntry<T>>>,
+        key: K,
+        value: T,
+        expires_at: Instant,
+    ) {
+        if let Ok(mut cache) = cache.lock() {
+            cache.put(key, CacheEntry { value, expires_at });
+        }
+    }
+}
+
+/// Context gathered about a connection beyond the bare hostname the client
+/// supplied. `PolicyEngine` does not take it as input; `allow_async` does
+/// its own PTR lookup.
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionContext {
+    /// Hostnames recovered via PTR lookup on the connecting IP, if any
+    pub ptr_names: Vec<String>,
+}
@@ -6,7 +6,10 @@ pub mod filter;
 pub mod proxy;
 
 // Re-export commonly used types
-pub use config::{HostGroup, NetworkConfig, NetworkMode, DefaultMode};
+pub use config::{Config, ConfigLoader, HostGroup, NetworkConfig, NetworkMode, Policy, DefaultMode};
 pub use error::{ProxyError, Result, ValidationError};
-pub use filter::{HostMatcher, PolicyEngine};
-pub use proxy::{ProxyServer, ProxyServerConfig};
+pub use filter::{HostMatcher, LearningRecorder, PolicyEngine, ResolvedRanges, DEFAULT_DENSITY_THRESHOLD};
+pub use proxy::{
+    PolicyPrompt, ProxyServer, ProxyServerConfig, RateLimit, RELAY_PROTOCOL_VERSION, SessionAllowlist,
+    SharedPolicyEngine, SniFallback, UpstreamRouter, WireProtocol,
+};
@@ -0,0 +1,90 @@
+//! Dial a destination through an upstream HTTP CONNECT proxy
+//!
+//! When `ProxyServerConfig::upstream_router` selects an `http://`/`https://`
+//! upstream for a destination, `dial` hands the connection off to
+//! [`connect_via_upstream`] instead of calling `TcpStream::connect`
+//! directly, so sandbox egress can be chained through a corporate HTTP
+//! proxy. Local policy and learning evaluation in `connect_filtered`
+//! already happened by the time this runs — this module only speaks the
+//! upstream leg of the CONNECT handshake. See `super::socks5_connect` for
+//! the SOCKS5 upstream counterpart.
+
+use crate::error::{ProxyError, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use url::Url;
+
+/// Connect to `host:port` by tunneling through the HTTP CONNECT proxy at
+/// `upstream`, returning the established stream ready for
+/// `copy_bidirectional`. Credentials in `upstream`'s userinfo, if present,
+/// are sent as a `Proxy-Authorization: Basic` header.
+pub(super) async fn connect_via_upstream(
+    upstream: &Url,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream> {
+    let upstream_host = upstream
+        .host_str()
+        .ok_or_else(|| ProxyError::Network(format!("upstream proxy URL has no host: {upstream}")))?;
+    let upstream_port = upstream
+        .port_or_known_default()
+        .ok_or_else(|| ProxyError::Network(format!("upstream proxy URL has no port: {upstream}")))?;
+
+    let stream = TcpStream::connect((upstream_host, upstream_port)).await?;
+    let mut reader = BufReader::new(stream);
+
+    let auth_header = if !upstream.username().is_empty() {
+        let credentials = format!(
+            "{}:{}",
+            upstream.username(),
+            upstream.password().unwrap_or("")
+        );
+        format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            BASE64.encode(credentials)
+        )
+    } else {
+        String::new()
+    };
+
+    let request =
+        format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n{auth_header}\r\n");
+    reader.get_mut().write_all(request.as_bytes()).await?;
+    reader.get_mut().flush().await?;
+
+    let status_line = read_status_line(&mut reader).await?;
+    if !status_line.starts_with("HTTP/1.1 200") && !status_line.starts_with("HTTP/1.0 200") {
+        return Err(ProxyError::Network(format!(
+            "upstream proxy refused CONNECT {host}:{port}: {status_line}"
+        )));
+    }
+
+    // Drain the remaining response headers up to the blank line before
+    // handing the stream back; there's nothing useful in them for a
+    // successful CONNECT, but we must not leave them sitting in the
+    // `BufReader`'s internal buffer when we unwrap it below.
+    loop {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await?;
+        if n == 0 || line == "\r\n" {
+            break;
+        }
+    }
+
+    Ok(reader.into_inner())
+}
+
+/// Read a single `\r\n`-terminated line (the HTTP status line) from the
+/// upstream proxy's response.
+async fn read_status_line(reader: &mut BufReader<TcpStream>) -> Result<String> {
+    let mut line = String::new();
+    let n = reader.read_line(&mut line).await?;
+    if n == 0 {
+        return Err(ProxyError::Network(
+            "upstream proxy closed the connection before responding".to_string(),
+        ));
+    }
+    Ok(line.trim_end().to_string())
+}
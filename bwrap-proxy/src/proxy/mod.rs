@@ -0,0 +1,18 @@
+pub mod http_connect;
+pub mod prompt;
+pub mod proxy_protocol;
+pub mod server;
+pub mod sni;
+pub mod socks5;
+pub mod socks5_connect;
+pub mod throttle;
+pub mod upstream;
+
+pub use prompt::PolicyPrompt;
+pub use throttle::RateLimit;
+pub use upstream::UpstreamRouter;
+
+pub use server::{
+    ProxyServer, ProxyServerConfig, RELAY_PROTOCOL_VERSION, SessionAllowlist, SharedPolicyEngine, SniFallback,
+    WireProtocol,
+};
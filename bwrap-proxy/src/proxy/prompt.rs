@@ -0,0 +1,80 @@
+//! Interactive allow/deny prompt fallback for policy-denied CONNECTs
+//!
+//! When `ProxyServerConfig::policy_prompt` is set, a host the policy engine
+//! denies isn't blocked outright: `connect_filtered` asks the launching
+//! bw-* process via a control socket (see `bwrap_core::prompt`) whether to
+//! allow it anyway, and keeps the connection open while it waits. The
+//! control socket server is responsible for serializing concurrent prompts
+//! on the terminal and for persisting "allow and remember" answers back
+//! into policy; this side only needs to ask, interpret the answer, and —
+//! for "allow for this session" — record the host in
+//! `super::server::SessionAllowlist` so later connections in the same
+//! proxy process skip the prompt without touching the config file.
+
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tracing::debug;
+
+/// Where to reach the parent process's prompt control socket, and how long
+/// to wait for an answer before falling back to deny.
+#[derive(Debug, Clone)]
+pub struct PolicyPrompt {
+    pub socket_path: PathBuf,
+    pub timeout: Duration,
+}
+
+/// The user's answer to an allow/deny prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PromptDecision {
+    /// Allow this connection only
+    AllowOnce,
+    /// Allow this host for the remainder of the proxy process's lifetime,
+    /// without persisting it to policy — see `super::server::SessionAllowlist`
+    AllowSession,
+    /// Allow this connection and persist the domain to policy
+    AllowPersist,
+    /// Deny this connection only
+    DenyOnce,
+    /// Deny this connection and persist the domain to policy
+    DenyPersist,
+}
+
+/// Ask the parent process whether to allow `host`, blocking until it
+/// answers or `prompt.timeout` elapses. Any failure to reach the control
+/// socket, a malformed reply, or a timed-out wait all fall back to
+/// `DenyOnce` — an unreachable prompt must never fail open.
+pub(super) async fn ask(prompt: &PolicyPrompt, host: &str) -> PromptDecision {
+    match tokio::time::timeout(prompt.timeout, ask_inner(prompt, host)).await {
+        Ok(Ok(decision)) => decision,
+        Ok(Err(e)) => {
+            debug!("Policy prompt for {} failed: {}", host, e);
+            PromptDecision::DenyOnce
+        }
+        Err(_) => {
+            debug!("Policy prompt for {} timed out after {:?}", host, prompt.timeout);
+            PromptDecision::DenyOnce
+        }
+    }
+}
+
+async fn ask_inner(prompt: &PolicyPrompt, host: &str) -> crate::error::Result<PromptDecision> {
+    let conn = UnixStream::connect(&prompt.socket_path).await?;
+    let (read_half, mut write_half) = conn.into_split();
+
+    write_half.write_all(format!("PROMPT {host}\n").as_bytes()).await?;
+    write_half.flush().await?;
+
+    let mut reader = BufReader::new(read_half);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+
+    Ok(match line.trim() {
+        "ALLOW_ONCE" => PromptDecision::AllowOnce,
+        "ALLOW_SESSION" => PromptDecision::AllowSession,
+        "ALLOW_PERSIST" => PromptDecision::AllowPersist,
+        "DENY_PERSIST" => PromptDecision::DenyPersist,
+        _ => PromptDecision::DenyOnce,
+    })
+}
@@ -0,0 +1,161 @@
+//! Parse an optional PROXY protocol v2 header ahead of the text CONNECT line
+//!
+//! Counterpart to bw-relay's encoder (`bw-relay/src/proxy_protocol.rs`):
+//! when bw-relay is run with `--proxy-protocol-v2`, each UDS connection
+//! opens with one of these headers ahead of the usual `CONNECT host
+//! port\n` line, so `handle_client_text` can attribute its log output to
+//! the real client endpoint that opened the tunnel rather than just
+//! "something connected to the relay's local socket" — the same
+//! standardized per-connection identity carried on a real load balancer's
+//! PROXY protocol hop (cf. the `proxy-protocol` crate ngrok-rust uses).
+//!
+//! Only the specific subset bw-relay actually emits is handled: the v2
+//! signature, a TCP4 or TCP6 address block, and an optional
+//! PP2_TYPE_AUTHORITY TLV carrying a non-IP-literal destination hostname.
+//! Other commands and TLV types are accepted and skipped rather than
+//! rejected, since this is a read-only audit aid, not a protocol gateway
+//! bw-relay's own CONNECT handshake still does the real work through; an
+//! address family or transport other than TCP4/TCP6 is rejected with an
+//! error.
+//!
+//! When `buf` doesn't start with the v2 signature at all, `parse` returns
+//! `Ok(None)` — not an error — since most connections (and every
+//! `VERSION <n>\n` probe) won't carry one.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub(super) const SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+const PP2_TYPE_AUTHORITY: u8 = 0x02;
+const FAMILY_TCP4: u8 = 0x11;
+const FAMILY_TCP6: u8 = 0x21;
+
+/// A parsed header: the source endpoint the original client connected
+/// from, and the destination bw-relay says it's tunneling to. The
+/// hostname, when the header carried a PP2_TYPE_AUTHORITY TLV for it,
+/// takes priority over the address block's destination address, which is
+/// just the unspecified address whenever the real destination wasn't an
+/// IP literal (see bw-relay's encoder).
+pub(super) struct ProxyProtocolHeader {
+    pub source: SocketAddr,
+    pub destination_host: Option<String>,
+    pub destination_port: u16,
+    /// Bytes of `buf` the header occupied, so the caller knows where the
+    /// `CONNECT ...` line starts.
+    pub consumed: usize,
+}
+
+/// Parse a PROXY protocol v2 header from the start of `buf`, if present.
+pub(super) fn parse(buf: &[u8]) -> anyhow::Result<Option<ProxyProtocolHeader>> {
+    if buf.len() < 16 || buf[..12] != SIGNATURE {
+        return Ok(None);
+    }
+
+    let family = buf[13];
+    let len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    let header_end = 16 + len;
+    if buf.len() < header_end {
+        anyhow::bail!(
+            "Truncated PROXY protocol v2 header: need {header_end} bytes, got {}",
+            buf.len()
+        );
+    }
+
+    let payload = &buf[16..header_end];
+    let (source_ip, dest_ip, addr_len) = match family {
+        FAMILY_TCP4 => {
+            if payload.len() < 12 {
+                anyhow::bail!("PROXY protocol v2 TCP4 address block too short");
+            }
+            let src = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+            let dst = Ipv4Addr::new(payload[4], payload[5], payload[6], payload[7]);
+            (IpAddr::V4(src), IpAddr::V4(dst), 12)
+        }
+        FAMILY_TCP6 => {
+            if payload.len() < 36 {
+                anyhow::bail!("PROXY protocol v2 TCP6 address block too short");
+            }
+            let mut src = [0u8; 16];
+            let mut dst = [0u8; 16];
+            src.copy_from_slice(&payload[0..16]);
+            dst.copy_from_slice(&payload[16..32]);
+            (IpAddr::V6(Ipv6Addr::from(src)), IpAddr::V6(Ipv6Addr::from(dst)), 36)
+        }
+        other => anyhow::bail!("Unsupported PROXY protocol v2 address family/transport: {other:#x}"),
+    };
+
+    let port_offset = addr_len - 4;
+    let source_port = u16::from_be_bytes([payload[port_offset], payload[port_offset + 1]]);
+    let destination_port = u16::from_be_bytes([payload[port_offset + 2], payload[port_offset + 3]]);
+    let destination_host = parse_authority_tlv(&payload[addr_len..]).or_else(|| Some(dest_ip.to_string()));
+
+    Ok(Some(ProxyProtocolHeader {
+        source: SocketAddr::new(source_ip, source_port),
+        destination_host,
+        destination_port,
+        consumed: header_end,
+    }))
+}
+
+/// Pull a PP2_TYPE_AUTHORITY value out of a header's TLV list, if present.
+fn parse_authority_tlv(mut tlvs: &[u8]) -> Option<String> {
+    while tlvs.len() >= 3 {
+        let tlv_type = tlvs[0];
+        let tlv_len = u16::from_be_bytes([tlvs[1], tlvs[2]]) as usize;
+        let value = tlvs.get(3..3 + tlv_len)?;
+        if tlv_type == PP2_TYPE_AUTHORITY {
+            return std::str::from_utf8(value).ok().map(|s| s.to_string());
+        }
+        tlvs = &tlvs[3 + tlv_len..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_returns_none_without_signature() {
+        let buf = b"CONNECT example.com 443\n";
+        assert!(parse(buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_tcp4_header_with_authority_tlv() {
+        let authority = b"example.com";
+        let addr_block_len = 12;
+        let tlv_len = 3 + authority.len();
+
+        let mut buf = SIGNATURE.to_vec();
+        buf.push(0x21); // version 2, command PROXY
+        buf.push(FAMILY_TCP4);
+        buf.extend_from_slice(&((addr_block_len + tlv_len) as u16).to_be_bytes());
+        buf.extend_from_slice(&[127, 0, 0, 1]); // source
+        buf.extend_from_slice(&[0, 0, 0, 0]); // destination (unspecified - hostname in TLV)
+        buf.extend_from_slice(&54321u16.to_be_bytes());
+        buf.extend_from_slice(&443u16.to_be_bytes());
+        buf.push(PP2_TYPE_AUTHORITY);
+        buf.extend_from_slice(&(authority.len() as u16).to_be_bytes());
+        buf.extend_from_slice(authority);
+        buf.extend_from_slice(b"CONNECT example.com 443\n");
+
+        let header = parse(&buf).unwrap().unwrap();
+        assert_eq!(header.source, "127.0.0.1:54321".parse().unwrap());
+        assert_eq!(header.destination_host.as_deref(), Some("example.com"));
+        assert_eq!(header.destination_port, 443);
+        assert_eq!(&buf[header.consumed..], b"CONNECT example.com 443\n");
+    }
+
+    #[test]
+    fn test_parse_rejects_truncated_header() {
+        let mut buf = SIGNATURE.to_vec();
+        buf.push(0x21);
+        buf.push(FAMILY_TCP4);
+        buf.extend_from_slice(&100u16.to_be_bytes());
+        buf.extend_from_slice(&[127, 0, 0, 1]);
+
+        assert!(parse(&buf).is_err());
+    }
+}
@@ -1,10 +1,58 @@
+use super::upstream::UpstreamRouter;
 use crate::config::schema::NetworkConfig;
 use crate::error::Result;
-use crate::filter::{LearningRecorder, PolicyEngine};
+use crate::filter::{DnsResolver, LearningRecorder, PolicyEngine};
+use arc_swap::ArcSwap;
+use chrono::Duration;
+use std::collections::HashSet;
+use std::net::IpAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::net::UnixListener;
+use tokio::task::JoinSet;
 use tracing::{debug, info};
+use url::Url;
+
+/// Lock-free, hot-swappable handle to the active policy engine.
+///
+/// Holding `None` means no filtering is configured (open mode); a reload
+/// that fails to parse/validate simply never calls `store`, so the
+/// previous good engine (if any) stays live.
+pub type SharedPolicyEngine = Arc<ArcSwap<Option<PolicyEngine>>>;
+
+/// Version of the text wire protocol (see `handle_client_text`), bumped
+/// whenever a request/response line's meaning or format changes in a way
+/// an older `bw-relay` couldn't cope with. `bw-relay` sends a `VERSION`
+/// probe over a short-lived connection before relying on the rest of the
+/// protocol, so a mismatched pair refuses clearly at startup instead of
+/// failing mysteriously on the first real `CONNECT`.
+pub const RELAY_PROTOCOL_VERSION: u32 = 1;
+
+/// Wire protocol a `ProxyServer` speaks on its Unix domain socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireProtocol {
+    /// The ad-hoc `"CONNECT host port\n"` text protocol bw-relay speaks today
+    #[default]
+    Text,
+    /// Standard SOCKS5 (RFC 1928), for off-the-shelf SOCKS5 clients
+    Socks5,
+}
+
+/// What `verify_sni` does when the client's first flight is TLS but its SNI
+/// can't be read (Encrypted Client Hello). Unreadable-but-TLS is distinct
+/// from non-TLS traffic, which always passes through regardless of this
+/// setting — see `super::sni::ClientHelloSniff::is_tls`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SniFallback {
+    /// Let the connection through on whatever the CONNECT host already
+    /// cleared — matches `verify_sni`'s original behavior before ECH was
+    /// considered.
+    #[default]
+    Allow,
+    /// Block the connection rather than trust a CONNECT host that can no
+    /// longer be cross-checked against the real destination.
+    Block,
+}
 
 /// Policy filtering proxy server configuration
 /// Communicates with bw-relay via a simple text protocol over Unix Domain Socket
@@ -14,12 +62,97 @@ pub struct ProxyServerConfig {
     pub socket_path: PathBuf,
     /// Network configuration with policies and groups
     pub network_config: Arc<NetworkConfig>,
-    /// Policy engine for evaluation
-    pub policy_engine: Option<Arc<PolicyEngine>>,
+    /// Policy engine for evaluation, swappable by a `ConfigWatcher` without restart
+    pub policy_engine: Option<SharedPolicyEngine>,
     /// Learning recorder for learning mode
     pub learning_recorder: Option<Arc<LearningRecorder>>,
     /// Optional path to save learning data on shutdown
     pub learning_output: Option<PathBuf>,
+    /// If true, save learning data with `LearningRecorder::save_with_stats`
+    /// (raw addresses, sorted by hit count and annotated with access stats)
+    /// instead of the default compact `save_to_file`
+    pub learning_save_stats: bool,
+    /// If set, prune learning entries not seen within this window before
+    /// every save
+    pub learning_max_age: Option<Duration>,
+    /// If true, keep the socket bound after the first connection instead of
+    /// unlinking it, so later, unrelated processes can also connect — used
+    /// by a long-lived proxy daemon that many sandbox launches share.
+    /// Default (`false`) keeps the existing single-shot-per-invocation
+    /// behavior, where the socket is unlinked from the host after the first
+    /// relay connects so nothing else can attach to it.
+    pub persistent: bool,
+    /// Wire protocol to speak on `socket_path`; see `WireProtocol`
+    pub protocol: WireProtocol,
+    /// If true, peek the client's TLS ClientHello after CONNECT and run its
+    /// SNI `server_name` through the policy engine too, so a client can't
+    /// bypass host-based policy by dialing an allowed CONNECT target while
+    /// presenting a different SNI to the remote. See `super::sni`.
+    pub verify_sni: bool,
+    /// What to do when `verify_sni` is set but the first flight is TLS and
+    /// still isn't readable (e.g. Encrypted Client Hello, which keeps
+    /// `server_name` confidential from anything sitting where this proxy
+    /// does). Ignored for traffic that isn't TLS at all, which is always
+    /// let through untouched. See `SniFallback`.
+    pub sni_fallback: SniFallback,
+    /// Per-destination upstream proxy selection: dial allowed destinations
+    /// directly, through one global upstream, or through a different
+    /// upstream depending on the destination host. See `UpstreamRouter`,
+    /// `super::http_connect`, and `super::socks5_connect`.
+    pub upstream_router: UpstreamRouter,
+    /// On SIGTERM/SIGINT, wait for in-flight tunnels to finish instead of
+    /// dropping them immediately. Default `false` keeps the original
+    /// abrupt-shutdown behavior.
+    pub graceful_shutdown: bool,
+    /// How long to wait for in-flight tunnels to finish during a graceful
+    /// shutdown before force-closing whatever's left. Ignored unless
+    /// `graceful_shutdown` is set.
+    pub drain_timeout: std::time::Duration,
+    /// If set, explicitly forward-resolve the CONNECT host, re-check policy
+    /// against the resolved address (so CIDR/IP rules see the real
+    /// destination), and pin the dial to exactly that address — closing the
+    /// gap where a name resolves to an allowed host at check time but a
+    /// blocked one at connect time. Ignored for destinations
+    /// `upstream_router` routes through an upstream proxy, since the
+    /// upstream does its own resolution.
+    pub resolver: Option<Arc<DnsResolver>>,
+    /// If set, cap the tunnel's bandwidth in each direction via a
+    /// token-bucket wrapper around the client socket. See `super::throttle`.
+    pub rate_limit: Option<super::throttle::RateLimit>,
+    /// If set, a host the policy engine denies isn't blocked outright: ask
+    /// the launching bw-* process via this control socket (allow
+    /// once/session/persist/deny), holding the connection open while it
+    /// waits. See `super::prompt`.
+    pub policy_prompt: Option<super::prompt::PolicyPrompt>,
+    /// Hosts allowed via an "allow for this session" policy-prompt answer;
+    /// consulted before prompting again so the user isn't re-asked about
+    /// the same host twice in one proxy process's lifetime.
+    pub session_allowlist: SessionAllowlist,
+}
+
+/// Hosts allowed via an "allow for this session" policy-prompt answer.
+/// Unlike `PromptDecision::AllowPersist`, these never touch the config
+/// file — they live only as long as this proxy process and are forgotten
+/// on the next sandbox launch, which starts a fresh, empty allowlist.
+#[derive(Clone, Default)]
+pub struct SessionAllowlist(Arc<Mutex<HashSet<String>>>);
+
+impl SessionAllowlist {
+    /// Record `host` as allowed for the rest of this process's lifetime
+    pub fn allow(&self, host: &str) {
+        self.0.lock().unwrap().insert(host.to_string());
+    }
+
+    /// Whether `host` was previously allowed via `allow`
+    pub fn contains(&self, host: &str) -> bool {
+        self.0.lock().unwrap().contains(host)
+    }
+}
+
+/// Wrap a fixed policy engine (never hot-reloaded) in a `SharedPolicyEngine`
+/// for callers that don't need live reload.
+pub fn static_policy_engine(engine: PolicyEngine) -> SharedPolicyEngine {
+    Arc::new(ArcSwap::from_pointee(Some(engine)))
 }
 
 /// Policy filtering proxy server
@@ -54,6 +187,11 @@ impl ProxyServer {
         let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
         let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
 
+        // Tracks in-flight connection-handling tasks so a graceful shutdown
+        // can wait for them (or force-abort whatever's left at the deadline)
+        // instead of just returning out from under them.
+        let mut connections: JoinSet<()> = JoinSet::new();
+
         let mut first_connection = true;
         loop {
             tokio::select! {
@@ -66,14 +204,16 @@ impl ProxyServer {
                     // This prevents other processes from connecting to this socket.
                     if first_connection {
                         first_connection = false;
-                        let _ = std::fs::remove_file(&self.config.socket_path);
-                        debug!("Socket unlinked after first connection");
+                        if !self.config.persistent {
+                            let _ = std::fs::remove_file(&self.config.socket_path);
+                            debug!("Socket unlinked after first connection");
+                        }
                     }
 
                     let config = self.config.clone();
 
                     // Spawn a task for each connection
-                    tokio::spawn(async move {
+                    connections.spawn(async move {
                         let _ = handle_client(socket, config).await;
                     });
                 }
@@ -93,13 +233,43 @@ impl ProxyServer {
             }
         }
 
+        if self.config.graceful_shutdown && !connections.is_empty() {
+            info!(
+                "Draining {} in-flight connection(s) (up to {:?})",
+                connections.len(),
+                self.config.drain_timeout
+            );
+            let _ = tokio::time::timeout(self.config.drain_timeout, async {
+                while connections.join_next().await.is_some() {}
+            })
+            .await;
+
+            if !connections.is_empty() {
+                info!(
+                    "Drain timeout elapsed with {} connection(s) still open; forcing shutdown",
+                    connections.len()
+                );
+                connections.abort_all();
+            }
+        }
+
         Ok(())
     }
 
     /// Save learning data to file if learning mode is active
     fn save_learning_data(&self) {
         if let (Some(ref recorder), Some(ref output_path)) = (&self.config.learning_recorder, &self.config.learning_output) {
-            match recorder.save_to_file(output_path) {
+            if let Some(max_age) = self.config.learning_max_age {
+                recorder.prune(max_age);
+            }
+
+            let result = if self.config.learning_save_stats {
+                recorder.save_with_stats(output_path)
+            } else {
+                recorder.save_to_file(output_path)
+            };
+
+            match result {
                 Ok(_) => {
                     info!("Learning data saved to {:?}", output_path);
                 }
@@ -111,14 +281,226 @@ impl ProxyServer {
     }
 }
 
+/// Handle a single client connection over UDS, in whichever wire protocol
+/// `config.protocol` selects
+async fn handle_client(stream: tokio::net::UnixStream, config: ProxyServerConfig) -> Result<()> {
+    match config.protocol {
+        WireProtocol::Text => handle_client_text(stream, config).await,
+        WireProtocol::Socks5 => super::socks5::handle_client(stream, config).await,
+    }
+}
+
+/// Outcome of policy-checking and dialing a requested destination, shared
+/// by every wire protocol's `handle_client` so each only needs to map it to
+/// its own reply format.
+pub(super) enum ConnectOutcome {
+    /// The policy engine denied the destination
+    Blocked,
+    /// Dialing the destination failed
+    DialFailed,
+    /// Connected; ready to tunnel
+    Connected(tokio::net::TcpStream),
+}
+
+/// Check `host`/`ip`/`port` against the configured policy engine, if any.
+/// Loading the swap here (rather than holding a reference across an
+/// `.await` point) means a hot-reload mid-connection can't leave a caller
+/// pinned to a stale engine. Absent a policy engine, everything is allowed.
+pub(super) fn policy_allows(config: &ProxyServerConfig, host: &str, ip: Option<IpAddr>, port: Option<u16>) -> bool {
+    if let Some(ref policy_engine) = config.policy_engine {
+        let engine = policy_engine.load();
+        if let Some(ref engine) = **engine {
+            let allowed = engine.allow(host, ip, port);
+            debug!("Policy check for {} (ip={:?}, port={:?}): allowed={}", host, ip, port, allowed);
+            return allowed;
+        }
+    }
+    true
+}
+
+/// Policy-check, learning-record, and dial `host:port`, the shared core of
+/// every wire protocol's CONNECT handling.
+pub(super) async fn connect_filtered(
+    config: &ProxyServerConfig,
+    host: &str,
+    port: u16,
+) -> ConnectOutcome {
+    if !policy_allows(config, host, None, Some(port)) && !config.session_allowlist.contains(host) {
+        match &config.policy_prompt {
+            Some(prompt) => match super::prompt::ask(prompt, host).await {
+                super::prompt::PromptDecision::DenyOnce => {
+                    debug!("Connection to {}:{} denied via policy prompt", host, port);
+                    return ConnectOutcome::Blocked;
+                }
+                super::prompt::PromptDecision::DenyPersist => {
+                    debug!("Connection to {}:{} denied and persisted via policy prompt", host, port);
+                    return ConnectOutcome::Blocked;
+                }
+                super::prompt::PromptDecision::AllowOnce => {
+                    debug!("Connection to {} allowed once via policy prompt", host);
+                }
+                super::prompt::PromptDecision::AllowSession => {
+                    debug!("Connection to {} allowed for the rest of this session via policy prompt", host);
+                    config.session_allowlist.allow(host);
+                }
+                super::prompt::PromptDecision::AllowPersist => {
+                    debug!("Connection to {} allowed and persisted via policy prompt", host);
+                }
+            },
+            None => {
+                debug!("Connection blocked by policy: {}:{}", host, port);
+                return ConnectOutcome::Blocked;
+            }
+        }
+    }
+
+    // Record access in learning mode if enabled
+    if let Some(ref learning_recorder) = config.learning_recorder {
+        learning_recorder.record(host, None);
+
+        // Save learning data immediately after recording
+        if let Some(ref output_path) = config.learning_output {
+            if let Some(max_age) = config.learning_max_age {
+                learning_recorder.prune(max_age);
+            }
+
+            let result = if config.learning_save_stats {
+                learning_recorder.save_with_stats(output_path)
+            } else {
+                learning_recorder.save_to_file(output_path)
+            };
+
+            if let Err(e) = result {
+                debug!("Failed to save learning data: {}", e);
+            }
+        }
+    }
+
+    let upstream = config.upstream_router.select(host).cloned();
+
+    // If a resolver is configured (and this destination isn't being handed
+    // off to an upstream proxy, which does its own resolution), explicitly
+    // resolve `host` and re-check policy against the resolved address so
+    // CIDR/IP rules see the real destination, then pin the dial to exactly
+    // that address. This closes the gap where `host` resolves to an
+    // allowed address at check time but a different (blocked) one at
+    // connect time.
+    if upstream.is_none() {
+        if let Some(ref resolver) = config.resolver {
+            match resolve_and_pin(config, resolver, host, port).await {
+                Ok(ip) => {
+                    return dial(host, port, Some(ip), None).await;
+                }
+                Err(ResolutionFailure::NoAddresses) => {
+                    debug!("Could not resolve {} to an address", host);
+                    return ConnectOutcome::DialFailed;
+                }
+                Err(ResolutionFailure::PolicyDenied) => {
+                    debug!(
+                        "Connection blocked: no address {} resolves to passes policy",
+                        host
+                    );
+                    return ConnectOutcome::Blocked;
+                }
+            }
+        }
+    }
+
+    dial(host, port, None, upstream).await
+}
+
+/// Why `resolve_and_pin` couldn't return a usable address
+enum ResolutionFailure {
+    /// The resolver returned no addresses at all
+    NoAddresses,
+    /// Every resolved address was denied by policy
+    PolicyDenied,
+}
+
+/// Resolve `host` to an address the policy engine approves, preferring the
+/// first one that passes. A literal IP is checked (and returned) without a
+/// DNS round-trip.
+async fn resolve_and_pin(
+    config: &ProxyServerConfig,
+    resolver: &DnsResolver,
+    host: &str,
+    port: u16,
+) -> std::result::Result<IpAddr, ResolutionFailure> {
+    if let Ok(literal) = host.parse::<IpAddr>() {
+        return if policy_allows(config, host, Some(literal), Some(port)) {
+            Ok(literal)
+        } else {
+            Err(ResolutionFailure::PolicyDenied)
+        };
+    }
+
+    let candidates = resolver.forward_lookup(host).await;
+    if candidates.is_empty() {
+        return Err(ResolutionFailure::NoAddresses);
+    }
+
+    candidates
+        .into_iter()
+        .find(|ip| policy_allows(config, host, Some(*ip), Some(port)))
+        .ok_or(ResolutionFailure::PolicyDenied)
+}
+
+/// Dial `host:port` directly, through `upstream` (selected by
+/// `UpstreamRouter::select`) if one applies to this destination, or (if
+/// `pinned_addr` is set) directly to that resolved and policy-approved
+/// address instead of re-resolving `host`.
+async fn dial(
+    host: &str,
+    port: u16,
+    pinned_addr: Option<IpAddr>,
+    upstream: Option<Url>,
+) -> ConnectOutcome {
+    debug!("Attempting to connect to {}:{}", host, port);
+    let dial_result = if let Some(ref upstream) = upstream {
+        match upstream.scheme() {
+            "socks5" | "socks5h" => super::socks5_connect::connect_via_upstream(upstream, host, port).await,
+            _ => super::http_connect::connect_via_upstream(upstream, host, port).await,
+        }
+    } else if let Some(ip) = pinned_addr {
+        tokio::net::TcpStream::connect((ip, port))
+            .await
+            .map_err(Into::into)
+    } else {
+        tokio::net::TcpStream::connect(format!("{}:{}", host, port))
+            .await
+            .map_err(Into::into)
+    };
+
+    match dial_result {
+        Ok(remote) => {
+            debug!("Connection succeeded to {}:{}", host, port);
+            ConnectOutcome::Connected(remote)
+        }
+        Err(e) => {
+            // Only log remote connection failures at debug level (not failures)
+            debug!("Remote connection failed to {}:{}: {}", host, port, e);
+            ConnectOutcome::DialFailed
+        }
+    }
+}
+
 /// Handle a single client connection over UDS
 ///
 /// Protocol: Simple text-based CONNECT protocol
 /// Format: "CONNECT host port\n"
 /// Response: "OK\n", "BLOCKED\n", "FAIL\n", or "ERROR\n"
 ///
+/// `bw-relay` also opens a short-lived connection at startup to send
+/// `"VERSION <n>\n"`, checked against `RELAY_PROTOCOL_VERSION` below and
+/// answered with `"VERSION_OK <n>\n"` or `"VERSION_MISMATCH <n>\n"`; that
+/// connection is then closed without ever sending a `CONNECT`.
+///
+/// When bw-relay runs with `--proxy-protocol-v2`, a binary PROXY protocol
+/// v2 header naming the real client endpoint precedes the request line;
+/// see `super::proxy_protocol`.
+///
 /// Filters connections based on the policy engine before allowing them through.
-async fn handle_client(
+async fn handle_client_text(
     mut stream: tokio::net::UnixStream,
     config: ProxyServerConfig,
 ) -> Result<()> {
@@ -138,11 +520,55 @@ async fn handle_client(
         return Ok(());
     }
 
-    let request_str = String::from_utf8_lossy(&buf[..n]);
+    // If bw-relay was run with `--proxy-protocol-v2`, a PROXY protocol v2
+    // header naming the real client endpoint sits ahead of the usual
+    // request line; peel it off and log it before parsing the rest as
+    // before. Most connections won't carry one, and `parse` reports that
+    // with `Ok(None)`, not an error.
+    let (client_endpoint, request_bytes) = match super::proxy_protocol::parse(&buf[..n]) {
+        Ok(Some(header)) => {
+            info!(
+                "PROXY protocol v2: client={} destination={}:{}",
+                header.source,
+                header.destination_host.as_deref().unwrap_or("?"),
+                header.destination_port
+            );
+            (Some(header.source), &buf[header.consumed..n])
+        }
+        Ok(None) => (None, &buf[..n]),
+        Err(e) => {
+            debug!("Malformed PROXY protocol v2 header: {}", e);
+            let _ = stream.write_all(b"ERROR\n").await;
+            return Ok(());
+        }
+    };
+
+    let request_str = String::from_utf8_lossy(request_bytes);
     debug!("Raw request: {:?}", request_str);
     let request_str = request_str.trim();
     debug!("Trimmed request: {:?}", request_str);
 
+    if let Some(version_str) = request_str.strip_prefix("VERSION ") {
+        let reply = match version_str.trim().parse::<u32>() {
+            Ok(client_version) if client_version == RELAY_PROTOCOL_VERSION => {
+                format!("VERSION_OK {RELAY_PROTOCOL_VERSION}\n")
+            }
+            Ok(client_version) => {
+                debug!(
+                    "Relay protocol mismatch: client={}, server={}",
+                    client_version, RELAY_PROTOCOL_VERSION
+                );
+                format!("VERSION_MISMATCH {RELAY_PROTOCOL_VERSION}\n")
+            }
+            Err(_) => {
+                debug!("Invalid VERSION request: {:?}", request_str);
+                "ERROR\n".to_string()
+            }
+        };
+        let _ = stream.write_all(reply.as_bytes()).await;
+        return Ok(());
+    }
+
     if !request_str.starts_with("CONNECT ") {
         debug!("Invalid request format: {:?}", request_str);
         let _ = stream.write_all(b"ERROR\n").await;
@@ -168,55 +594,83 @@ async fn handle_client(
         }
     };
 
-    debug!("CONNECT request: {}:{}", host, port);
-
-    // Apply policy filtering if a policy engine is configured
-    if let Some(ref policy_engine) = config.policy_engine {
-        let allowed = policy_engine.allow(host, None);
-        debug!("Policy check for {}: allowed={}", host, allowed);
+    match client_endpoint {
+        Some(addr) => info!("CONNECT request: {}:{} (client={})", host, port, addr),
+        None => debug!("CONNECT request: {}:{}", host, port),
+    }
 
-        if !allowed {
-            debug!("Connection blocked by policy: {}:{}", host, port);
+    match connect_filtered(&config, host, port).await {
+        ConnectOutcome::Blocked => {
             let _ = stream.write_all(b"BLOCKED\n").await;
-            return Ok(());
+            Ok(())
         }
-    }
-
-    // Record access in learning mode if enabled
-    if let Some(ref learning_recorder) = config.learning_recorder {
-        learning_recorder.record(host, None);
-
-        // Save learning data immediately after recording
-        if let Some(ref output_path) = config.learning_output {
-            if let Err(e) = learning_recorder.save_to_file(output_path) {
-                debug!("Failed to save learning data: {}", e);
-            }
+        ConnectOutcome::DialFailed => {
+            let _ = stream.write_all(b"FAIL\n").await;
+            Ok(())
         }
-    }
+        ConnectOutcome::Connected(mut remote) => {
+            // Optionally peek the ClientHello before acking, so a SNI that
+            // violates policy gets BLOCKED instead of a half-open tunnel.
+            let mut client_hello_prefix = Vec::new();
+            if config.verify_sni {
+                match super::sni::sniff_client_hello(&mut stream).await {
+                    Ok(sniff) => {
+                        client_hello_prefix = sniff.prefix;
+                        match &sniff.server_name {
+                            Some(sni_host) if !policy_allows(&config, sni_host, None, Some(port)) => {
+                                debug!(
+                                    "Connection blocked by SNI policy: {} (CONNECT host was {})",
+                                    sni_host, host
+                                );
+                                let _ = stream.write_all(b"BLOCKED\n").await;
+                                return Ok(());
+                            }
+                            None if sniff.is_tls && config.sni_fallback == SniFallback::Block => {
+                                debug!(
+                                    "Connection blocked: TLS ClientHello for {}:{} had no readable SNI (ECH?) and sni_fallback=Block",
+                                    host, port
+                                );
+                                let _ = stream.write_all(b"BLOCKED\n").await;
+                                return Ok(());
+                            }
+                            _ => {}
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Failed to sniff ClientHello for {}:{}: {}", host, port, e);
+                    }
+                }
+            }
 
-    // Try to connect to the destination
-    debug!("Attempting to connect to {}:{}", host, port);
-    match tokio::net::TcpStream::connect(format!("{}:{}", host, port)).await {
-        Ok(mut remote) => {
-            debug!("Connection succeeded to {}:{}", host, port);
             // Send success response
             stream.write_all(b"OK\n").await?;
             stream.flush().await?;
 
-            // Tunnel data bidirectionally between client and remote
-            if let Err(e) = tokio::io::copy_bidirectional(&mut stream, &mut remote).await {
+            // Replay whatever we already read off the client (the ClientHello,
+            // if sniffing was enabled) before tunneling the rest.
+            if !client_hello_prefix.is_empty() {
+                if let Err(e) = remote.write_all(&client_hello_prefix).await {
+                    debug!("Failed to replay buffered bytes to remote: {}", e);
+                    return Ok(());
+                }
+            }
+
+            // Tunnel data bidirectionally between client and remote, through
+            // a bandwidth-throttling wrapper around the client socket if
+            // `rate_limit` is configured.
+            let tunnel_result = if let Some(rate_limit) = config.rate_limit {
+                let mut throttled = super::throttle::ThrottledStream::new(stream, rate_limit);
+                tokio::io::copy_bidirectional(&mut throttled, &mut remote).await
+            } else {
+                tokio::io::copy_bidirectional(&mut stream, &mut remote).await
+            };
+            if let Err(e) = tunnel_result {
                 debug!("Tunnel error: {}", e);
             }
 
             debug!("Tunnel closed for {}:{}", host, port);
             Ok(())
         }
-        Err(e) => {
-            // Only log remote connection failures at debug level (not failures)
-            debug!("Remote connection failed to {}:{}: {}", host, port, e);
-            let _ = stream.write_all(b"FAIL\n").await;
-            Ok(())
-        }
     }
 }
 
@@ -224,17 +678,35 @@ async fn handle_client(
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-    #[test]
-    fn test_proxy_server_creation() {
-        let socket_path = NamedTempFile::new().unwrap().path().to_path_buf();
-        let config = ProxyServerConfig {
-            socket_path: socket_path.clone(),
+    fn test_config(socket_path: PathBuf) -> ProxyServerConfig {
+        ProxyServerConfig {
+            socket_path,
             network_config: Arc::new(Default::default()),
             policy_engine: None,
             learning_recorder: None,
             learning_output: None,
-        };
+            learning_save_stats: false,
+            learning_max_age: None,
+            persistent: false,
+            protocol: WireProtocol::Text,
+            verify_sni: false,
+            sni_fallback: SniFallback::default(),
+            upstream_router: UpstreamRouter::direct(),
+            graceful_shutdown: false,
+            drain_timeout: std::time::Duration::from_secs(30),
+            resolver: None,
+            rate_limit: None,
+            policy_prompt: None,
+            session_allowlist: SessionAllowlist::default(),
+        }
+    }
+
+    #[test]
+    fn test_proxy_server_creation() {
+        let socket_path = NamedTempFile::new().unwrap().path().to_path_buf();
+        let config = test_config(socket_path.clone());
 
         let server = ProxyServer::new(config);
         // Just verify it can be created without panicking
@@ -243,4 +715,37 @@ mod tests {
             socket_path
         );
     }
+
+    #[tokio::test]
+    async fn test_handle_client_text_version_handshake_ok() {
+        let (mut client, server_stream) = tokio::net::UnixStream::pair().unwrap();
+        let config = test_config(PathBuf::new());
+        tokio::spawn(handle_client_text(server_stream, config));
+
+        client
+            .write_all(format!("VERSION {RELAY_PROTOCOL_VERSION}\n").as_bytes())
+            .await
+            .unwrap();
+        let mut buf = [0u8; 64];
+        let n = client.read(&mut buf).await.unwrap();
+        assert_eq!(
+            &buf[..n],
+            format!("VERSION_OK {RELAY_PROTOCOL_VERSION}\n").as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_client_text_version_handshake_mismatch() {
+        let (mut client, server_stream) = tokio::net::UnixStream::pair().unwrap();
+        let config = test_config(PathBuf::new());
+        tokio::spawn(handle_client_text(server_stream, config));
+
+        client.write_all(b"VERSION 999\n").await.unwrap();
+        let mut buf = [0u8; 64];
+        let n = client.read(&mut buf).await.unwrap();
+        assert_eq!(
+            &buf[..n],
+            format!("VERSION_MISMATCH {RELAY_PROTOCOL_VERSION}\n").as_bytes()
+        );
+    }
 }
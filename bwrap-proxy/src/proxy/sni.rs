@@ -0,0 +1,139 @@
+//! Peek the client's TLS ClientHello and extract its SNI `server_name`
+//!
+//! `connect_filtered` only ever sees the hostname the client *said* it
+//! wanted in its CONNECT line; a client that dials an allowed host while
+//! presenting a different SNI to the remote can use that to tunnel past
+//! host-based policy. When `ProxyServerConfig::verify_sni` is set, the
+//! wire-protocol handlers call [`sniff_client_hello`] after dialing but
+//! before acking the tunnel, and run the extracted SNI through the policy
+//! engine too (see `server::policy_allows`).
+//!
+//! Parsing the ClientHello itself is delegated to the `tls-parser` crate
+//! rather than hand-rolled, since TLS record/handshake framing is exactly
+//! the kind of format where a hand-rolled parser quietly breaks on the
+//! next ClientHello shape a real client sends.
+
+use crate::error::Result;
+use tls_parser::{
+    parse_tls_extensions, parse_tls_plaintext, TlsExtension, TlsMessage, TlsMessageHandshake,
+};
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixStream;
+
+/// Refuse to buffer more than this much of the client's first flight before
+/// giving up on sniffing it; a real ClientHello is a few hundred bytes to a
+/// few KB (longer with large certificate-related extensions), so anything
+/// past this is either not TLS or not worth blocking the connection over.
+const MAX_CLIENT_HELLO_BYTES: usize = 16 * 1024;
+
+/// Result of peeking the client's first flight: the raw bytes read (which
+/// must be replayed to the remote before tunneling, since they were
+/// consumed off the client socket) and the SNI `server_name`, if the first
+/// record was a TLS ClientHello that carried one.
+pub(super) struct ClientHelloSniff {
+    pub prefix: Vec<u8>,
+    pub server_name: Option<String>,
+    /// Whether `prefix` actually started with a TLS handshake record
+    /// (`ContentType::Handshake == 0x16`). Lets callers tell "this wasn't
+    /// TLS, nothing to check" apart from "this was TLS but we couldn't read
+    /// an SNI" (e.g. Encrypted Client Hello, where `server_name` is
+    /// genuinely unreadable) — see `ProxyServerConfig::sni_fallback`.
+    pub is_tls: bool,
+}
+
+/// Read from `stream` until a complete TLS ClientHello has been buffered (or
+/// `MAX_CLIENT_HELLO_BYTES` is hit), and extract its SNI `server_name`.
+///
+/// Returns `Ok` with `server_name: None` — not an error — whenever the
+/// client's first flight isn't a recognizable TLS ClientHello (not TLS at
+/// all, or truncated past the byte cap): callers should let the connection
+/// through and just replay `prefix` untouched, since refusing non-TLS
+/// traffic isn't this function's job.
+pub(super) async fn sniff_client_hello(stream: &mut UnixStream) -> Result<ClientHelloSniff> {
+    let mut buf = Vec::new();
+
+    loop {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        // Not a TLS handshake record at all (ContentType::Handshake == 0x16):
+        // nothing more to learn by reading further.
+        if buf.first() != Some(&0x16) {
+            break;
+        }
+
+        match parse_tls_plaintext(&buf) {
+            Ok((_, plaintext)) => {
+                let server_name = plaintext.msg.iter().find_map(extract_sni);
+                return Ok(ClientHelloSniff {
+                    prefix: buf,
+                    server_name,
+                    is_tls: true,
+                });
+            }
+            Err(nom::Err::Incomplete(_)) if buf.len() < MAX_CLIENT_HELLO_BYTES => continue,
+            Err(_) => break,
+        }
+    }
+
+    let is_tls = buf.first() == Some(&0x16);
+    Ok(ClientHelloSniff {
+        prefix: buf,
+        server_name: None,
+        is_tls,
+    })
+}
+
+/// Pull the `host_name` SNI entry out of a single parsed TLS message, if it
+/// is a ClientHello carrying a `server_name` extension.
+fn extract_sni(msg: &TlsMessage) -> Option<String> {
+    let TlsMessage::Handshake(TlsMessageHandshake::ClientHello(hello)) = msg else {
+        return None;
+    };
+    let ext = hello.ext?;
+    let (_, extensions) = parse_tls_extensions(ext).ok()?;
+
+    extensions.into_iter().find_map(|extension| {
+        let TlsExtension::SNI(names) = extension else {
+            return None;
+        };
+        names.into_iter().find_map(|(_sni_type, name)| {
+            std::str::from_utf8(name).ok().map(|s| s.to_string())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_non_tls_first_byte_is_not_reported_as_tls() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        drop(client);
+
+        let sniff = sniff_client_hello(&mut server).await.unwrap();
+        assert!(!sniff.is_tls);
+        assert_eq!(sniff.server_name, None);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_tls_record_is_reported_as_tls_with_no_sni() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        // A handshake-record header (content type 0x16) with a body that
+        // never completes into a parseable ClientHello — e.g. an Encrypted
+        // Client Hello's outer SNI being unreadable by this code.
+        client.write_all(&[0x16, 0x03, 0x01, 0x00, 0x05, 0xAA, 0xAA, 0xAA]).await.unwrap();
+        drop(client);
+
+        let sniff = sniff_client_hello(&mut server).await.unwrap();
+        assert!(sniff.is_tls);
+        assert_eq!(sniff.server_name, None);
+    }
+}
@@ -0,0 +1,267 @@
+//! RFC 1928 SOCKS5 wire protocol over the proxy's Unix domain socket
+//!
+//! An alternative to the ad-hoc `"CONNECT host port\n"` text protocol in
+//! `server::handle_client_text`, so off-the-shelf SOCKS5 clients (and
+//! `bw-relay`) can connect without a custom shim. Selected per-server via
+//! `ProxyServerConfig::protocol`; shares policy-checking, learning, and
+//! dialing with the text protocol through `server::connect_filtered`.
+
+use super::server::{connect_filtered, policy_allows, ConnectOutcome, ProxyServerConfig, SniFallback};
+use crate::error::{ProxyError, Result};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tracing::debug;
+
+const VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+const REP_SUCCEEDED: u8 = 0x00;
+const REP_NOT_ALLOWED_BY_RULESET: u8 = 0x02;
+const REP_CONNECTION_REFUSED: u8 = 0x05;
+const REP_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REP_ADDRESS_TYPE_NOT_SUPPORTED: u8 = 0x08;
+
+/// Handle a single client connection as a SOCKS5 server: greeting, no-auth
+/// method selection, the CONNECT request, then the same policy check,
+/// learning record, and tunnel as the text protocol.
+pub(super) async fn handle_client(mut stream: UnixStream, config: ProxyServerConfig) -> Result<()> {
+    if let Err(e) = handshake(&mut stream).await {
+        debug!("SOCKS5 handshake failed: {}", e);
+        return Ok(());
+    }
+
+    let (host, port) = match read_connect_request(&mut stream).await {
+        Ok(Some(dest)) => dest,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            debug!("SOCKS5 request parse failed: {}", e);
+            return Ok(());
+        }
+    };
+
+    debug!("SOCKS5 CONNECT request: {}:{}", host, port);
+
+    match connect_filtered(&config, &host, port).await {
+        ConnectOutcome::Blocked => {
+            let _ = write_reply(&mut stream, REP_NOT_ALLOWED_BY_RULESET).await;
+            Ok(())
+        }
+        ConnectOutcome::DialFailed => {
+            let _ = write_reply(&mut stream, REP_CONNECTION_REFUSED).await;
+            Ok(())
+        }
+        ConnectOutcome::Connected(mut remote) => {
+            let mut client_hello_prefix = Vec::new();
+            if config.verify_sni {
+                match super::sni::sniff_client_hello(&mut stream).await {
+                    Ok(sniff) => {
+                        client_hello_prefix = sniff.prefix;
+                        match &sniff.server_name {
+                            Some(sni_host) if !policy_allows(&config, sni_host, None, Some(port)) => {
+                                debug!(
+                                    "Connection blocked by SNI policy: {} (CONNECT host was {})",
+                                    sni_host, host
+                                );
+                                let _ = write_reply(&mut stream, REP_NOT_ALLOWED_BY_RULESET).await;
+                                return Ok(());
+                            }
+                            None if sniff.is_tls && config.sni_fallback == SniFallback::Block => {
+                                debug!(
+                                    "Connection blocked: TLS ClientHello for {}:{} had no readable SNI (ECH?) and sni_fallback=Block",
+                                    host, port
+                                );
+                                let _ = write_reply(&mut stream, REP_NOT_ALLOWED_BY_RULESET).await;
+                                return Ok(());
+                            }
+                            _ => {}
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Failed to sniff ClientHello for {}:{}: {}", host, port, e);
+                    }
+                }
+            }
+
+            write_reply(&mut stream, REP_SUCCEEDED).await?;
+
+            if !client_hello_prefix.is_empty() {
+                if let Err(e) = remote.write_all(&client_hello_prefix).await {
+                    debug!("Failed to replay buffered bytes to remote: {}", e);
+                    return Ok(());
+                }
+            }
+
+            let tunnel_result = if let Some(rate_limit) = config.rate_limit {
+                let mut throttled = super::throttle::ThrottledStream::new(stream, rate_limit);
+                tokio::io::copy_bidirectional(&mut throttled, &mut remote).await
+            } else {
+                tokio::io::copy_bidirectional(&mut stream, &mut remote).await
+            };
+            if let Err(e) = tunnel_result {
+                debug!("Tunnel error: {}", e);
+            }
+            debug!("Tunnel closed for {}:{}", host, port);
+            Ok(())
+        }
+    }
+}
+
+/// Read the greeting (`VER NMETHODS METHODS...`) and reply that we only
+/// support the no-auth method, regardless of what the client offered —
+/// same posture as every other wire protocol this proxy speaks.
+async fn handshake(stream: &mut UnixStream) -> Result<()> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    let [version, nmethods] = header;
+    if version != VERSION {
+        return Err(ProxyError::Socks5(format!("unsupported SOCKS version: {version}")));
+    }
+
+    let mut methods = vec![0u8; nmethods as usize];
+    stream.read_exact(&mut methods).await?;
+
+    stream.write_all(&[VERSION, METHOD_NO_AUTH]).await?;
+    Ok(())
+}
+
+/// Read the `VER CMD RSV ATYP DST.ADDR DST.PORT` request. Returns `Ok(None)`
+/// after already replying to an unsupported command or address type, since
+/// there's nothing left for the caller to connect.
+async fn read_connect_request(stream: &mut UnixStream) -> Result<Option<(String, u16)>> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let [version, cmd, _rsv, atyp] = header;
+
+    if version != VERSION {
+        return Err(ProxyError::Socks5(format!("unsupported SOCKS version: {version}")));
+    }
+
+    if cmd != CMD_CONNECT {
+        let _ = write_reply(stream, REP_COMMAND_NOT_SUPPORTED).await;
+        return Ok(None);
+    }
+
+    let host = match atyp {
+        ATYP_IPV4 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            Ipv4Addr::from(addr).to_string()
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            let mut domain = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut domain).await?;
+            String::from_utf8(domain)
+                .map_err(|e| ProxyError::Socks5(format!("invalid domain name: {e}")))?
+        }
+        ATYP_IPV6 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            Ipv6Addr::from(addr).to_string()
+        }
+        _ => {
+            let _ = write_reply(stream, REP_ADDRESS_TYPE_NOT_SUPPORTED).await;
+            return Ok(None);
+        }
+    };
+
+    let mut port_bytes = [0u8; 2];
+    stream.read_exact(&mut port_bytes).await?;
+    let port = u16::from_be_bytes(port_bytes);
+
+    Ok(Some((host, port)))
+}
+
+/// Reply `VER REP RSV ATYP BND.ADDR BND.PORT`, with an all-zero IPv4
+/// bind address: this proxy doesn't expose (or need to report) a real
+/// bind address for the tunnel it just opened.
+async fn write_reply(stream: &mut UnixStream, rep: u8) -> Result<()> {
+    let reply = [VERSION, rep, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0];
+    stream.write_all(&reply).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixStream as TokioUnixStream;
+
+    async fn connected_pair() -> (TokioUnixStream, TokioUnixStream) {
+        TokioUnixStream::pair().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handshake_replies_no_auth() {
+        let (mut client, mut server) = connected_pair().await;
+
+        client.write_all(&[VERSION, 1, METHOD_NO_AUTH]).await.unwrap();
+        handshake(&mut server).await.unwrap();
+
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [VERSION, METHOD_NO_AUTH]);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_wrong_version() {
+        let (mut client, mut server) = connected_pair().await;
+
+        client.write_all(&[0x04, 1, METHOD_NO_AUTH]).await.unwrap();
+        let result = handshake(&mut server).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_connect_request_parses_domain() {
+        let (mut client, mut server) = connected_pair().await;
+
+        let domain = b"example.com";
+        let mut request = vec![VERSION, CMD_CONNECT, 0x00, ATYP_DOMAIN, domain.len() as u8];
+        request.extend_from_slice(domain);
+        request.extend_from_slice(&443u16.to_be_bytes());
+        client.write_all(&request).await.unwrap();
+
+        let (host, port) = read_connect_request(&mut server).await.unwrap().unwrap();
+        assert_eq!(host, "example.com");
+        assert_eq!(port, 443);
+    }
+
+    #[tokio::test]
+    async fn test_read_connect_request_parses_ipv4() {
+        let (mut client, mut server) = connected_pair().await;
+
+        let mut request = vec![VERSION, CMD_CONNECT, 0x00, ATYP_IPV4];
+        request.extend_from_slice(&[93, 184, 216, 34]);
+        request.extend_from_slice(&80u16.to_be_bytes());
+        client.write_all(&request).await.unwrap();
+
+        let (host, port) = read_connect_request(&mut server).await.unwrap().unwrap();
+        assert_eq!(host, "93.184.216.34");
+        assert_eq!(port, 80);
+    }
+
+    #[tokio::test]
+    async fn test_read_connect_request_rejects_unsupported_command() {
+        let (mut client, mut server) = connected_pair().await;
+
+        // CMD=0x02 (BIND) isn't supported
+        let mut request = vec![VERSION, 0x02, 0x00, ATYP_IPV4];
+        request.extend_from_slice(&[0, 0, 0, 0]);
+        request.extend_from_slice(&0u16.to_be_bytes());
+        client.write_all(&request).await.unwrap();
+
+        let result = read_connect_request(&mut server).await.unwrap();
+        assert!(result.is_none());
+
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], REP_COMMAND_NOT_SUPPORTED);
+    }
+}
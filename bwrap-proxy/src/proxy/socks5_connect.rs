@@ -0,0 +1,129 @@
+//! Dial a destination through an upstream SOCKS5 proxy
+//!
+//! The SOCKS5 counterpart to `super::http_connect`: when `UpstreamRouter`
+//! selects a `socks5://`/`socks5h://` upstream, `dial` hands the connection
+//! off to [`connect_via_upstream`] instead of calling `TcpStream::connect`
+//! directly. Speaks the no-auth and username/password (RFC 1929) methods;
+//! always requests the CONNECT command with a domain-name address so the
+//! upstream does its own resolution.
+
+use crate::error::{ProxyError, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+const VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USER_PASS: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xFF;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Connect to `host:port` by tunneling through the SOCKS5 proxy at
+/// `upstream`, returning the established stream ready for
+/// `copy_bidirectional`. Credentials in `upstream`'s userinfo, if present,
+/// are offered via the username/password auth method.
+pub(super) async fn connect_via_upstream(upstream: &Url, host: &str, port: u16) -> Result<TcpStream> {
+    let upstream_host = upstream
+        .host_str()
+        .ok_or_else(|| ProxyError::Network(format!("upstream proxy URL has no host: {upstream}")))?;
+    let upstream_port = upstream
+        .port_or_known_default()
+        .ok_or_else(|| ProxyError::Network(format!("upstream proxy URL has no port: {upstream}")))?;
+
+    let mut stream = TcpStream::connect((upstream_host, upstream_port)).await?;
+
+    let use_auth = !upstream.username().is_empty();
+    let methods: &[u8] = if use_auth {
+        &[METHOD_NO_AUTH, METHOD_USER_PASS]
+    } else {
+        &[METHOD_NO_AUTH]
+    };
+    let mut greeting = vec![VERSION, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream.write_all(&greeting).await?;
+
+    let mut method_reply = [0u8; 2];
+    stream.read_exact(&mut method_reply).await?;
+    if method_reply[0] != VERSION {
+        return Err(ProxyError::Network(format!(
+            "upstream SOCKS5 proxy spoke an unexpected protocol version: {}",
+            method_reply[0]
+        )));
+    }
+
+    match method_reply[1] {
+        METHOD_NO_AUTH => {}
+        METHOD_USER_PASS if use_auth => authenticate(&mut stream, upstream).await?,
+        METHOD_NO_ACCEPTABLE => {
+            return Err(ProxyError::Network(
+                "upstream SOCKS5 proxy accepted none of the offered auth methods".to_string(),
+            ));
+        }
+        other => {
+            return Err(ProxyError::Network(format!(
+                "upstream SOCKS5 proxy selected an unsupported auth method: {other}"
+            )));
+        }
+    }
+
+    let host_bytes = host.as_bytes();
+    let mut request = vec![VERSION, CMD_CONNECT, 0x00, ATYP_DOMAIN, host_bytes.len() as u8];
+    request.extend_from_slice(host_bytes);
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply_header = [0u8; 4];
+    stream.read_exact(&mut reply_header).await?;
+    if reply_header[1] != 0x00 {
+        return Err(ProxyError::Network(format!(
+            "upstream SOCKS5 proxy refused CONNECT {host}:{port}: reply code {}",
+            reply_header[1]
+        )));
+    }
+
+    // Drain the bound-address portion of the reply so it isn't left
+    // sitting in front of the tunneled bytes once we hand the stream back.
+    let bound_addr_len = match reply_header[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            len[0] as usize
+        }
+        other => {
+            return Err(ProxyError::Network(format!(
+                "upstream SOCKS5 proxy reply used an unknown address type: {other}"
+            )));
+        }
+    };
+    let mut trailer = vec![0u8; bound_addr_len + 2];
+    stream.read_exact(&mut trailer).await?;
+
+    Ok(stream)
+}
+
+/// RFC 1929 username/password sub-negotiation, run after the method
+/// selection picks `METHOD_USER_PASS`.
+async fn authenticate(stream: &mut TcpStream, upstream: &Url) -> Result<()> {
+    let username = upstream.username().as_bytes();
+    let password = upstream.password().unwrap_or("").as_bytes();
+
+    let mut request = vec![0x01, username.len() as u8];
+    request.extend_from_slice(username);
+    request.push(password.len() as u8);
+    request.extend_from_slice(password);
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0x00 {
+        return Err(ProxyError::Network(
+            "upstream SOCKS5 proxy rejected the offered credentials".to_string(),
+        ));
+    }
+    Ok(())
+}
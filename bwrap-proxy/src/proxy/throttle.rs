@@ -0,0 +1,183 @@
+//! Per-connection bandwidth throttling for the tunnel
+//!
+//! [`ThrottledStream`] wraps the client-facing Unix domain socket before it
+//! is handed to `tokio::io::copy_bidirectional`, so a single token bucket
+//! per direction caps how fast a sandboxed connection can move bytes
+//! without the sandbox's agent being able to saturate the host link.
+//! Wrapping only the client side (rather than both client and remote) is
+//! enough: every byte of both directions passes through that one socket.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+/// Bandwidth caps for a tunneled connection, in bytes/sec
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Cap on bytes read from the client (upload)
+    pub up_bytes_per_sec: u64,
+    /// Cap on bytes written to the client (download)
+    pub down_bytes_per_sec: u64,
+}
+
+impl RateLimit {
+    /// The same cap in both directions
+    pub fn symmetric(bytes_per_sec: u64) -> Self {
+        Self {
+            up_bytes_per_sec: bytes_per_sec,
+            down_bytes_per_sec: bytes_per_sec,
+        }
+    }
+}
+
+/// A token bucket refilled at `rate` tokens/sec up to a `burst` cap;
+/// consuming more tokens than are available reports the wait needed for
+/// the shortfall to refill instead of blocking.
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// A bucket whose burst capacity equals one second's worth of `rate`,
+    /// starting full so the first read/write isn't immediately throttled.
+    fn new(rate_bytes_per_sec: u64) -> Self {
+        let rate = rate_bytes_per_sec as f64;
+        Self {
+            rate,
+            burst: rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Consume `n` tokens, refilling first. Returns the duration to sleep
+    /// for if `n` exceeded what was available; the bucket is left at zero
+    /// in that case (a shortfall never goes negative).
+    fn consume(&mut self, n: usize) -> Option<Duration> {
+        self.refill();
+        let n = n as f64;
+        if self.tokens >= n {
+            self.tokens -= n;
+            None
+        } else {
+            let shortfall = n - self.tokens;
+            self.tokens = 0.0;
+            Some(Duration::from_secs_f64(shortfall / self.rate))
+        }
+    }
+}
+
+/// Wraps an `AsyncRead + AsyncWrite` stream, throttling reads (upload) and
+/// writes (download) against independent token buckets.
+///
+/// Throttling is applied after each underlying read/write completes rather
+/// than before: since the size of the next chunk isn't known in advance, we
+/// let it through and then charge the bucket for what was actually
+/// transferred, sleeping off any shortfall before the *next* I/O op is
+/// allowed to proceed. Over many chunks this converges to the configured
+/// rate the same way a pre-charged bucket would.
+pub struct ThrottledStream<S> {
+    inner: S,
+    read_bucket: TokenBucket,
+    write_bucket: TokenBucket,
+    read_sleep: Option<Pin<Box<Sleep>>>,
+    write_sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> ThrottledStream<S> {
+    pub fn new(inner: S, rate_limit: RateLimit) -> Self {
+        Self {
+            inner,
+            read_bucket: TokenBucket::new(rate_limit.up_bytes_per_sec),
+            write_bucket: TokenBucket::new(rate_limit.down_bytes_per_sec),
+            read_sleep: None,
+            write_sleep: None,
+        }
+    }
+}
+
+/// Poll a pending throttle delay, if any, to completion. Returns `true` once
+/// there is no delay left to wait out (either there wasn't one, or it just
+/// elapsed), `false` if the caller should return `Poll::Pending`.
+fn poll_delay(cx: &mut Context<'_>, sleep: &mut Option<Pin<Box<Sleep>>>) -> bool {
+    match sleep {
+        Some(s) => match s.as_mut().poll(cx) {
+            Poll::Ready(_) => {
+                *sleep = None;
+                true
+            }
+            Poll::Pending => false,
+        },
+        None => true,
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ThrottledStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if !poll_delay(cx, &mut this.read_sleep) {
+            return Poll::Pending;
+        }
+
+        let before = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let transferred = buf.filled().len() - before;
+            if transferred > 0 {
+                this.read_sleep = this
+                    .read_bucket
+                    .consume(transferred)
+                    .map(|d| Box::pin(tokio::time::sleep(d)));
+            }
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ThrottledStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        if !poll_delay(cx, &mut this.write_sleep) {
+            return Poll::Pending;
+        }
+
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            if n > 0 {
+                this.write_sleep = this
+                    .write_bucket
+                    .consume(n)
+                    .map(|d| Box::pin(tokio::time::sleep(d)));
+            }
+        }
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,94 @@
+//! Per-destination upstream proxy selection
+//!
+//! `ProxyServerConfig::upstream_router` decides, for each destination
+//! `connect_filtered` is about to dial, whether to connect directly or
+//! hand the dial off to a further upstream proxy (see `super::http_connect`
+//! and `super::socks5_connect` for the two upstream schemes `dial`
+//! supports). This lets sandbox egress be chained through a mandated
+//! gateway while the local policy engine still makes the allow/deny call.
+
+use crate::filter::HostMatcher;
+use url::Url;
+
+/// A compiled, ready-to-query set of upstream routing rules
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamRouter {
+    /// `(host pattern matcher, upstream url)` pairs; the most specific
+    /// match (see `HostMatcher::matches_with_specificity`) wins
+    rules: Vec<(HostMatcher, Url)>,
+    /// Upstream used when no `rules` entry matches `host` (or there are no
+    /// rules at all)
+    fallback: Option<Url>,
+}
+
+impl UpstreamRouter {
+    /// No upstream configured: every destination is dialed directly
+    pub fn direct() -> Self {
+        Self::default()
+    }
+
+    /// Every destination is dialed through this one upstream
+    pub fn global(url: Url) -> Self {
+        Self {
+            rules: Vec::new(),
+            fallback: Some(url),
+        }
+    }
+
+    /// Per-domain upstream selection, falling back to `fallback` (if any)
+    /// for destinations no rule matches
+    pub fn by_domain(rules: Vec<(HostMatcher, Url)>, fallback: Option<Url>) -> Self {
+        Self { rules, fallback }
+    }
+
+    /// The upstream `host` should be dialed through, if any: the most
+    /// specific matching rule wins, falling back to this router's default
+    /// upstream (if set), then to `None` (dial `host` directly).
+    pub fn select(&self, host: &str) -> Option<&Url> {
+        self.rules
+            .iter()
+            .filter_map(|(matcher, url)| {
+                matcher.matches_with_specificity(host).map(|spec| (spec, url))
+            })
+            .max_by_key(|(spec, _)| *spec)
+            .map(|(_, url)| url)
+            .or(self.fallback.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(pattern: &str) -> HostMatcher {
+        let mut m = HostMatcher::new();
+        m.add_pattern(pattern);
+        m
+    }
+
+    #[test]
+    fn direct_router_selects_nothing() {
+        let router = UpstreamRouter::direct();
+        assert!(router.select("example.com").is_none());
+    }
+
+    #[test]
+    fn global_router_selects_everything() {
+        let upstream = Url::parse("http://proxy:8080").unwrap();
+        let router = UpstreamRouter::global(upstream.clone());
+        assert_eq!(router.select("anything.example.com"), Some(&upstream));
+    }
+
+    #[test]
+    fn by_domain_prefers_more_specific_match_and_falls_back() {
+        let global = Url::parse("http://global:8080").unwrap();
+        let specific = Url::parse("socks5://internal:1080").unwrap();
+        let router = UpstreamRouter::by_domain(
+            vec![(matcher("*.api.example.com"), specific.clone())],
+            Some(global.clone()),
+        );
+
+        assert_eq!(router.select("svc.api.example.com"), Some(&specific));
+        assert_eq!(router.select("unrelated.org"), Some(&global));
+    }
+}